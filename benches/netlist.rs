@@ -0,0 +1,108 @@
+//! Netlist benchmarks with scaled up schemas.
+//!
+//! The items of the fixtures are copied side by side on a single sheet,
+//! `echo x100` has 100 copies of the echo schema with about 18000 wires.
+//!
+//! ```sh
+//! cargo bench --bench netlist
+//! ```
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use recad_core::{
+    gr::Pos,
+    netlist::Netlist,
+    schema::SchemaItem,
+    Schema,
+};
+
+/// The offset of the copies in mm.
+const WIDTH: f32 = 508.0;
+const HEIGHT: f32 = 381.0;
+
+fn shift(pos: &mut Pos, x: f32, y: f32) {
+    pos.x += x;
+    pos.y += y;
+}
+
+/// Renumber the reference for the copy, `R1` is `R1001` in the second copy.
+fn renumber(reference: &str, copy: usize) -> String {
+    let digits = reference.trim_start_matches(|c: char| !c.is_ascii_digit());
+    match digits.parse::<usize>() {
+        Ok(number) => format!(
+            "{}{}",
+            &reference[..reference.len() - digits.len()],
+            copy * 1000 + number
+        ),
+        Err(_) => reference.to_string(),
+    }
+}
+
+/// Load the schema with `count` copies of the items, the copies are placed in a square grid
+/// and every copy has its own references.
+fn scaled(path: &Path, count: usize) -> Schema {
+    let mut scaled = Schema::load(path).unwrap();
+    scaled.items.clear();
+    let columns = (count as f32).sqrt().ceil() as usize;
+    for index in 0..count {
+        let x = (index % columns) as f32 * WIDTH;
+        let y = (index / columns) as f32 * HEIGHT;
+        for mut item in Schema::load(path).unwrap().items {
+            match &mut item {
+                SchemaItem::Wire(wire) => wire.pts.0.iter_mut().for_each(|pt| {
+                    pt.x += x;
+                    pt.y += y;
+                }),
+                SchemaItem::Bus(bus) => bus.pts.0.iter_mut().for_each(|pt| {
+                    pt.x += x;
+                    pt.y += y;
+                }),
+                SchemaItem::BusEntry(entry) => shift(&mut entry.pos, x, y),
+                SchemaItem::GlobalLabel(label) => shift(&mut label.pos, x, y),
+                SchemaItem::HierarchicalLabel(label) => shift(&mut label.pos, x, y),
+                SchemaItem::LocalLabel(label) => shift(&mut label.pos, x, y),
+                SchemaItem::Junction(junction) => shift(&mut junction.pos, x, y),
+                SchemaItem::NoConnect(nc) => shift(&mut nc.pos, x, y),
+                SchemaItem::NetclassFlag(flag) => shift(&mut flag.pos, x, y),
+                SchemaItem::Symbol(symbol) => {
+                    shift(&mut symbol.pos, x, y);
+                    let reference = renumber(&symbol.property("Reference"), index);
+                    symbol.set_property("Reference", &reference);
+                    symbol.instances.iter_mut().for_each(|i| i.reference = reference.clone());
+                }
+                _ => continue,
+            }
+            scaled.items.push(item);
+        }
+    }
+    scaled
+}
+
+fn netlist(c: &mut Criterion) {
+    let mut group = c.benchmark_group("netlist");
+    group.sample_size(10);
+    for name in ["echo", "all_elements"] {
+        let path = format!("tests/{}/{}.kicad_sch", name, name);
+        for count in [1, 10, 100] {
+            let schema = scaled(Path::new(&path), count);
+            group.bench_with_input(BenchmarkId::new(name, format!("x{}", count)), &schema, |b, schema| {
+                b.iter(|| Netlist::from(schema).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn queries(c: &mut Criterion) {
+    let mut group = c.benchmark_group("queries");
+    group.sample_size(10);
+    let schema = scaled(Path::new("tests/echo/echo.kicad_sch"), 100);
+    let netlist = Netlist::from(&schema).unwrap();
+    group.bench_function("echo/x100/pin_net", |b| {
+        b.iter(|| netlist.pin_net("R1", "1").map(|net| net.pins.len()))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, netlist, queries);
+criterion_main!(benches);
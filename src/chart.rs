@@ -0,0 +1,583 @@
+//! Plot simulation results as charts.
+//!
+//! A [`Chart`] has an x and y axis with linear or logarithmic scale and one or more traces.
+//! The chart is drawn with the same [`Plotter`] backends and [`Theme`] as the schema.
+//! [`Chart::waveform`] and [`Chart::bode`] create the charts from a [`RawPlot`].
+//!
+//! ```
+//! use std::path::Path;
+//! use recad_core::{
+//!     chart::Chart,
+//!     plot::{PlotCommand, Plotter, SvgPlotter},
+//!     rawfile::RawFile,
+//! };
+//!
+//! let raw = RawFile::load(Path::new("tests/spice/rc.raw")).unwrap();
+//! let chart = Chart::waveform(raw.plot("Transient Analysis").unwrap(), &["in", "out"]).unwrap();
+//!
+//! let mut svg = SvgPlotter::new();
+//! chart.plot(&mut svg, PlotCommand::default()).unwrap();
+//! svg.save(Path::new("target/out/rc_tran.svg")).unwrap();
+//! ```
+use crate::{
+    gr::{Effects, Font, Justify, Pos, Pt, Pts, Rect},
+    plot::{
+        theme::{Style, Theme},
+        Paint, PlotCommand, Plotter,
+    },
+    rawfile::{RawPlot, Vector},
+    Error,
+};
+
+/// The margins around the plot area: left, top, right and bottom.
+const MARGINS: (f32, f32, f32, f32) = (18.0, 10.0, 6.0, 14.0);
+/// The number of ticks for a linear axis.
+const TICKS: usize = 5;
+
+/// The scale of an axis.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AxisScale {
+    #[default]
+    Linear,
+    Log,
+}
+
+/// An axis of the chart.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Axis {
+    pub label: String,
+    pub scale: AxisScale,
+    /// The range of the axis, taken from the traces when it is not set.
+    pub range: Option<(f64, f64)>,
+}
+
+impl Axis {
+    /// The value in the scale of the axis.
+    fn value(&self, value: f64) -> f64 {
+        match self.scale {
+            AxisScale::Linear => value,
+            AxisScale::Log => value.log10(),
+        }
+    }
+
+    /// Test if the value can be shown on the axis.
+    fn valid(&self, value: f64) -> bool {
+        value.is_finite() && (self.scale == AxisScale::Linear || value > 0.0)
+    }
+
+    /// Get the range and the ticks of the axis for the values.
+    ///
+    /// A fixed range of a log axis is clamped to the smallest and the largest
+    /// positive value when its bounds are not positive.
+    fn ticks<'a>(&self, values: impl Iterator<Item = &'a f64>) -> ((f64, f64), Vec<f64>) {
+        let data = || {
+            values
+                .filter(|v| self.valid(**v))
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+                    (min.min(*v), max.max(*v))
+                })
+        };
+        let (min, max) = match self.range {
+            Some((min, max)) if self.scale == AxisScale::Log && (min <= 0.0 || max <= 0.0) => {
+                let (data_min, data_max) = data();
+                (
+                    if min > 0.0 { min } else { data_min },
+                    if max > 0.0 { max } else { data_max },
+                )
+            }
+            Some(range) => range,
+            None => data(),
+        };
+        match self.scale {
+            AxisScale::Linear => {
+                let (min, max) = if min > max {
+                    (0.0, 1.0)
+                } else if min == max {
+                    (min - 1.0, max + 1.0)
+                } else {
+                    (min, max)
+                };
+                let step = nice_step((max - min) / TICKS as f64);
+                let (min, max) = if self.range.is_some() {
+                    (min, max)
+                } else {
+                    ((min / step).floor() * step, (max / step).ceil() * step)
+                };
+                let first = (min / step).ceil() as i64;
+                let last = (max / step).floor() as i64;
+                ((min, max), (first..=last).map(|i| i as f64 * step).collect())
+            }
+            AxisScale::Log => {
+                let (min, max) = if min > max || !min.is_finite() || !max.is_finite() {
+                    (1.0, 10.0)
+                } else {
+                    (min, max)
+                };
+                let (mut first, mut last) = (min.log10().floor() as i32, max.log10().ceil() as i32);
+                if first == last {
+                    first -= 1;
+                    last += 1;
+                }
+                let (min, max) = if self.range.is_some() {
+                    (min, max)
+                } else {
+                    (10f64.powi(first), 10f64.powi(last))
+                };
+                let ticks = (first..=last)
+                    .map(|e| 10f64.powi(e))
+                    .filter(|t| *t >= min * 0.999_999 && *t <= max * 1.000_001)
+                    .collect();
+                ((min, max), ticks)
+            }
+        }
+    }
+}
+
+/// Get a step of 1, 2 or 5 times a power of ten.
+fn nice_step(step: f64) -> f64 {
+    let magnitude = 10f64.powf(step.log10().floor());
+    let normalized = step / magnitude;
+    let nice = if normalized <= 1.0 {
+        1.0
+    } else if normalized <= 2.0 {
+        2.0
+    } else if normalized <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    nice * magnitude
+}
+
+/// Format the value with the SI prefix, e.g. `1.5k` or `100u`.
+fn si(value: f64) -> String {
+    const PREFIXES: [(f64, &str); 10] = [
+        (1e12, "T"),
+        (1e9, "G"),
+        (1e6, "M"),
+        (1e3, "k"),
+        (1.0, ""),
+        (1e-3, "m"),
+        (1e-6, "u"),
+        (1e-9, "n"),
+        (1e-12, "p"),
+        (1e-15, "f"),
+    ];
+    if value.abs() < 1e-18 {
+        return String::from("0");
+    }
+    for (scale, prefix) in PREFIXES {
+        if value.abs() >= scale * 0.999_999 {
+            let number = format!("{:.3}", value / scale);
+            let number = number.trim_end_matches('0').trim_end_matches('.');
+            return format!("{}{}", number, prefix);
+        }
+    }
+    format!("{:e}", value)
+}
+
+/// A trace of the chart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub label: String,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+}
+
+/// A chart with the simulation results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chart {
+    pub title: String,
+    pub x_axis: Axis,
+    pub y_axis: Axis,
+    pub traces: Vec<Trace>,
+    /// Draw the grid lines at the ticks.
+    pub grid: bool,
+    /// Draw the legend with the trace labels.
+    pub legend: bool,
+    /// The size of the chart in mm.
+    pub size: (f32, f32),
+}
+
+impl Chart {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            x_axis: Axis::default(),
+            y_axis: Axis::default(),
+            traces: Vec::new(),
+            grid: true,
+            legend: true,
+            size: (160.0, 100.0),
+        }
+    }
+
+    /// Set the label and the scale of the x axis.
+    pub fn x_axis(mut self, label: &str, scale: AxisScale) -> Self {
+        self.x_axis.label = label.to_string();
+        self.x_axis.scale = scale;
+        self
+    }
+
+    /// Set the label and the scale of the y axis.
+    pub fn y_axis(mut self, label: &str, scale: AxisScale) -> Self {
+        self.y_axis.label = label.to_string();
+        self.y_axis.scale = scale;
+        self
+    }
+
+    /// Set a fixed range for the x axis.
+    pub fn x_range(mut self, min: f64, max: f64) -> Self {
+        self.x_axis.range = Some((min, max));
+        self
+    }
+
+    /// Set a fixed range for the y axis.
+    pub fn y_range(mut self, min: f64, max: f64) -> Self {
+        self.y_axis.range = Some((min, max));
+        self
+    }
+
+    /// Add a trace.
+    pub fn trace(mut self, label: &str, x: Vec<f64>, y: Vec<f64>) -> Self {
+        self.traces.push(Trace {
+            label: label.to_string(),
+            x,
+            y,
+        });
+        self
+    }
+
+    pub fn grid(mut self, grid: bool) -> Self {
+        self.grid = grid;
+        self
+    }
+
+    pub fn legend(mut self, legend: bool) -> Self {
+        self.legend = legend;
+        self
+    }
+
+    /// Set the size of the chart in mm.
+    pub fn size(mut self, width: f32, height: f32) -> Self {
+        self.size = (width, height);
+        self
+    }
+
+    /// Create a chart with the vectors over the scale of the plot, e.g. the transient
+    /// waveforms. The vectors are variable names or net names. For complex vectors
+    /// the magnitude is used.
+    pub fn waveform(plot: &RawPlot, vectors: &[&str]) -> Result<Self, Error> {
+        let scale = scale(plot)?;
+        let mut chart = Chart::new(&plot.name).x_axis(&plot.variables[0].name, AxisScale::Linear);
+        for name in vectors {
+            let (kind, vector) = vector(plot, name)?;
+            if chart.y_axis.label.is_empty() {
+                chart.y_axis.label = kind;
+            }
+            let values = match vector {
+                Vector::Real(values) => values.clone(),
+                Vector::Complex(_) => vector.magnitude(),
+            };
+            chart = chart.trace(name, scale.clone(), values);
+        }
+        Ok(chart)
+    }
+
+    /// Create the bode plot of the vectors, the magnitude in dB and the phase in
+    /// degrees over the logarithmic frequency.
+    pub fn bode(plot: &RawPlot, vectors: &[&str]) -> Result<(Self, Self), Error> {
+        let scale = scale(plot)?;
+        let label = &plot.variables[0].name;
+        let mut magnitude = Chart::new(&format!("{} magnitude", plot.name))
+            .x_axis(label, AxisScale::Log)
+            .y_axis("dB", AxisScale::Linear);
+        let mut phase = Chart::new(&format!("{} phase", plot.name))
+            .x_axis(label, AxisScale::Log)
+            .y_axis("degree", AxisScale::Linear);
+        for name in vectors {
+            let (_, vector) = vector(plot, name)?;
+            let db = vector.magnitude().iter().map(|m| 20.0 * m.log10()).collect();
+            magnitude = magnitude.trace(name, scale.clone(), db);
+            phase = phase.trace(name, scale.clone(), vector.phase());
+        }
+        Ok((magnitude, phase))
+    }
+
+    /// Plot the chart.
+    pub fn plot(&self, plotter: &mut impl Plotter, command: PlotCommand) -> Result<(), Error> {
+        let theme = Theme::from(command.theme);
+        let (width, height) = self.size;
+        let area = Rect {
+            start: Pt {
+                x: MARGINS.0,
+                y: MARGINS.1,
+            },
+            end: Pt {
+                x: width - MARGINS.2,
+                y: height - MARGINS.3,
+            },
+        };
+        let ((x_min, x_max), x_ticks) = self.x_axis.ticks(self.traces.iter().flat_map(|t| t.x.iter()));
+        let ((y_min, y_max), y_ticks) = self.y_axis.ticks(self.traces.iter().flat_map(|t| t.y.iter()));
+        let (x_min, x_max) = (self.x_axis.value(x_min), self.x_axis.value(x_max));
+        let (y_min, y_max) = (self.y_axis.value(y_min), self.y_axis.value(y_max));
+        let x_pos = |x: f64| {
+            area.start.x
+                + ((self.x_axis.value(x) - x_min) / (x_max - x_min)) as f32
+                    * (area.end.x - area.start.x)
+        };
+        let y_pos = |y: f64| {
+            let y = ((self.y_axis.value(y) - y_min) / (y_max - y_min)).clamp(0.0, 1.0);
+            area.end.y - y as f32 * (area.end.y - area.start.y)
+        };
+
+        let axis = Paint {
+            color: theme.color(None, Style::Axis),
+            fill: None,
+            width: theme.width(0.0, Style::Axis),
+        };
+        let grid = Paint {
+            color: theme.color(None, Style::Grid),
+            fill: None,
+            width: theme.width(0.0, Style::Grid),
+        };
+        let text = |justify: Vec<Justify>| Effects {
+            font: Font {
+                face: Some(theme.face()),
+                size: theme.font_size((0.0, 0.0), Style::Axis),
+                color: Some(theme.color(None, Style::Axis)),
+                ..Default::default()
+            },
+            justify,
+            hide: false,
+        };
+
+        //grid and ticks
+        for tick in &x_ticks {
+            let x = x_pos(*tick);
+            if self.grid {
+                line(plotter, Pt { x, y: area.start.y }, Pt { x, y: area.end.y }, grid.clone());
+            }
+            line(plotter, Pt { x, y: area.end.y }, Pt { x, y: area.end.y + 1.0 }, axis.clone());
+            plotter.text(&si(*tick), Pos { x, y: area.end.y + 2.0, angle: 0.0 }, text(vec![Justify::Top]));
+        }
+        for tick in &y_ticks {
+            let y = y_pos(*tick);
+            if self.grid {
+                line(plotter, Pt { x: area.start.x, y }, Pt { x: area.end.x, y }, grid.clone());
+            }
+            line(plotter, Pt { x: area.start.x - 1.0, y }, Pt { x: area.start.x, y }, axis.clone());
+            plotter.text(&si(*tick), Pos { x: area.start.x - 1.5, y, angle: 0.0 }, text(vec![Justify::Right]));
+        }
+        //the minor grid lines of the logarithmic x axis
+        if self.grid && self.x_axis.scale == AxisScale::Log {
+            for decade in &x_ticks {
+                for factor in 2..10 {
+                    let value = decade * factor as f64;
+                    if self.x_axis.value(value) < x_max {
+                        let x = x_pos(value);
+                        line(plotter, Pt { x, y: area.start.y }, Pt { x, y: area.end.y }, grid.clone());
+                    }
+                }
+            }
+        }
+
+        //frame and labels
+        plotter.polyline(
+            Pts(vec![
+                area.start,
+                Pt { x: area.end.x, y: area.start.y },
+                area.end,
+                Pt { x: area.start.x, y: area.end.y },
+                area.start,
+            ]),
+            axis.clone(),
+        );
+        plotter.text(&self.title, Pos { x: width / 2.0, y: MARGINS.1 / 2.0, angle: 0.0 }, text(vec![]));
+        plotter.text(
+            &self.x_axis.label,
+            Pos { x: (area.start.x + area.end.x) / 2.0, y: height - 3.0, angle: 0.0 },
+            text(vec![]),
+        );
+        plotter.text(
+            &self.y_axis.label,
+            Pos { x: 3.0, y: (area.start.y + area.end.y) / 2.0, angle: 270.0 },
+            text(vec![]),
+        );
+
+        //traces
+        for (index, trace) in self.traces.iter().enumerate() {
+            let pts = trace
+                .x
+                .iter()
+                .zip(&trace.y)
+                .filter(|(x, y)| self.x_axis.valid(**x) && self.y_axis.valid(**y))
+                .map(|(x, y)| Pt { x: x_pos(*x), y: y_pos(*y) })
+                .filter(|pt| pt.x >= area.start.x - 0.001 && pt.x <= area.end.x + 0.001)
+                .collect::<Vec<Pt>>();
+            if pts.len() > 1 {
+                plotter.polyline(
+                    Pts(pts),
+                    Paint {
+                        color: theme.trace(index),
+                        fill: None,
+                        width: theme.width(0.0, Style::Trace),
+                    },
+                );
+            }
+            if self.legend {
+                let y = area.start.y + 3.0 + index as f32 * 3.0;
+                let x = area.start.x + 3.0;
+                line(
+                    plotter,
+                    Pt { x, y },
+                    Pt { x: x + 5.0, y },
+                    Paint {
+                        color: theme.trace(index),
+                        fill: None,
+                        width: theme.width(0.0, Style::Trace),
+                    },
+                );
+                plotter.text(&trace.label, Pos { x: x + 6.0, y, angle: 0.0 }, text(vec![Justify::Left]));
+            }
+        }
+
+        plotter.scale(command.scale);
+        plotter.set_view_box(Rect {
+            start: Pt { x: 0.0, y: 0.0 },
+            end: Pt { x: width, y: height },
+        });
+        Ok(())
+    }
+}
+
+fn line(plotter: &mut impl Plotter, start: Pt, end: Pt, paint: Paint) {
+    plotter.polyline(Pts(vec![start, end]), paint);
+}
+
+/// Get the real values of the plot scale.
+fn scale(plot: &RawPlot) -> Result<Vec<f64>, Error> {
+    match plot.scale() {
+        Some(Vector::Real(values)) => Ok(values.clone()),
+        Some(Vector::Complex(values)) => Ok(values.iter().map(|(re, _)| *re).collect()),
+        None => Err(Error(
+            String::from("chart"),
+            format!("plot {} has no variables", plot.name),
+        )),
+    }
+}
+
+/// Get the kind and the values of a variable or the voltage of a net.
+fn vector<'a>(plot: &'a RawPlot, name: &str) -> Result<(String, &'a Vector), Error> {
+    plot.variables
+        .iter()
+        .find(|v| v.name.eq_ignore_ascii_case(name))
+        .or_else(|| {
+            plot.variables.iter().find(|v| {
+                v.kind == "voltage"
+                    && (v.name.eq_ignore_ascii_case(&format!("v({})", name))
+                        || v.name.eq_ignore_ascii_case(name))
+            })
+        })
+        .map(|v| (v.kind.clone(), &v.values))
+        .ok_or_else(|| {
+            Error(
+                String::from("chart"),
+                format!("vector {} not found in {}", name, plot.name),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{si, Axis, AxisScale, Chart};
+    use crate::{
+        plot::{PlotCommand, PlotterImpl, PlotterNodes},
+        rawfile::RawFile,
+    };
+
+    fn texts(chart: &Chart) -> Vec<String> {
+        let mut plotter = PlotterImpl::new();
+        chart.plot(&mut plotter, PlotCommand::default()).unwrap();
+        plotter
+            .iter()
+            .filter_map(|node| match node {
+                PlotterNodes::Text { text, .. } => Some(text.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!("0", si(0.0));
+        assert_eq!("1k", si(1000.0));
+        assert_eq!("1.5k", si(1500.0));
+        assert_eq!("100u", si(1e-4));
+        assert_eq!("-2m", si(-0.002));
+        assert_eq!("500m", si(0.5));
+    }
+
+    #[test]
+    fn ticks() {
+        let linear = Axis::default();
+        let ((min, max), ticks) = linear.ticks([0.1, 0.93].iter());
+        assert_eq!((0.0, 1.0), (min, max));
+        assert_eq!(
+            vec!["0", "200m", "400m", "600m", "800m", "1"],
+            ticks.iter().map(|t| si(*t)).collect::<Vec<String>>()
+        );
+
+        let log = Axis {
+            scale: AxisScale::Log,
+            ..Default::default()
+        };
+        let ((min, max), ticks) = log.ticks([15.0, 2e4, 0.0].iter());
+        assert_eq!((10.0, 1e5), (min, max));
+        assert_eq!(vec![10.0, 100.0, 1000.0, 1e4, 1e5], ticks);
+
+        let clamped = Axis {
+            scale: AxisScale::Log,
+            range: Some((0.0, 1e3)),
+            ..Default::default()
+        };
+        let ((min, max), ticks) = clamped.ticks([-1.0, 0.0, 2.0, 50.0].iter());
+        assert_eq!((2.0, 1e3), (min, max));
+        assert_eq!(vec![10.0, 100.0, 1000.0], ticks);
+        let ((min, max), ticks) = clamped.ticks([-1.0, 0.0].iter());
+        assert_eq!((1.0, 10.0), (min, max));
+        assert_eq!(vec![1.0, 10.0], ticks);
+    }
+
+    #[test]
+    fn waveform() {
+        let raw = RawFile::load(Path::new("tests/spice/rc.raw")).unwrap();
+        let chart = Chart::waveform(raw.plot("Transient Analysis").unwrap(), &["in", "OUT", "v1#branch"]).unwrap();
+        assert_eq!(3, chart.traces.len());
+        assert_eq!("voltage", chart.y_axis.label);
+        assert_eq!(vec![0.0, 0.5, 0.75], chart.traces[1].y);
+        assert_eq!(vec![0.0, -1e-3, -5e-4], chart.traces[2].y);
+        let texts = texts(&chart);
+        for text in ["Transient Analysis", "time", "voltage", "in", "OUT", "v1#branch", "0", "2m"] {
+            assert!(texts.contains(&text.to_string()), "{} not in {:?}", text, texts);
+        }
+        assert!(Chart::waveform(raw.plot("Transient Analysis").unwrap(), &["missing"]).is_err());
+    }
+
+    #[test]
+    fn bode() {
+        let raw = RawFile::load(Path::new("tests/spice/rc.raw")).unwrap();
+        let (magnitude, phase) = Chart::bode(raw.plot("AC Analysis").unwrap(), &["out"]).unwrap();
+        assert_eq!(AxisScale::Log, magnitude.x_axis.scale);
+        assert_eq!(0.0, magnitude.traces[0].y[0]);
+        assert!((magnitude.traces[0].y[1] + 3.0103).abs() < 1e-3);
+        assert_eq!(vec![0.0, -45.0], phase.traces[0].y);
+        let texts = texts(&magnitude);
+        for text in ["1", "10", "100", "1k", "dB"] {
+            assert!(texts.contains(&text.to_string()), "{} not in {:?}", text, texts);
+        }
+    }
+}
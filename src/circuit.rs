@@ -11,24 +11,18 @@
 // assert_eq!("kicad_sch", root.name);
 // ```
 
-use crate::{Circuit, Error};
+use crate::{model_library::ModelLibrary, value::Value, Circuit, Error};
 
 use indexmap::IndexMap;
-use lazy_static::lazy_static;
-use regex::Regex;
 use std::{
+    cell::OnceCell,
     fs::{self, File},
     io::Write,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
 
-lazy_static! {
-    pub static ref RE_SUBCKT: regex::Regex =
-        Regex::new(r"(?i:\.SUBCKT) ([a-zA-Z0-9]*) .*").unwrap();
-    pub static ref RE_MODEL: regex::Regex = Regex::new(r"(?i:\.model) ([a-zA-Z0-9]*) .*").unwrap();
-    pub static ref RE_INCLUDE: regex::Regex = Regex::new(r"(?i:\.include) (.*)").unwrap();
-}
-
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum CircuitItem {
     R(String, String, String, String),
     C(String, String, String, String),
@@ -37,6 +31,653 @@ pub enum CircuitItem {
     Q(String, String, String, String, String),
     X(String, Vec<String>, String),
     V(String, String, String, String),
+    L(String, String, String, String),
+    I(String, String, String, String),
+    M(String, String, String, String, String, String),
+    K(String, String, String, String),
+    E(String, String, String, String, String, String),
+    F(String, String, String, String, String),
+    G(String, String, String, String, String, String),
+    H(String, String, String, String, String),
+    B(String, String, String, String),
+    S(String, String, String, String, String, String),
+    W(String, String, String, String, String),
+}
+
+/// The items are equal when all fields are equal, the values of resistors,
+/// capacitors and inductors are compared as [`Value`]: `100n` and `0.1u` are equal.
+impl PartialEq for CircuitItem {
+    fn eq(&self, other: &Self) -> bool {
+        use CircuitItem::*;
+        match (self, other) {
+            (R(r1, a1, b1, v1), R(r2, a2, b2, v2))
+            | (C(r1, a1, b1, v1), C(r2, a2, b2, v2))
+            | (L(r1, a1, b1, v1), L(r2, a2, b2, v2)) => {
+                (r1, a1, b1) == (r2, a2, b2) && same_value(v1, v2)
+            }
+            (D(a1, b1, c1, d1), D(a2, b2, c2, d2))
+            | (V(a1, b1, c1, d1), V(a2, b2, c2, d2))
+            | (I(a1, b1, c1, d1), I(a2, b2, c2, d2))
+            | (K(a1, b1, c1, d1), K(a2, b2, c2, d2))
+            | (B(a1, b1, c1, d1), B(a2, b2, c2, d2)) => (a1, b1, c1, d1) == (a2, b2, c2, d2),
+            (J(a1, b1, c1, d1, e1), J(a2, b2, c2, d2, e2))
+            | (Q(a1, b1, c1, d1, e1), Q(a2, b2, c2, d2, e2))
+            | (F(a1, b1, c1, d1, e1), F(a2, b2, c2, d2, e2))
+            | (H(a1, b1, c1, d1, e1), H(a2, b2, c2, d2, e2))
+            | (W(a1, b1, c1, d1, e1), W(a2, b2, c2, d2, e2)) => {
+                (a1, b1, c1, d1, e1) == (a2, b2, c2, d2, e2)
+            }
+            (M(a1, b1, c1, d1, e1, f1), M(a2, b2, c2, d2, e2, f2))
+            | (E(a1, b1, c1, d1, e1, f1), E(a2, b2, c2, d2, e2, f2))
+            | (G(a1, b1, c1, d1, e1, f1), G(a2, b2, c2, d2, e2, f2))
+            | (S(a1, b1, c1, d1, e1, f1), S(a2, b2, c2, d2, e2, f2)) => {
+                (a1, b1, c1, d1, e1, f1) == (a2, b2, c2, d2, e2, f2)
+            }
+            (X(a1, b1, c1), X(a2, b2, c2)) => (a1, b1, c1) == (a2, b2, c2),
+            _ => false,
+        }
+    }
+}
+
+/// Compare two spice values, values with parameters or options like
+/// `{rload}` or `10k tc=0.01` are compared as text.
+fn same_value(a: &str, b: &str) -> bool {
+    let text = |v: &str| v.contains(|c: char| c.is_whitespace() || c == '{');
+    if a == b || text(a) || text(b) {
+        return a == b;
+    }
+    matches!((Value::parse_spice(a), Value::parse_spice(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+/// The specification of a voltage or current source.
+///
+/// A source can combine a DC value, an AC value for the small signal
+/// analysis and one transient function, see [`Circuit::voltage_source`].
+///
+/// ```
+/// use recad_core::circuit::SourceSpec;
+///
+/// let spec = SourceSpec::Pulse {
+///     v1: 0.0,
+///     v2: 5.0,
+///     delay: 0.0,
+///     rise: 1e-9,
+///     fall: 1e-9,
+///     width: 5e-6,
+///     period: 10e-6,
+/// };
+/// assert!(spec.validate().is_ok());
+/// assert_eq!("PULSE(0 5 0 1e-9 1e-9 5e-6 1e-5)", spec.to_string());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum SourceSpec {
+    /// DC value.
+    Dc(f64),
+    /// AC magnitude and phase in degrees.
+    Ac { magnitude: f64, phase: f64 },
+    /// Damped sinusoidal source.
+    Sin {
+        offset: f64,
+        amplitude: f64,
+        frequency: f64,
+        delay: f64,
+        damping: f64,
+        phase: f64,
+    },
+    /// Pulse source.
+    Pulse {
+        v1: f64,
+        v2: f64,
+        delay: f64,
+        rise: f64,
+        fall: f64,
+        width: f64,
+        period: f64,
+    },
+    /// Piece-wise linear source with the time and value pairs.
+    Pwl(Vec<(f64, f64)>),
+    /// Exponential source.
+    Exp {
+        v1: f64,
+        v2: f64,
+        rise_delay: f64,
+        rise_tau: f64,
+        fall_delay: f64,
+        fall_tau: f64,
+    },
+}
+
+impl SourceSpec {
+    /// Load a piece-wise linear source from a file.
+    ///
+    /// Every line contains the time and the value, separated by whitespace or
+    /// a comma. Empty lines and lines starting with `*` or `#` are ignored.
+    pub fn pwl_file(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)?;
+        let mut points = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('*') || line.starts_with('#') {
+                continue;
+            }
+            let values = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|v| !v.is_empty())
+                .map(|v| v.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>();
+            match values.as_deref() {
+                Ok([time, value]) => points.push((*time, *value)),
+                _ => {
+                    return Err(Error(
+                        String::from("spice"),
+                        format!(
+                            "{}:{}: expected time and value: '{}'",
+                            path.display(),
+                            number + 1,
+                            line
+                        ),
+                    ))
+                }
+            }
+        }
+        let spec = SourceSpec::Pwl(points);
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Check the parameters of the source.
+    pub fn validate(&self) -> Result<(), Error> {
+        let error = |msg: &str| Err(Error(String::from("spice"), format!("{}: {}", self.name(), msg)));
+        let values = match self {
+            SourceSpec::Dc(value) => vec![*value],
+            SourceSpec::Ac { magnitude, phase } => vec![*magnitude, *phase],
+            SourceSpec::Sin { offset, amplitude, frequency, delay, damping, phase } => {
+                if *frequency < 0.0 {
+                    return error("the frequency must not be negative");
+                }
+                if *delay < 0.0 {
+                    return error("the delay must not be negative");
+                }
+                vec![*offset, *amplitude, *frequency, *delay, *damping, *phase]
+            }
+            SourceSpec::Pulse { v1, v2, delay, rise, fall, width, period } => {
+                if [*delay, *rise, *fall, *width, *period].iter().any(|v| *v < 0.0) {
+                    return error("the times must not be negative");
+                }
+                if *period > 0.0 && rise + width + fall > *period {
+                    return error("rise, width and fall are longer than the period");
+                }
+                vec![*v1, *v2, *delay, *rise, *fall, *width, *period]
+            }
+            SourceSpec::Pwl(points) => {
+                if points.is_empty() {
+                    return error("no points");
+                }
+                if points.first().is_some_and(|(time, _)| *time < 0.0) {
+                    return error("the times must not be negative");
+                }
+                if points.windows(2).any(|p| p[1].0 <= p[0].0) {
+                    return error("the times must be increasing");
+                }
+                points.iter().flat_map(|(time, value)| [*time, *value]).collect()
+            }
+            SourceSpec::Exp { v1, v2, rise_delay, rise_tau, fall_delay, fall_tau } => {
+                if *rise_delay < 0.0 || *fall_delay < 0.0 {
+                    return error("the delays must not be negative");
+                }
+                if *rise_tau <= 0.0 || *fall_tau <= 0.0 {
+                    return error("the time constants must be positive");
+                }
+                if fall_delay < rise_delay {
+                    return error("the fall delay is before the rise delay");
+                }
+                vec![*v1, *v2, *rise_delay, *rise_tau, *fall_delay, *fall_tau]
+            }
+        };
+        if values.iter().any(|v| !v.is_finite()) {
+            return error("the values must be finite");
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            SourceSpec::Dc(_) => "DC",
+            SourceSpec::Ac { .. } => "AC",
+            SourceSpec::Sin { .. } => "SIN",
+            SourceSpec::Pulse { .. } => "PULSE",
+            SourceSpec::Pwl(_) => "PWL",
+            SourceSpec::Exp { .. } => "EXP",
+        }
+    }
+}
+
+impl std::fmt::Display for SourceSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let values = match self {
+            SourceSpec::Dc(value) => return write!(f, "DC {}", spice_number(*value)),
+            SourceSpec::Ac { magnitude, phase } => {
+                return write!(f, "AC {} {}", spice_number(*magnitude), spice_number(*phase))
+            }
+            SourceSpec::Sin { offset, amplitude, frequency, delay, damping, phase } => {
+                vec![*offset, *amplitude, *frequency, *delay, *damping, *phase]
+            }
+            SourceSpec::Pulse { v1, v2, delay, rise, fall, width, period } => {
+                vec![*v1, *v2, *delay, *rise, *fall, *width, *period]
+            }
+            SourceSpec::Pwl(points) => points.iter().flat_map(|(time, value)| [*time, *value]).collect(),
+            SourceSpec::Exp { v1, v2, rise_delay, rise_tau, fall_delay, fall_tau } => {
+                vec![*v1, *v2, *rise_delay, *rise_tau, *fall_delay, *fall_tau]
+            }
+        };
+        write!(
+            f,
+            "{}({})",
+            self.name(),
+            values.iter().map(|v| spice_number(*v)).collect::<Vec<String>>().join(" ")
+        )
+    }
+}
+
+/// Format a number for the spice netlist, very small and large numbers
+/// are written in the exponent notation.
+pub(crate) fn spice_number(value: f64) -> String {
+    if value == 0.0 || (1e-3..1e6).contains(&value.abs()) {
+        format!("{}", value)
+    } else {
+        format!("{:e}", value)
+    }
+}
+
+/// The frequency sweep of the `.ac` and `.noise` analysis with the number of points.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sweep {
+    /// Points per decade.
+    Dec(u32),
+    /// Points per octave.
+    Oct(u32),
+    /// Total number of points.
+    Lin(u32),
+}
+
+impl std::fmt::Display for Sweep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Sweep::Dec(points) => write!(f, "dec {}", points),
+            Sweep::Oct(points) => write!(f, "oct {}", points),
+            Sweep::Lin(points) => write!(f, "lin {}", points),
+        }
+    }
+}
+
+/// The spice analysis commands, see [`Circuit::analysis`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Analysis {
+    /// Operating point analysis.
+    Op,
+    /// DC sweep of a source.
+    Dc {
+        source: String,
+        start: f64,
+        stop: f64,
+        step: f64,
+    },
+    /// Small signal AC analysis.
+    Ac { sweep: Sweep, start: f64, stop: f64 },
+    /// Transient analysis, the optional start time and the maximum step size.
+    Tran {
+        step: f64,
+        stop: f64,
+        start: Option<f64>,
+        max_step: Option<f64>,
+    },
+    /// Noise analysis, the output node, the optional reference node and the input source.
+    Noise {
+        output: String,
+        reference: Option<String>,
+        source: String,
+        sweep: Sweep,
+        start: f64,
+        stop: f64,
+    },
+    /// Transfer function from the input source to the output variable.
+    Tf { output: String, source: String },
+}
+
+impl Analysis {
+    /// Check the parameters of the analysis.
+    pub fn validate(&self) -> Result<(), Error> {
+        let error = |msg: &str| Err(Error(String::from("spice"), format!("{}: {}", self.name(), msg)));
+        let frequencies = |sweep: &Sweep, start: f64, stop: f64| {
+            if matches!(sweep, Sweep::Dec(0) | Sweep::Oct(0) | Sweep::Lin(0)) {
+                return error("the number of points must be positive");
+            }
+            if !start.is_finite() || !stop.is_finite() || start <= 0.0 || stop < start {
+                return error("the frequencies must be positive and the stop frequency must not be below the start frequency");
+            }
+            Ok(())
+        };
+        match self {
+            Analysis::Op => Ok(()),
+            Analysis::Dc { source, start, stop, step } => {
+                if source.is_empty() {
+                    return error("no source");
+                }
+                if ![*start, *stop, *step].iter().all(|v| v.is_finite()) {
+                    return error("the values must be finite");
+                }
+                if *step == 0.0 || (stop - start) * step < 0.0 {
+                    return error("the step does not reach the stop value");
+                }
+                Ok(())
+            }
+            Analysis::Ac { sweep, start, stop } => frequencies(sweep, *start, *stop),
+            Analysis::Tran { step, stop, start, max_step } => {
+                if !step.is_finite() || !stop.is_finite() || *step <= 0.0 || *stop <= 0.0 {
+                    return error("the step and the stop time must be positive");
+                }
+                if step > stop {
+                    return error("the step is larger than the stop time");
+                }
+                if start.is_some_and(|start| !(0.0..*stop).contains(&start)) {
+                    return error("the start time must be between zero and the stop time");
+                }
+                if max_step.is_some_and(|max| !max.is_finite() || max <= 0.0) {
+                    return error("the maximum step must be positive");
+                }
+                Ok(())
+            }
+            Analysis::Noise { output, source, sweep, start, stop, .. } => {
+                if output.is_empty() || source.is_empty() {
+                    return error("no output or source");
+                }
+                frequencies(sweep, *start, *stop)
+            }
+            Analysis::Tf { output, source } => {
+                if output.is_empty() || source.is_empty() {
+                    return error("no output or source");
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Analysis::Op => ".op",
+            Analysis::Dc { .. } => ".dc",
+            Analysis::Ac { .. } => ".ac",
+            Analysis::Tran { .. } => ".tran",
+            Analysis::Noise { .. } => ".noise",
+            Analysis::Tf { .. } => ".tf",
+        }
+    }
+}
+
+impl std::fmt::Display for Analysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Analysis::Op => write!(f, ".op"),
+            Analysis::Dc { source, start, stop, step } => write!(
+                f,
+                ".dc {} {} {} {}",
+                source,
+                spice_number(*start),
+                spice_number(*stop),
+                spice_number(*step)
+            ),
+            Analysis::Ac { sweep, start, stop } => write!(
+                f,
+                ".ac {} {} {}",
+                sweep,
+                spice_number(*start),
+                spice_number(*stop)
+            ),
+            Analysis::Tran { step, stop, start, max_step } => {
+                write!(f, ".tran {} {}", spice_number(*step), spice_number(*stop))?;
+                if start.is_some() || max_step.is_some() {
+                    write!(f, " {}", spice_number(start.unwrap_or(0.0)))?;
+                }
+                if let Some(max_step) = max_step {
+                    write!(f, " {}", spice_number(*max_step))?;
+                }
+                Ok(())
+            }
+            Analysis::Noise { output, reference, source, sweep, start, stop } => {
+                let output = if let Some(reference) = reference {
+                    format!("v({},{})", output, reference)
+                } else {
+                    format!("v({})", output)
+                };
+                write!(
+                    f,
+                    ".noise {} {} {} {} {}",
+                    output,
+                    source,
+                    sweep,
+                    spice_number(*start),
+                    spice_number(*stop)
+                )
+            }
+            Analysis::Tf { output, source } => write!(f, ".tf {} {}", output, source),
+        }
+    }
+}
+
+/// Sweep a parameter with `.step`, see [`Circuit::step`].
+///
+/// ngspice does not support `.step`, the sweep is expanded into
+/// a circuit for every value with [`Circuit::variants`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// Linear sweep from start to stop.
+    Lin {
+        param: String,
+        start: f64,
+        stop: f64,
+        step: f64,
+    },
+    /// Sweep over the list of values.
+    List { param: String, values: Vec<f64> },
+}
+
+impl Step {
+    /// The name of the swept parameter.
+    pub fn param(&self) -> &str {
+        match self {
+            Step::Lin { param, .. } | Step::List { param, .. } => param,
+        }
+    }
+
+    /// The values of the sweep, a linear sweep includes the stop value
+    /// when it is reached by the steps.
+    pub fn values(&self) -> Vec<f64> {
+        match self {
+            Step::Lin { start, stop, step, .. } => {
+                //count the steps, the tolerance keeps the stop value from rounding errors.
+                let count = ((stop - start) / step + 1e-9).floor() as usize;
+                (0..=count).map(|i| start + i as f64 * step).collect()
+            }
+            Step::List { values, .. } => values.clone(),
+        }
+    }
+}
+
+/// The analysis of a measurement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeasureAnalysis {
+    Dc,
+    Ac,
+    Tran,
+}
+
+/// The statistic functions of a measurement.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Statistic {
+    Max,
+    Min,
+    Pp,
+    Avg,
+    Rms,
+    Integ,
+}
+
+/// The function of a measurement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeasureFunction {
+    /// A statistic function of the vector in the optional range.
+    Stat {
+        statistic: Statistic,
+        vector: String,
+        from: Option<f64>,
+        to: Option<f64>,
+    },
+    /// The value of the vector at the point.
+    FindAt { vector: String, at: f64 },
+    /// The point where the vector crosses the value.
+    When { vector: String, value: f64 },
+    /// The distance between the points where the trigger and the target
+    /// vectors first rise above the values.
+    TrigTarg {
+        trig: String,
+        trig_value: f64,
+        targ: String,
+        targ_value: f64,
+    },
+}
+
+/// A `.meas` statement, see [`Circuit::measure`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measure {
+    pub name: String,
+    pub analysis: MeasureAnalysis,
+    pub function: MeasureFunction,
+}
+
+impl Measure {
+    /// Check the parameters of the measurement.
+    pub fn validate(&self) -> Result<(), Error> {
+        let error = |msg: &str| Err(Error(String::from("spice"), format!(".meas {}: {}", self.name, msg)));
+        if !is_identifier(&self.name) {
+            return error("invalid name");
+        }
+        let (vectors, values) = match &self.function {
+            MeasureFunction::Stat { vector, from, to, .. } => {
+                if let (Some(from), Some(to)) = (from, to) {
+                    if to < from {
+                        return error("the range end is before the start");
+                    }
+                }
+                (vec![vector], vec![from.unwrap_or(0.0), to.unwrap_or(0.0)])
+            }
+            MeasureFunction::FindAt { vector, at } => (vec![vector], vec![*at]),
+            MeasureFunction::When { vector, value } => (vec![vector], vec![*value]),
+            MeasureFunction::TrigTarg { trig, trig_value, targ, targ_value } => {
+                (vec![trig, targ], vec![*trig_value, *targ_value])
+            }
+        };
+        if vectors.iter().any(|v| v.trim().is_empty()) {
+            return error("no vector");
+        }
+        if values.iter().any(|v| !v.is_finite()) {
+            return error("the values must be finite");
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Measure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let analysis = match self.analysis {
+            MeasureAnalysis::Dc => "dc",
+            MeasureAnalysis::Ac => "ac",
+            MeasureAnalysis::Tran => "tran",
+        };
+        write!(f, ".meas {} {} ", analysis, self.name)?;
+        match &self.function {
+            MeasureFunction::Stat { statistic, vector, from, to } => {
+                let statistic = match statistic {
+                    Statistic::Max => "MAX",
+                    Statistic::Min => "MIN",
+                    Statistic::Pp => "PP",
+                    Statistic::Avg => "AVG",
+                    Statistic::Rms => "RMS",
+                    Statistic::Integ => "INTEG",
+                };
+                write!(f, "{} {}", statistic, vector)?;
+                if let Some(from) = from {
+                    write!(f, " FROM={}", spice_number(*from))?;
+                }
+                if let Some(to) = to {
+                    write!(f, " TO={}", spice_number(*to))?;
+                }
+                Ok(())
+            }
+            MeasureFunction::FindAt { vector, at } => {
+                write!(f, "FIND {} AT={}", vector, spice_number(*at))
+            }
+            MeasureFunction::When { vector, value } => {
+                write!(f, "WHEN {}={}", vector, spice_number(*value))
+            }
+            MeasureFunction::TrigTarg { trig, trig_value, targ, targ_value } => write!(
+                f,
+                "TRIG {} VAL={} RISE=1 TARG {} VAL={} RISE=1",
+                trig,
+                spice_number(*trig_value),
+                targ,
+                spice_number(*targ_value)
+            ),
+        }
+    }
+}
+
+/// Test if the name is a valid parameter or measurement name.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Check and join the source specifications.
+fn source_specs(reference: &str, specs: &[SourceSpec]) -> Result<String, Error> {
+    if specs.is_empty() {
+        return Err(Error(
+            String::from("spice"),
+            format!("{}: the source has no specification", reference),
+        ));
+    }
+    let mut dc = false;
+    let mut ac = false;
+    let mut transient = false;
+    for spec in specs {
+        spec.validate()
+            .map_err(|e| Error(e.0, format!("{}: {}", reference, e.1)))?;
+        let (seen, kind) = match spec {
+            SourceSpec::Dc(_) => (&mut dc, "DC"),
+            SourceSpec::Ac { .. } => (&mut ac, "AC"),
+            _ => (&mut transient, "transient"),
+        };
+        if *seen {
+            return Err(Error(
+                String::from("spice"),
+                format!("{}: more than one {} specification", reference, kind),
+            ));
+        }
+        *seen = true;
+    }
+    Ok(specs.iter().map(|s| s.to_string()).collect::<Vec<String>>().join(" "))
+}
+
+/// The model library of a circuit, it is created from the pathlist when it is first used.
+///
+/// The cache is not part of the circuit and is ignored when circuits are compared.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LibraryCache(OnceCell<Arc<ModelLibrary>>);
+
+impl PartialEq for LibraryCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
 }
 
 impl Circuit {
@@ -48,9 +689,42 @@ impl Circuit {
             subcircuits: IndexMap::new(),
             controls: Vec::new(),
             options: IndexMap::new(),
+            params: IndexMap::new(),
+            models: IndexMap::new(),
+            include_files: Vec::new(),
+            lib_files: Vec::new(),
+            analyses: Vec::new(),
+            steps: Vec::new(),
+            measures: Vec::new(),
+            library: LibraryCache::default(),
         }
     }
 
+    /// The name of the circuit.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The elements of the circuit.
+    pub fn items(&self) -> &[CircuitItem] {
+        &self.items
+    }
+
+    /// The subcircuits with the nodes and the circuit.
+    pub fn subcircuits(&self) -> &IndexMap<String, (Vec<String>, Circuit)> {
+        &self.subcircuits
+    }
+
+    /// The models with the model name and the model definition.
+    pub fn models(&self) -> &IndexMap<String, String> {
+        &self.models
+    }
+
+    /// The parameter sweeps of the circuit.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
     //Add a resistor to the netlist.
     pub fn resistor(&mut self, reference: String, n0: String, n1: String, value: String) {
         self.items.push(CircuitItem::R(reference, n0, n1, value));
@@ -101,6 +775,230 @@ impl Circuit {
         self.items.push(CircuitItem::V(reference, n1, n2, value));
     }
 
+    //Add a voltage source with the source specifications to the netlist.
+    pub fn voltage_source(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        specs: &[SourceSpec],
+    ) -> Result<(), Error> {
+        let value = source_specs(&reference, specs)?;
+        self.items.push(CircuitItem::V(reference, n1, n2, value));
+        Ok(())
+    }
+
+    //Add a current source with the source specifications to the netlist.
+    pub fn current_source(
+        &mut self,
+        reference: String,
+        n1: String,
+        n2: String,
+        specs: &[SourceSpec],
+    ) -> Result<(), Error> {
+        let value = source_specs(&reference, specs)?;
+        self.items.push(CircuitItem::I(reference, n1, n2, value));
+        Ok(())
+    }
+
+    //Add an inductor to the netlist.
+    pub fn inductor(&mut self, reference: String, n0: String, n1: String, value: String) {
+        self.items.push(CircuitItem::L(reference, n0, n1, value));
+    }
+
+    //Add a current source to the netlist.
+    pub fn current(&mut self, reference: String, n0: String, n1: String, value: String) {
+        self.items.push(CircuitItem::I(reference, n0, n1, value));
+    }
+
+    //Add a mosfet to the netlist, the nodes are drain, gate, source and bulk.
+    pub fn mosfet(
+        &mut self,
+        reference: String,
+        nd: String,
+        ng: String,
+        ns: String,
+        nb: String,
+        value: String,
+    ) {
+        self.items
+            .push(CircuitItem::M(reference, nd, ng, ns, nb, value));
+    }
+
+    //Couple two inductors with the coupling coefficient.
+    pub fn coupling(&mut self, reference: String, l0: String, l1: String, value: String) {
+        self.items.push(CircuitItem::K(reference, l0, l1, value));
+    }
+
+    //Add a voltage controlled voltage source to the netlist.
+    pub fn vcvs(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        nc0: String,
+        nc1: String,
+        gain: String,
+    ) {
+        self.items
+            .push(CircuitItem::E(reference, n0, n1, nc0, nc1, gain));
+    }
+
+    //Add a current controlled current source to the netlist, the current
+    //is measured through the voltage source `vcontrol`.
+    pub fn cccs(&mut self, reference: String, n0: String, n1: String, vcontrol: String, gain: String) {
+        self.items
+            .push(CircuitItem::F(reference, n0, n1, vcontrol, gain));
+    }
+
+    //Add a voltage controlled current source to the netlist.
+    pub fn vccs(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        nc0: String,
+        nc1: String,
+        transconductance: String,
+    ) {
+        self.items
+            .push(CircuitItem::G(reference, n0, n1, nc0, nc1, transconductance));
+    }
+
+    //Add a current controlled voltage source to the netlist, the current
+    //is measured through the voltage source `vcontrol`.
+    pub fn ccvs(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        vcontrol: String,
+        transresistance: String,
+    ) {
+        self.items
+            .push(CircuitItem::H(reference, n0, n1, vcontrol, transresistance));
+    }
+
+    //Add a behavioral source to the netlist, the expression
+    //starts with `V=` or `I=`.
+    pub fn behavioral(&mut self, reference: String, n0: String, n1: String, expression: String) {
+        self.items.push(CircuitItem::B(reference, n0, n1, expression));
+    }
+
+    //Add a voltage controlled switch to the netlist.
+    pub fn switch(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        nc0: String,
+        nc1: String,
+        model: String,
+    ) {
+        self.items
+            .push(CircuitItem::S(reference, n0, n1, nc0, nc1, model));
+    }
+
+    //Add a current controlled switch to the netlist, the current
+    //is measured through the voltage source `vcontrol`.
+    pub fn current_switch(
+        &mut self,
+        reference: String,
+        n0: String,
+        n1: String,
+        vcontrol: String,
+        model: String,
+    ) {
+        self.items
+            .push(CircuitItem::W(reference, n0, n1, vcontrol, model));
+    }
+
+    /// Add an analysis command.
+    pub fn analysis(&mut self, analysis: Analysis) -> Result<(), Error> {
+        analysis.validate()?;
+        self.analyses.push(analysis);
+        Ok(())
+    }
+
+    /// Define a parameter with `.param`, the value can be an expression.
+    pub fn param(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        if !is_identifier(name) {
+            return Err(Error(
+                String::from("spice"),
+                format!(".param: invalid name '{}'", name),
+            ));
+        }
+        if value.trim().is_empty() {
+            return Err(Error(
+                String::from("spice"),
+                format!(".param {}: no value", name),
+            ));
+        }
+        self.params.insert(name.to_string(), value.trim().to_string());
+        Ok(())
+    }
+
+    /// Sweep a parameter with `.step`, the parameter must be defined with [`Circuit::param`].
+    /// The steps are not written to the netlist, see [`Circuit::variants`].
+    pub fn step(&mut self, step: Step) -> Result<(), Error> {
+        let error = |msg: String| Err(Error(String::from("spice"), msg));
+        let (param, values) = match &step {
+            Step::Lin { param, start, stop, step } => {
+                if *step == 0.0 || (stop - start) * step < 0.0 {
+                    return error(format!(".step {}: the step does not reach the stop value", param));
+                }
+                (param, vec![*start, *stop, *step])
+            }
+            Step::List { param, values } => {
+                if values.is_empty() {
+                    return error(format!(".step {}: no values", param));
+                }
+                (param, values.clone())
+            }
+        };
+        if !self.params.contains_key(param) {
+            return error(format!(".step: parameter '{}' is not defined", param));
+        }
+        if values.iter().any(|v| !v.is_finite()) {
+            return error(format!(".step {}: the values must be finite", param));
+        }
+        self.steps.push(step);
+        Ok(())
+    }
+
+    /// Expand the steps into a circuit for every combination of the parameter values,
+    /// the last step changes fastest. The variants have no steps and the swept
+    /// parameters are set to the step values. A circuit without steps returns itself.
+    pub fn variants(&self) -> Vec<Circuit> {
+        let mut result = vec![Circuit { steps: Vec::new(), ..self.clone() }];
+        for step in &self.steps {
+            result = result
+                .iter()
+                .flat_map(|circuit| {
+                    step.values().into_iter().map(move |value| {
+                        let mut variant = circuit.clone();
+                        variant.params.insert(step.param().to_string(), spice_number(value));
+                        variant
+                    })
+                })
+                .collect();
+        }
+        result
+    }
+
+    /// Add a `.meas` statement.
+    pub fn measure(&mut self, measure: Measure) -> Result<(), Error> {
+        measure.validate()?;
+        if self.measures.iter().any(|m| m.name == measure.name) {
+            return Err(Error(
+                String::from("spice"),
+                format!(".meas: duplicate name '{}'", measure.name),
+            ));
+        }
+        self.measures.push(measure);
+        Ok(())
+    }
+
     pub fn option(&mut self, option: String, value: String) {
         self.options.insert(option, value);
     }
@@ -121,21 +1019,58 @@ impl Circuit {
             Box::new(std::io::stdout())
         };
 
-        for s in self.to_str(true).unwrap() {
+        for s in self.to_str(true)? {
             writeln!(out, "{}", s)?;
         }
+        out.flush()?;
+        Ok(())
+    }
 
-        if !self.controls.is_empty() {
-            writeln!(out, ".control")?;
-            for c in &self.controls {
-                writeln!(out, "{}", c)?;
+    /// Write the models and subcircuits of the circuit as a spice library file.
+    ///
+    /// The models which are used by the subcircuits and not defined in the circuit
+    /// are not included, they are resolved by the circuit which uses the library.
+    pub fn save_library(&self, filename: &Path) -> Result<(), Error> {
+        let mut out = File::create(filename)?;
+        writeln!(out, "* {}", self.name)?;
+        for (key, value) in &self.models {
+            writeln!(out, ".model {} {}", key, value)?;
+        }
+        for (key, (nodes, circuit)) in &self.subcircuits {
+            writeln!(out, ".subckt {} {}", key, nodes.join(" "))?;
+            for line in circuit.body() {
+                writeln!(out, "{}", line)?;
             }
-            writeln!(out, ".endc")?;
+            writeln!(out, ".ends {}", key)?;
         }
         out.flush()?;
         Ok(())
     }
 
+    /// Get the value of the item with the reference, the items
+    /// which can be changed with [`Circuit::set_value`] are supported.
+    pub fn value(&self, reference: &str) -> Option<&str> {
+        self.items.iter().find_map(|item| match item {
+            CircuitItem::R(r, _, _, v)
+            | CircuitItem::C(r, _, _, v)
+            | CircuitItem::D(r, _, _, v)
+            | CircuitItem::V(r, _, _, v)
+            | CircuitItem::L(r, _, _, v)
+            | CircuitItem::I(r, _, _, v)
+            | CircuitItem::K(r, _, _, v)
+            | CircuitItem::B(r, _, _, v)
+            | CircuitItem::E(r, _, _, _, _, v)
+            | CircuitItem::F(r, _, _, _, v)
+            | CircuitItem::G(r, _, _, _, _, v)
+            | CircuitItem::H(r, _, _, _, v)
+                if r == reference =>
+            {
+                Some(v.as_str())
+            }
+            _ => None,
+        })
+    }
+
     pub fn set_value(&mut self, reference: &str, value: &str) -> Result<(), Error> {
         for item in &mut self.items.iter_mut() {
             match item {
@@ -160,12 +1095,23 @@ impl Circuit {
                 CircuitItem::J(_, _, _, _, _) => {}
                 CircuitItem::Q(_, _, _, _, _) => {}
                 CircuitItem::X(_, _, _) => {}
-                CircuitItem::V(r, _, _, ref mut v) => {
+                CircuitItem::V(r, _, _, ref mut v)
+                | CircuitItem::L(r, _, _, ref mut v)
+                | CircuitItem::I(r, _, _, ref mut v)
+                | CircuitItem::K(r, _, _, ref mut v)
+                | CircuitItem::B(r, _, _, ref mut v)
+                | CircuitItem::E(r, _, _, _, _, ref mut v)
+                | CircuitItem::F(r, _, _, _, ref mut v)
+                | CircuitItem::G(r, _, _, _, _, ref mut v)
+                | CircuitItem::H(r, _, _, _, ref mut v) => {
                     if reference == r {
                         *v = value.to_string();
                         return Ok(());
                     }
                 }
+                CircuitItem::M(_, _, _, _, _, _) => {}
+                CircuitItem::S(_, _, _, _, _, _) => {}
+                CircuitItem::W(_, _, _, _, _) => {}
             }
         }
         Err(Error(
@@ -173,126 +1119,151 @@ impl Circuit {
             format!("spice model not found {}", reference),
         ))
     }
+
+    /// Set the value of a resistor, capacitor, inductor, source or coupling
+    /// with the reference, the value is written in the spice notation.
+    pub fn set_number(&mut self, reference: &str, value: Value) -> Result<(), Error> {
+        for item in self.items.iter_mut() {
+            match item {
+                CircuitItem::R(r, _, _, v)
+                | CircuitItem::C(r, _, _, v)
+                | CircuitItem::L(r, _, _, v)
+                | CircuitItem::V(r, _, _, v)
+                | CircuitItem::I(r, _, _, v)
+                | CircuitItem::K(r, _, _, v)
+                    if r == reference =>
+                {
+                    *v = value.spice();
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Err(Error(
+            String::from("spice"),
+            format!("no numeric value for {}", reference),
+        ))
+    }
 }
 
 impl Circuit {
+    /// Get the model library of the pathlist, the library is indexed when it is first used.
+    pub fn library(&self) -> Result<Arc<ModelLibrary>, Error> {
+        if let Some(library) = self.library.0.get() {
+            return Ok(library.clone());
+        }
+        let pathlist = self.pathlist.iter().map(PathBuf::from).collect::<Vec<PathBuf>>();
+        let library = Arc::new(ModelLibrary::new(&pathlist)?);
+        let _ = self.library.0.set(library.clone());
+        Ok(library)
+    }
+
+    /// Use an existing model library, e.g. a library which is shared by
+    /// several circuits or loaded with [`ModelLibrary::cached`].
+    pub fn set_library(&mut self, library: Arc<ModelLibrary>) {
+        self.library = LibraryCache(OnceCell::from(library));
+    }
+
+    /// Find the library file which defines the subcircuit or model `key` in the pathlist.
+    /// Returns the library file and the files it includes.
     pub fn get_includes(&self, key: String) -> Result<IndexMap<String, String>, Error> {
+        let library = self.library()?;
+        let Some(definition) = library.get(&key) else {
+            return Err(Error(
+                "spice".to_string(),
+                format!("spice model not found {}", key),
+            ));
+        };
         let mut result: IndexMap<String, String> = IndexMap::new();
-        for path in &self.pathlist {
-            let content = match fs::read_dir(path) {
-                Ok(content) => content,
-                Err(e) => {
-                    return Err(Error(
-                        "spice".to_string(),
-                        format!("directory not found {} ({})", path, e),
-                    ))
-                }
-            };
-            for entry in content {
-                let dir = entry.unwrap();
-                if dir.path().is_file() {
-                    let content = fs::read_to_string(dir.path())?;
-                    for cap in RE_SUBCKT.captures_iter(&content) {
-                        let text1 = cap.get(1).map_or("", |m| m.as_str());
-                        if text1 == key {
-                            result.insert(key, dir.path().to_str().unwrap().to_string());
-                            if let Some(caps) = RE_INCLUDE.captures(&content) {
-                                for cap in caps.iter().skip(1) {
-                                    let text1 = cap.map_or("", |m| m.as_str());
-                                    if !text1.contains('/') {
-                                        //when there is no slash i could be
-                                        //a relative path.
-                                        let mut parent = dir
-                                            .path()
-                                            .parent()
-                                            .unwrap()
-                                            .to_str()
-                                            .unwrap()
-                                            .to_string();
-                                        parent += "/";
-                                        parent += text1;
-                                        result.insert(text1.to_string(), parent.to_string());
-                                    } else {
-                                        result.insert(text1.to_string(), text1.to_string());
-                                    }
-                                }
-                            }
-                            return Ok(result);
-                        }
-                    }
-                    for cap in RE_MODEL.captures_iter(&content) {
-                        let text1 = cap.get(1).map_or("", |m| m.as_str());
-                        if text1 == key {
-                            result.insert(key, dir.path().to_str().unwrap().to_string());
-                            if let Some(caps) = RE_INCLUDE.captures(&content) {
-                                for cap in caps.iter().skip(1) {
-                                    let text1 = cap.map_or("", |m| m.as_str());
-                                    if !text1.contains('/') {
-                                        //when there is no slash i could be
-                                        //a relative path.
-                                        let mut parent = dir
-                                            .path()
-                                            .parent()
-                                            .unwrap()
-                                            .to_str()
-                                            .unwrap()
-                                            .to_string();
-                                        parent += "/";
-                                        parent += text1;
-                                        result.insert(text1.to_string(), parent.to_string());
-                                    } else {
-                                        result.insert(text1.to_string(), text1.to_string());
-                                    }
-                                }
-                            }
-                            return Ok(result);
-                        }
+        result.insert(key, definition.file.to_string_lossy().to_string());
+        for include in library.includes(&definition.file) {
+            let include = include.to_string_lossy().to_string();
+            result.insert(include.clone(), include);
+        }
+        Ok(result)
+    }
+
+    /// The names of the subcircuits and models which are used by the items.
+    fn used_models(&self, names: &mut Vec<String>) {
+        for item in &self.items {
+            if let CircuitItem::X(_, _, value)
+            | CircuitItem::D(_, _, _, value)
+            | CircuitItem::J(_, _, _, _, value)
+            | CircuitItem::Q(_, _, _, _, value)
+            | CircuitItem::M(_, _, _, _, _, value)
+            | CircuitItem::S(_, _, _, _, _, value)
+            | CircuitItem::W(_, _, _, _, value) = item
+            {
+                if let Some(name) = value.split_whitespace().next() {
+                    if !names.iter().any(|n| n == name) {
+                        names.push(name.to_string());
                     }
                 }
             }
         }
-        Err(Error(
-            "spice".to_string(),
-            format!("spice model not found {}", key),
-        ))
+        for (_, circuit) in self.subcircuits.values() {
+            circuit.used_models(names);
+        }
+    }
+
+    /// Test if the subcircuit or model is defined in the circuit.
+    fn defines(&self, name: &str) -> bool {
+        self.subcircuits.contains_key(name)
+            || self.models.contains_key(name)
+            || self.subcircuits.values().any(|(_, c)| c.defines(name))
     }
 
     fn includes(&self) -> Result<Vec<String>, Error> {
-        let mut includes: IndexMap<String, String> = IndexMap::new();
-        for item in &self.items {
-            if let CircuitItem::X(_, _, value) = item {
-                if !includes.contains_key(value) && !self.subcircuits.contains_key(value) {
-                    let incs = self.get_includes(value.to_string())?;
-                    for (key, value) in incs {
-                        includes.entry(key).or_insert(value);
-                    }
-                }
-            } else if let CircuitItem::J(_, _, _, _, value) = item {
-                if !includes.contains_key(value) && !self.subcircuits.contains_key(value) {
-                    let incs = self.get_includes(value.to_string())?;
-                    for (key, value) in incs {
-                        includes.entry(key).or_insert(value);
-                    }
+        let mut includes: Vec<String> = self.include_files.clone();
+        let mut libs: Vec<(String, String)> = self.lib_files.clone();
+        //the models can be defined in the included files.
+        let explicit = !self.include_files.is_empty() || !self.lib_files.is_empty();
+        let mut names = Vec::new();
+        self.used_models(&mut names);
+        for name in names {
+            if self.defines(&name) {
+                continue;
+            }
+            let library = match self.library() {
+                Ok(library) => library,
+                Err(_) if explicit => continue,
+                Err(err) => return Err(err),
+            };
+            let Some(definition) = library.get(&name) else {
+                if explicit {
+                    continue;
                 }
-            } else if let CircuitItem::Q(_, _, _, _, value) = item {
-                if !includes.contains_key(value) && !self.subcircuits.contains_key(value) {
-                    let incs = self.get_includes(value.to_string())?;
-                    for (key, value) in incs {
-                        includes.entry(key).or_insert(value);
-                    }
+                return Err(Error(
+                    "spice".to_string(),
+                    format!("spice model not found {}", name),
+                ));
+            };
+            let file = definition.file.to_string_lossy().to_string();
+            if let Some(section) = &definition.section {
+                let lib = (file, section.clone());
+                if !libs.contains(&lib) {
+                    libs.push(lib);
                 }
-            } else if let CircuitItem::D(_, _, _, value) = item {
-                if !includes.contains_key(value) && !self.subcircuits.contains_key(value) {
-                    let incs = self.get_includes(value.to_string())?;
-                    for (key, value) in incs {
-                        includes.entry(key).or_insert(value);
+            } else {
+                let files = library.includes(&definition.file);
+                for include in std::iter::once(file)
+                    .chain(files.iter().map(|f| f.to_string_lossy().to_string()))
+                {
+                    if !includes.contains(&include) {
+                        includes.push(include);
                     }
                 }
             }
         }
         let mut result = Vec::new();
-        for (_, v) in includes {
-            result.push(format!(".include {}\n", v).to_string());
+        for include in includes {
+            let include = format!(".include {}\n", include);
+            if !result.contains(&include) {
+                result.push(include);
+            }
+        }
+        for (file, section) in libs {
+            result.push(format!(".lib {} {}", file, section));
         }
         Ok(result)
     }
@@ -303,10 +1274,40 @@ impl Circuit {
         res.push(String::from(".title auto generated netlist file."));
 
         res.append(&mut self.includes()?);
+        res.append(&mut self.body());
+
+        if close {
+            for analysis in &self.analyses {
+                res.push(analysis.to_string());
+            }
+            for measure in &self.measures {
+                res.push(measure.to_string());
+            }
+            if !self.controls.is_empty() {
+                res.push(String::from(".control"));
+                for c in &self.controls {
+                    res.push(c.to_string());
+                }
+                res.push(String::from(".endc"));
+            }
+            res.push(String::from(".end"));
+        }
+        Ok(res)
+    }
+
+    /// The parameters, models, subcircuits, options and items of the circuit.
+    fn body(&self) -> Vec<String> {
+        let mut res = Vec::new();
+        for (key, value) in &self.params {
+            res.push(format!(".param {}={}", key, value));
+        }
+        for (key, value) in &self.models {
+            res.push(format!(".model {} {}", key, value));
+        }
         for (key, value) in &self.subcircuits {
             let nodes = value.0.join(" ");
             res.push(format!(".subckt {} {}", key, nodes));
-            res.append(&mut value.1.to_str(false).unwrap());
+            res.append(&mut value.1.body());
             res.push(".ends".to_string());
         }
 
@@ -345,7 +1346,7 @@ impl Circuit {
                     }
                 }
                 CircuitItem::J(reference, n0, n1, n2, value) => {
-                    if reference.starts_with('Q') {
+                    if reference.starts_with('J') {
                         res.push(format!("{} {} {} {} {}", reference, n0, n1, n2, value));
                     } else {
                         res.push(format!("J{} {} {} {} {}", reference, n0, n1, n2, value));
@@ -357,32 +1358,357 @@ impl Circuit {
                         nodes += _n;
                         nodes += " ";
                     }
-                    res.push(format!("X{} {}{}", reference, nodes, value));
+                    res.push(format!("{} {}{}", prefix('X', reference), nodes, value));
                 }
                 CircuitItem::V(reference, n0, n1, value) => {
-                    res.push(format!("V{} {} {} {}", reference, n0, n1, value));
+                    if reference.starts_with('V') {
+                        res.push(format!("{} {} {} {}", reference, n0, n1, value));
+                    } else {
+                        res.push(format!("V{} {} {} {}", reference, n0, n1, value));
+                    }
+                }
+                CircuitItem::L(reference, n0, n1, value) => {
+                    res.push(format!("{} {} {} {}", prefix('L', reference), n0, n1, value));
+                }
+                CircuitItem::I(reference, n0, n1, value) => {
+                    res.push(format!("{} {} {} {}", prefix('I', reference), n0, n1, value));
+                }
+                CircuitItem::M(reference, nd, ng, ns, nb, value) => {
+                    res.push(format!(
+                        "{} {} {} {} {} {}",
+                        prefix('M', reference),
+                        nd,
+                        ng,
+                        ns,
+                        nb,
+                        value
+                    ));
+                }
+                CircuitItem::K(reference, l0, l1, value) => {
+                    res.push(format!(
+                        "{} {} {} {}",
+                        prefix('K', reference),
+                        prefix('L', l0),
+                        prefix('L', l1),
+                        value
+                    ));
+                }
+                CircuitItem::E(reference, n0, n1, nc0, nc1, value) => {
+                    res.push(format!(
+                        "{} {} {} {} {} {}",
+                        prefix('E', reference),
+                        n0,
+                        n1,
+                        nc0,
+                        nc1,
+                        value
+                    ));
+                }
+                CircuitItem::F(reference, n0, n1, vcontrol, value) => {
+                    res.push(format!(
+                        "{} {} {} {} {}",
+                        prefix('F', reference),
+                        n0,
+                        n1,
+                        prefix('V', vcontrol),
+                        value
+                    ));
+                }
+                CircuitItem::G(reference, n0, n1, nc0, nc1, value) => {
+                    res.push(format!(
+                        "{} {} {} {} {} {}",
+                        prefix('G', reference),
+                        n0,
+                        n1,
+                        nc0,
+                        nc1,
+                        value
+                    ));
+                }
+                CircuitItem::H(reference, n0, n1, vcontrol, value) => {
+                    res.push(format!(
+                        "{} {} {} {} {}",
+                        prefix('H', reference),
+                        n0,
+                        n1,
+                        prefix('V', vcontrol),
+                        value
+                    ));
+                }
+                CircuitItem::B(reference, n0, n1, value) => {
+                    res.push(format!("{} {} {} {}", prefix('B', reference), n0, n1, value));
+                }
+                CircuitItem::S(reference, n0, n1, nc0, nc1, value) => {
+                    res.push(format!(
+                        "{} {} {} {} {} {}",
+                        prefix('S', reference),
+                        n0,
+                        n1,
+                        nc0,
+                        nc1,
+                        value
+                    ));
+                }
+                CircuitItem::W(reference, n0, n1, vcontrol, value) => {
+                    res.push(format!(
+                        "{} {} {} {} {}",
+                        prefix('W', reference),
+                        n0,
+                        n1,
+                        prefix('V', vcontrol),
+                        value
+                    ));
                 }
             }
         }
 
-        if close {
-            res.push(String::from(".end"));
-        }
-        Ok(res)
+        res
+    }
+}
+
+/// Prefix the reference with the spice element letter, when it does not already start with it.
+fn prefix(letter: char, reference: &str) -> String {
+    if reference.starts_with(letter) {
+        reference.to_string()
+    } else {
+        format!("{}{}", letter, reference)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::RE_SUBCKT;
+    #[test]
+    fn test_subckt_definition() {
+        let definitions = crate::circuit_reader::definitions(".SUBCKT CMOS4007 1 2 3 4 5 6 7 8 9 10 11 12 13 14");
+        assert_eq!(vec![(String::from("CMOS4007"), None)], definitions.subckts);
+    }
+
+    #[test]
+    fn test_items() {
+        let s = |s: &str| s.to_string();
+        let mut circuit = crate::Circuit::new(s("items"), vec![s("tests/spice/models")]);
+        circuit.inductor(s("L1"), s("1"), s("2"), s("10u"));
+        circuit.inductor(s("2"), s("3"), s("0"), s("10u"));
+        circuit.coupling(s("K1"), s("L1"), s("2"), s("0.99"));
+        circuit.current(s("I1"), s("0"), s("1"), s("1m"));
+        circuit.mosfet(s("Q1"), s("d"), s("g"), s("0"), s("0"), s("BS170"));
+        circuit.vcvs(s("E1"), s("4"), s("0"), s("1"), s("2"), s("10"));
+        circuit.cccs(s("F1"), s("5"), s("0"), s("1"), s("2"));
+        circuit.vccs(s("G1"), s("6"), s("0"), s("1"), s("2"), s("1m"));
+        circuit.ccvs(s("H1"), s("7"), s("0"), s("V1"), s("1k"));
+        circuit.behavioral(s("B1"), s("8"), s("0"), s("V=V(1)*V(2)"));
+        circuit.switch(s("S1"), s("9"), s("0"), s("1"), s("0"), s("SMOD"));
+        circuit.current_switch(s("W1"), s("10"), s("0"), s("V1"), s("CSMOD"));
+        assert_eq!(
+            vec![
+                s(".title auto generated netlist file."),
+                s(".include tests/spice/models/models.lib\n"),
+                s("L1 1 2 10u"),
+                s("L2 3 0 10u"),
+                s("K1 L1 L2 0.99"),
+                s("I1 0 1 1m"),
+                s("MQ1 d g 0 0 BS170"),
+                s("E1 4 0 1 2 10"),
+                s("F1 5 0 V1 2"),
+                s("G1 6 0 1 2 1m"),
+                s("H1 7 0 V1 1k"),
+                s("B1 8 0 V=V(1)*V(2)"),
+                s("S1 9 0 1 0 SMOD"),
+                s("W1 10 0 V1 CSMOD"),
+                s(".end"),
+            ],
+            circuit.to_str(true).unwrap()
+        );
+    }
 
     #[test]
-    fn test_subckt_regext() {
-        let cap = RE_SUBCKT.captures_iter(".SUBCKT CMOS4007 1 2 3 4 5 6 7 8 9 10 11 12 13 14");
-        let mut res = String::from("not found");
-        for c in cap {
-            res = c.get(1).map_or("", |m| m.as_str()).to_string();
+    fn test_item_values() {
+        use super::CircuitItem;
+        let s = |s: &str| s.to_string();
+        let c = |value: &str| CircuitItem::C(s("C1"), s("1"), s("0"), s(value));
+        assert_eq!(c("100nF"), c("0.1u"));
+        assert_eq!(c("4.7n"), c("4700p"));
+        assert_ne!(c("100n"), c("100u"));
+        assert_ne!(c("{cload}"), c("100n"));
+        assert_eq!(
+            CircuitItem::D(s("D1"), s("1"), s("0"), s("1N4148")),
+            CircuitItem::D(s("D1"), s("1"), s("0"), s("1N4148"))
+        );
+        assert_ne!(c("100n"), CircuitItem::R(s("C1"), s("1"), s("0"), s("100n")));
+
+        let mut circuit = crate::Circuit::new(s("values"), vec![]);
+        circuit.capacitor(s("C1"), s("1"), s("0"), s("100nF"));
+        circuit.set_number("C1", "0.1u".parse().unwrap()).unwrap();
+        assert_eq!(Some("100n"), circuit.value("C1"));
+        assert_eq!(c("100nF"), circuit.items[0]);
+        assert!(circuit.set_number("C2", super::Value::new(1.0)).is_err());
+        circuit.diode(s("D1"), s("1"), s("0"), s("1N4148"));
+        assert!(circuit.set_number("D1", super::Value::new(1.0)).is_err());
+        circuit.set_value("C1", "{cload}").unwrap();
+        assert_eq!(Some("{cload}"), circuit.value("C1"));
+    }
+
+    #[test]
+    fn test_library_includes() {
+        let s = |s: &str| s.to_string();
+        let mut circuit = crate::Circuit::new(
+            s("library"),
+            vec![s("tests/spice/models"), s("tests/spice/library")],
+        );
+        circuit.circuit(s("U1"), vec![s("1"), s("2"), s("3"), s("vcc"), s("vee")], s("opamp")).unwrap();
+        circuit.bjt(s("1"), s("c"), s("b"), s("0"), s("QFAST"));
+        circuit.switch(s("S1"), s("9"), s("0"), s("1"), s("0"), s("SMOD"));
+        assert_eq!(
+            vec![
+                s(".title auto generated netlist file."),
+                s(".include tests/spice/library/opamp.lib\n"),
+                s(".include tests/spice/library/common/diodes.lib\n"),
+                s(".include tests/spice/library/common/switches.lib\n"),
+                s(".include tests/spice/models/models.lib\n"),
+                s(".lib tests/spice/library/corners.lib fast"),
+                s("XU1 1 2 3 vcc vee opamp"),
+                s("Q1 c b 0 QFAST"),
+                s("S1 9 0 1 0 SMOD"),
+                s(".end"),
+            ],
+            circuit.to_str(true).unwrap()
+        );
+
+        let library = circuit.library().unwrap();
+        let mut other = crate::Circuit::new(s("other"), vec![]);
+        other.set_library(library.clone());
+        assert!(std::sync::Arc::ptr_eq(&library, &other.library().unwrap()));
+    }
+
+    #[test]
+    fn test_source_spec() {
+        use super::SourceSpec;
+        let s = |s: &str| s.to_string();
+        let mut circuit = crate::Circuit::new(s("sources"), vec![]);
+        circuit
+            .voltage_source(
+                s("V1"),
+                s("in"),
+                s("0"),
+                &[
+                    SourceSpec::Dc(0.0),
+                    SourceSpec::Ac { magnitude: 1.0, phase: 0.0 },
+                    SourceSpec::Sin {
+                        offset: 0.0,
+                        amplitude: 1.0,
+                        frequency: 1000.0,
+                        delay: 0.0,
+                        damping: 0.0,
+                        phase: 0.0,
+                    },
+                ],
+            )
+            .unwrap();
+        circuit
+            .current_source(
+                s("I1"),
+                s("0"),
+                s("in"),
+                &[SourceSpec::pwl_file(std::path::Path::new("tests/spice/pwl.txt")).unwrap()],
+            )
+            .unwrap();
+        assert_eq!(
+            vec![
+                s(".title auto generated netlist file."),
+                s("V1 in 0 DC 0 AC 1 0 SIN(0 1 1000 0 0 0)"),
+                s("I1 0 in PWL(0 0 0.001 1 0.002 0.5)"),
+                s(".end"),
+            ],
+            circuit.to_str(true).unwrap()
+        );
+
+        //only one transient function per source
+        assert!(circuit
+            .voltage_source(
+                s("V2"),
+                s("in"),
+                s("0"),
+                &[SourceSpec::Pwl(vec![(0.0, 0.0)]), SourceSpec::Pwl(vec![(0.0, 1.0)])]
+            )
+            .is_err());
+        assert!(SourceSpec::Pwl(vec![(0.0, 0.0), (0.0, 1.0)]).validate().is_err());
+        assert!(SourceSpec::Exp {
+            v1: 0.0,
+            v2: 1.0,
+            rise_delay: 0.0,
+            rise_tau: 0.0,
+            fall_delay: 1.0,
+            fall_tau: 1.0
         }
-        assert_eq!("CMOS4007", res)
+        .validate()
+        .is_err());
+        assert!(SourceSpec::Dc(f64::NAN).validate().is_err());
+    }
+
+    #[test]
+    fn test_analysis() {
+        use super::{Analysis, Measure, MeasureAnalysis, MeasureFunction, Statistic, Step, Sweep};
+        let s = |s: &str| s.to_string();
+        let mut circuit = crate::Circuit::new(s("analysis"), vec![]);
+        circuit.param("rload", "10k").unwrap();
+        circuit.resistor(s("R1"), s("in"), s("out"), s("{rload}"));
+        circuit.measure(Measure {
+            name: s("vmax"),
+            analysis: MeasureAnalysis::Tran,
+            function: MeasureFunction::Stat {
+                statistic: Statistic::Max,
+                vector: s("v(out)"),
+                from: Some(1e-3),
+                to: None,
+            },
+        }).unwrap();
+        circuit.analysis(Analysis::Tran { step: 1e-6, stop: 10e-3, start: None, max_step: None }).unwrap();
+        circuit.analysis(Analysis::Ac { sweep: Sweep::Dec(10), start: 1.0, stop: 100e3 }).unwrap();
+        circuit.analysis(Analysis::Op).unwrap();
+        circuit.step(Step::List { param: s("rload"), values: vec![1e3, 10e3] }).unwrap();
+        assert_eq!(
+            vec![
+                s(".title auto generated netlist file."),
+                s(".param rload=10k"),
+                s("R1 in out {rload}"),
+                s(".tran 1e-6 0.01"),
+                s(".ac dec 10 1 100000"),
+                s(".op"),
+                s(".meas tran vmax MAX v(out) FROM=0.001"),
+                s(".end"),
+            ],
+            circuit.to_str(true).unwrap()
+        );
+
+        assert!(circuit.analysis(Analysis::Tran { step: 1e-3, stop: 1e-6, start: None, max_step: None }).is_err());
+        assert!(circuit.analysis(Analysis::Ac { sweep: Sweep::Dec(0), start: 1.0, stop: 10.0 }).is_err());
+        assert!(circuit.analysis(Analysis::Ac { sweep: Sweep::Oct(10), start: 0.0, stop: 10.0 }).is_err());
+        assert!(circuit.analysis(Analysis::Dc { source: s("V1"), start: 0.0, stop: 5.0, step: -0.1 }).is_err());
+        assert!(circuit.step(Step::Lin { param: s("cload"), start: 1.0, stop: 2.0, step: 0.5 }).is_err());
+        assert!(circuit.param("1x", "1").is_err());
+    }
+
+    #[test]
+    fn test_variants() {
+        use super::Step;
+        let s = |s: &str| s.to_string();
+        let mut circuit = crate::Circuit::new(s("step"), vec![]);
+        circuit.param("r", "1k").unwrap();
+        circuit.param("c", "1u").unwrap();
+        circuit.resistor(s("R1"), s("in"), s("out"), s("{r}"));
+        circuit.capacitor(s("C1"), s("out"), s("0"), s("{c}"));
+        assert_eq!(vec![circuit.clone()], circuit.variants());
+
+        let lin = Step::Lin { param: s("r"), start: 0.1, stop: 0.3, step: 0.1 };
+        assert_eq!(3, lin.values().len());
+        circuit.step(lin).unwrap();
+        circuit.step(Step::List { param: s("c"), values: vec![1e-6, 2e-6] }).unwrap();
+        let variants = circuit.variants();
+        assert_eq!(6, variants.len());
+        assert!(variants.iter().all(|v| v.steps().is_empty()));
+        let params = |circuit: &crate::Circuit| circuit.to_str(true).unwrap()[1..3].to_vec();
+        assert_eq!(vec![s(".param r=0.1"), s(".param c=1e-6")], params(&variants[0]));
+        assert_eq!(vec![s(".param r=0.1"), s(".param c=2e-6")], params(&variants[1]));
+        assert_eq!(vec![s(".param r=0.30000000000000004"), s(".param c=2e-6")], params(&variants[5]));
     }
 }
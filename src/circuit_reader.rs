@@ -0,0 +1,610 @@
+//! Read a spice netlist into a [`Circuit`].
+//!
+//! The reader handles comments, continuation lines, the element cards and the dot
+//! commands `.subckt`, `.model`, `.include`, `.lib`, `.param`, `.control`, the analysis
+//! commands, `.step` and `.meas`. All other dot commands are stored in [`Circuit::options`].
+use std::path::Path;
+
+use crate::{
+    circuit::{
+        Analysis, CircuitItem, Measure, MeasureAnalysis, MeasureFunction, Statistic, Step, Sweep,
+    },
+    value::Value,
+    Circuit, Error,
+};
+
+/// A logical line of the netlist with the line number.
+type Line = (usize, String);
+
+/// Split the content into logical lines. Comments are removed
+/// and continuation lines are joined.
+fn lines(content: &str) -> Vec<Line> {
+    let mut lines: Vec<Line> = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('*') {
+            continue;
+        }
+        //inline comments
+        let line = line.split(';').next().unwrap_or_default();
+        let line = match line.find(" $") {
+            Some(index) => &line[..index],
+            None => line,
+        }
+        .trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(continuation) = line.strip_prefix('+') {
+            if let Some((_, last)) = lines.last_mut() {
+                last.push(' ');
+                last.push_str(continuation.trim());
+                continue;
+            }
+        }
+        lines.push((number + 1, line.to_string()));
+    }
+    lines
+}
+
+/// The names which are defined in a spice library file.
+///
+/// The subcircuits and models are stored with the name of the
+/// `.lib` section which contains them.
+#[derive(Debug, Default)]
+pub(crate) struct Definitions {
+    pub subckts: Vec<(String, Option<String>)>,
+    pub models: Vec<(String, Option<String>)>,
+    /// The included files, `.include file` and `.lib file section`.
+    pub includes: Vec<String>,
+}
+
+/// Get the subcircuits, models and includes which are defined in a library file.
+pub(crate) fn definitions(content: &str) -> Definitions {
+    let mut definitions = Definitions::default();
+    let mut section: Option<String> = None;
+    for (_, line) in lines(content) {
+        let tokens = line.split_whitespace().collect::<Vec<&str>>();
+        match tokens[0].to_lowercase().as_str() {
+            ".subckt" if tokens.len() > 1 => {
+                definitions.subckts.push((tokens[1].to_string(), section.clone()))
+            }
+            ".model" if tokens.len() > 1 => {
+                definitions.models.push((tokens[1].to_string(), section.clone()))
+            }
+            ".include" | ".inc" if tokens.len() > 1 => {
+                definitions.includes.push(unquote(&tokens[1..].join(" ")))
+            }
+            ".lib" if tokens.len() == 2 => section = Some(tokens[1].to_string()),
+            ".lib" if tokens.len() > 2 => definitions.includes.push(unquote(tokens[1])),
+            ".endl" => section = None,
+            _ => {}
+        }
+    }
+    definitions
+}
+
+fn unquote(value: &str) -> String {
+    value.trim().trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+/// Split the parameters of a `.param` line, expressions in braces or quotes
+/// are kept together and spaces around `=` are allowed: `gain = {a + b}`.
+fn param_tokens(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut token = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '{') => depth += 1,
+            (None, '}') => depth -= 1,
+            (None, c) if c.is_whitespace() && depth <= 0 => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                continue;
+            }
+            _ => {}
+        }
+        token.push(c);
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+
+    //join the tokens of `key = value`
+    let mut params: Vec<String> = Vec::new();
+    for token in tokens {
+        match params.last_mut() {
+            Some(last) if last.ends_with('=') || token.starts_with('=') => last.push_str(&token),
+            _ => params.push(token),
+        }
+    }
+    params
+}
+
+fn error<T>(number: usize, msg: String) -> Result<T, Error> {
+    Err(Error(String::from("spice"), format!("line {}: {}", number, msg)))
+}
+
+impl Circuit {
+    /// Load a spice netlist from a file.
+    ///
+    /// The first line of the file is the title. Included files are not
+    /// loaded, they are kept as `.include` statements.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use recad_core::Circuit;
+    ///
+    /// let mut circuit = Circuit::load(Path::new("tests/spice/rc.cir")).unwrap();
+    /// circuit.set_value("R1", "2k").unwrap();
+    /// assert!(circuit.to_str(true).unwrap().contains(&String::from("R1 in out 2k")));
+    /// ```
+    pub fn load(path: &Path) -> Result<Circuit, Error> {
+        let content = std::fs::read_to_string(path)?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let pathlist = path
+            .parent()
+            .map(|p| vec![p.to_string_lossy().to_string()])
+            .unwrap_or_default();
+        let mut circuit = Circuit::parse(&content)?;
+        if circuit.name.is_empty() {
+            circuit.name = name;
+        }
+        circuit.pathlist = pathlist;
+        Ok(circuit)
+    }
+
+    /// Parse a spice netlist, the first line is the title.
+    pub fn parse(content: &str) -> Result<Circuit, Error> {
+        let title = content.lines().next().unwrap_or_default().trim();
+        let title = title
+            .strip_prefix(".title")
+            .or(title.strip_prefix(".TITLE"))
+            .unwrap_or(title)
+            .trim();
+        let mut circuit = Circuit::new(title.to_string(), Vec::new());
+        let lines = lines(content.split_once('\n').map(|(_, c)| c).unwrap_or_default())
+            .into_iter()
+            .map(|(number, line)| (number + 1, line))
+            .collect::<Vec<Line>>();
+        let mut lines = lines.into_iter();
+        circuit.parse_lines(&mut lines, None)?;
+        Ok(circuit)
+    }
+
+    fn parse_lines(
+        &mut self,
+        lines: &mut impl Iterator<Item = Line>,
+        subckt: Option<&str>,
+    ) -> Result<(), Error> {
+        while let Some((number, line)) = lines.next() {
+            let tokens = line.split_whitespace().collect::<Vec<&str>>();
+            let command = tokens[0].to_lowercase();
+            if !command.starts_with('.') {
+                self.items.push(element(number, &tokens)?);
+                continue;
+            }
+            match command.as_str() {
+                ".end" => break,
+                ".ends" => {
+                    if subckt.is_none() {
+                        return error(number, String::from(".ends without .subckt"));
+                    }
+                    return Ok(());
+                }
+                ".subckt" => {
+                    if tokens.len() < 2 {
+                        return error(number, String::from(".subckt without name"));
+                    }
+                    let mut circuit = Circuit::new(tokens[1].to_string(), Vec::new());
+                    circuit.parse_lines(lines, Some(tokens[1]))?;
+                    self.subcircuits.insert(
+                        tokens[1].to_string(),
+                        (tokens[2..].iter().map(|t| t.to_string()).collect(), circuit),
+                    );
+                }
+                ".model" => {
+                    if tokens.len() < 3 {
+                        return error(number, String::from(".model without name or type"));
+                    }
+                    self.models.insert(tokens[1].to_string(), tokens[2..].join(" "));
+                }
+                ".include" | ".inc" => {
+                    if tokens.len() < 2 {
+                        return error(number, String::from(".include without file"));
+                    }
+                    self.include_files.push(unquote(&tokens[1..].join(" ")));
+                }
+                ".lib" => {
+                    if tokens.len() != 3 {
+                        return error(number, String::from("expected .lib file section"));
+                    }
+                    self.lib_files.push((unquote(tokens[1]), tokens[2].to_string()));
+                }
+                ".param" => {
+                    let text = line.trim().split_once(char::is_whitespace).map(|(_, t)| t);
+                    for param in param_tokens(text.unwrap_or_default()) {
+                        let Some((key, value)) = param.split_once('=') else {
+                            return error(number, format!("invalid parameter '{}'", param));
+                        };
+                        self.param(key, value)
+                            .or_else(|e| error(number, e.1))?;
+                    }
+                }
+                ".control" => {
+                    for (_, line) in lines.by_ref() {
+                        if line.to_lowercase() == ".endc" {
+                            break;
+                        }
+                        self.controls.push(line);
+                    }
+                }
+                ".op" | ".dc" | ".ac" | ".tran" | ".noise" | ".tf" => {
+                    let analysis = analysis(number, &command, &tokens[1..])?;
+                    self.analysis(analysis).or_else(|e| error(number, e.1))?;
+                }
+                ".step" => {
+                    let step = step(number, &tokens[1..])?;
+                    self.step(step).or_else(|e| error(number, e.1))?;
+                }
+                ".meas" | ".measure" => {
+                    let measure = measure(number, &tokens[1..])?;
+                    self.measure(measure).or_else(|e| error(number, e.1))?;
+                }
+                _ => {
+                    let value = tokens[1..].join(" ");
+                    let option = self.options.entry(command[1..].to_string()).or_default();
+                    if !option.is_empty() {
+                        option.push(' ');
+                    }
+                    option.push_str(&value);
+                }
+            }
+        }
+        if let Some(name) = subckt {
+            return Err(Error(
+                String::from("spice"),
+                format!(".subckt {} without .ends", name),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Parse an element card.
+fn element(number: usize, tokens: &[&str]) -> Result<CircuitItem, Error> {
+    let reference = tokens[0].to_string();
+    let nodes = |count: usize| -> Result<(Vec<String>, String), Error> {
+        if tokens.len() < count + 2 {
+            return error(number, format!("{} expects {} nodes and a value", reference, count));
+        }
+        Ok((
+            tokens[1..count + 1].iter().map(|t| t.to_string()).collect(),
+            tokens[count + 1..].join(" "),
+        ))
+    };
+    let letter = reference.chars().next().unwrap_or_default().to_ascii_uppercase();
+    Ok(match letter {
+        'R' | 'C' | 'L' | 'D' | 'V' | 'I' | 'B' | 'K' => {
+            let (n, value) = nodes(2)?;
+            let (n0, n1) = (n[0].clone(), n[1].clone());
+            match letter {
+                'R' => CircuitItem::R(reference, n0, n1, value),
+                'C' => CircuitItem::C(reference, n0, n1, value),
+                'L' => CircuitItem::L(reference, n0, n1, value),
+                'D' => CircuitItem::D(reference, n0, n1, value),
+                'V' => CircuitItem::V(reference, n0, n1, value),
+                'I' => CircuitItem::I(reference, n0, n1, value),
+                'B' => CircuitItem::B(reference, n0, n1, value),
+                _ => CircuitItem::K(reference, n0, n1, value),
+            }
+        }
+        'Q' | 'J' | 'F' | 'H' | 'W' => {
+            let (n, value) = nodes(3)?;
+            let (n0, n1, n2) = (n[0].clone(), n[1].clone(), n[2].clone());
+            match letter {
+                'Q' => CircuitItem::Q(reference, n0, n1, n2, value),
+                'J' => CircuitItem::J(reference, n0, n1, n2, value),
+                'F' => CircuitItem::F(reference, n0, n1, n2, value),
+                'H' => CircuitItem::H(reference, n0, n1, n2, value),
+                _ => CircuitItem::W(reference, n0, n1, n2, value),
+            }
+        }
+        'M' | 'E' | 'G' | 'S' => {
+            let (n, value) = nodes(4)?;
+            let (n0, n1, n2, n3) = (n[0].clone(), n[1].clone(), n[2].clone(), n[3].clone());
+            match letter {
+                'M' => CircuitItem::M(reference, n0, n1, n2, n3, value),
+                'E' => CircuitItem::E(reference, n0, n1, n2, n3, value),
+                'G' => CircuitItem::G(reference, n0, n1, n2, n3, value),
+                _ => CircuitItem::S(reference, n0, n1, n2, n3, value),
+            }
+        }
+        'X' => {
+            //the subcircuit name is the last token which is not a parameter.
+            let Some(name) = tokens.iter().rposition(|t| !t.contains('=')).filter(|i| *i > 0)
+            else {
+                return error(number, format!("{} has no subcircuit name", reference));
+            };
+            CircuitItem::X(
+                reference,
+                tokens[1..name].iter().map(|t| t.to_string()).collect(),
+                tokens[name..].join(" "),
+            )
+        }
+        _ => return error(number, format!("unsupported element '{}'", reference)),
+    })
+}
+
+fn values(number: usize, tokens: &[&str]) -> Result<Vec<f64>, Error> {
+    tokens
+        .iter()
+        .map(|t| number_or_error(number, t))
+        .collect()
+}
+
+fn number_or_error(number: usize, value: &str) -> Result<f64, Error> {
+    match Value::parse_spice(value) {
+        Ok(value) => Ok(value.value),
+        Err(_) => error(number, format!("invalid number '{}'", value)),
+    }
+}
+
+fn sweep(number: usize, kind: &str, points: &str) -> Result<Sweep, Error> {
+    let Ok(points) = points.parse::<u32>() else {
+        return error(number, format!("invalid number of points '{}'", points));
+    };
+    match kind.to_lowercase().as_str() {
+        "dec" => Ok(Sweep::Dec(points)),
+        "oct" => Ok(Sweep::Oct(points)),
+        "lin" => Ok(Sweep::Lin(points)),
+        kind => error(number, format!("unknown sweep '{}'", kind)),
+    }
+}
+
+/// Parse the analysis commands.
+fn analysis(number: usize, command: &str, tokens: &[&str]) -> Result<Analysis, Error> {
+    match (command, tokens) {
+        (".op", []) => Ok(Analysis::Op),
+        (".dc", [source, start, stop, step]) => {
+            let values = values(number, &[start, stop, step])?;
+            Ok(Analysis::Dc {
+                source: source.to_string(),
+                start: values[0],
+                stop: values[1],
+                step: values[2],
+            })
+        }
+        (".ac", [kind, points, start, stop]) => Ok(Analysis::Ac {
+            sweep: sweep(number, kind, points)?,
+            start: number_or_error(number, start)?,
+            stop: number_or_error(number, stop)?,
+        }),
+        (".tran", [step, stop, rest @ ..]) if rest.len() <= 2 => {
+            let values = values(number, rest)?;
+            Ok(Analysis::Tran {
+                step: number_or_error(number, step)?,
+                stop: number_or_error(number, stop)?,
+                start: values.first().copied(),
+                max_step: values.get(1).copied(),
+            })
+        }
+        (".noise", [output, source, kind, points, start, stop, ..]) => {
+            let output = output.to_lowercase();
+            let Some(output) = output.strip_prefix("v(").and_then(|o| o.strip_suffix(')')) else {
+                return error(number, format!("invalid noise output '{}'", output));
+            };
+            let (output, reference) = match output.split_once(',') {
+                Some((output, reference)) => (output.trim(), Some(reference.trim().to_string())),
+                None => (output.trim(), None),
+            };
+            Ok(Analysis::Noise {
+                output: output.to_string(),
+                reference,
+                source: source.to_string(),
+                sweep: sweep(number, kind, points)?,
+                start: number_or_error(number, start)?,
+                stop: number_or_error(number, stop)?,
+            })
+        }
+        (".tf", [output, source]) => Ok(Analysis::Tf {
+            output: output.to_string(),
+            source: source.to_string(),
+        }),
+        _ => error(number, format!("unsupported {} '{}'", command, tokens.join(" "))),
+    }
+}
+
+/// Parse the `.step` command.
+fn step(number: usize, tokens: &[&str]) -> Result<Step, Error> {
+    let tokens = match tokens.first() {
+        Some(t) if t.eq_ignore_ascii_case("param") => &tokens[1..],
+        _ => tokens,
+    };
+    match tokens {
+        [param, list, values @ ..] if list.eq_ignore_ascii_case("list") => Ok(Step::List {
+            param: param.to_string(),
+            values: self::values(number, values)?,
+        }),
+        [param, start, stop, step] => {
+            let values = values(number, &[start, stop, step])?;
+            Ok(Step::Lin {
+                param: param.to_string(),
+                start: values[0],
+                stop: values[1],
+                step: values[2],
+            })
+        }
+        _ => error(number, format!("unsupported .step '{}'", tokens.join(" "))),
+    }
+}
+
+/// Parse the `.meas` command.
+fn measure(number: usize, tokens: &[&str]) -> Result<Measure, Error> {
+    let unsupported = || error(number, format!("unsupported .meas '{}'", tokens.join(" ")));
+    let [analysis, name, function, rest @ ..] = tokens else {
+        return unsupported();
+    };
+    let analysis = match analysis.to_lowercase().as_str() {
+        "dc" => MeasureAnalysis::Dc,
+        "ac" => MeasureAnalysis::Ac,
+        "tran" => MeasureAnalysis::Tran,
+        _ => return unsupported(),
+    };
+    //the values of the KEY=value tokens.
+    let value = |key: &str, token: &str| -> Option<String> {
+        token
+            .split_once('=')
+            .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.to_string())
+    };
+    let statistic = match function.to_uppercase().as_str() {
+        "MAX" => Some(Statistic::Max),
+        "MIN" => Some(Statistic::Min),
+        "PP" => Some(Statistic::Pp),
+        "AVG" => Some(Statistic::Avg),
+        "RMS" => Some(Statistic::Rms),
+        "INTEG" => Some(Statistic::Integ),
+        _ => None,
+    };
+    let function = match (function.to_uppercase().as_str(), rest) {
+        (_, [vector, range @ ..]) if statistic.is_some() => {
+            let mut from = None;
+            let mut to = None;
+            for token in range {
+                if let Some(v) = value("from", token) {
+                    from = Some(number_or_error(number, &v)?);
+                } else if let Some(v) = value("to", token) {
+                    to = Some(number_or_error(number, &v)?);
+                } else {
+                    return unsupported();
+                }
+            }
+            MeasureFunction::Stat {
+                statistic: statistic.unwrap(),
+                vector: vector.to_string(),
+                from,
+                to,
+            }
+        }
+        ("FIND", [vector, at]) => match value("at", at) {
+            Some(at) => MeasureFunction::FindAt {
+                vector: vector.to_string(),
+                at: number_or_error(number, &at)?,
+            },
+            None => return unsupported(),
+        },
+        ("WHEN", [condition]) => match condition.rsplit_once('=') {
+            Some((vector, value)) => MeasureFunction::When {
+                vector: vector.to_string(),
+                value: number_or_error(number, value)?,
+            },
+            None => return unsupported(),
+        },
+        ("TRIG", [trig, trig_value, _, targ_keyword, targ, targ_value, _])
+            if targ_keyword.eq_ignore_ascii_case("TARG") =>
+        {
+            let (Some(trig_value), Some(targ_value)) =
+                (value("val", trig_value), value("val", targ_value))
+            else {
+                return unsupported();
+            };
+            MeasureFunction::TrigTarg {
+                trig: trig.to_string(),
+                trig_value: number_or_error(number, &trig_value)?,
+                targ: targ.to_string(),
+                targ_value: number_or_error(number, &targ_value)?,
+            }
+        }
+        _ => return unsupported(),
+    };
+    Ok(Measure {
+        name: name.to_string(),
+        analysis,
+        function,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{circuit::CircuitItem, Circuit};
+
+    #[test]
+    fn numbers() {
+        assert_eq!(4700.0, super::number_or_error(1, "4.7k").unwrap());
+        assert_eq!(2.2e6, super::number_or_error(1, "2.2Meg").unwrap());
+        assert_eq!(1e-3, super::number_or_error(1, "1mA").unwrap());
+        assert_eq!(1.5e-9, super::number_or_error(1, "1.5e-9").unwrap());
+        assert_eq!(-10.0, super::number_or_error(1, "-10V").unwrap());
+        assert!(super::number_or_error(1, "V1").is_err());
+    }
+
+    #[test]
+    fn load() {
+        let circuit = Circuit::load(Path::new("tests/spice/rc.cir")).unwrap();
+        let s = |s: &str| s.to_string();
+        assert_eq!("rc filter", circuit.name());
+        assert_eq!(
+            &vec![
+                CircuitItem::V(s("V1"), s("in"), s("0"), s("DC 0 AC 1 SIN(0 1 1k)")),
+                CircuitItem::R(s("R1"), s("in"), s("out"), s("1k")),
+                CircuitItem::C(s("C1"), s("out"), s("0"), s("{cload}")),
+                CircuitItem::X(s("X1"), vec![s("out"), s("buf"), s("0")], s("follower gain=2")),
+                CircuitItem::D(s("D1"), s("buf"), s("0"), s("DCLAMP")),
+                CircuitItem::J(s("J1"), s("buf"), s("in"), s("0"), s("JN")),
+                CircuitItem::X(s("XU1"), vec![s("buf"), s("sense"), s("0")], s("follower")),
+            ],
+            circuit.items()
+        );
+        assert_eq!(Some(&s("D (IS=1e-14 RS=0.1)")), circuit.models().get("DCLAMP"));
+        let (nodes, follower) = circuit.subcircuits().get("follower").unwrap();
+        assert_eq!(&vec![s("in"), s("out"), s("gnd"), s("params:"), s("gain=1")], nodes);
+        assert_eq!(2, follower.items().len());
+        assert_eq!(vec![s("run"), s("print v(out)")], circuit.controls);
+    }
+
+    #[test]
+    fn round_trip() {
+        let circuit = Circuit::load(Path::new("tests/spice/rc.cir")).unwrap();
+        let content = circuit.to_str(true).unwrap().join("\n");
+        let mut reloaded = Circuit::parse(&content).unwrap();
+        reloaded.name = circuit.name.clone();
+        reloaded.pathlist = circuit.pathlist.clone();
+        assert_eq!(circuit, reloaded);
+        assert!(content.contains("\nJ1 buf in 0 JN\n"));
+        assert!(content.contains("\nX1 out buf 0 follower gain=2\n"));
+        assert!(content.contains("\nXU1 buf sense 0 follower\n"));
+    }
+
+    #[test]
+    fn params() {
+        assert_eq!(
+            vec!["gain={a + b}", "x='c * 2'", "y=1", "z={max(1, 2)}"],
+            super::param_tokens("gain={a + b} x = 'c * 2' y=1 z= {max(1, 2)}")
+        );
+        let circuit = Circuit::parse("title\n.param a=1 b=2 gain={a + b}\n").unwrap();
+        assert_eq!(Some(&String::from("{a + b}")), circuit.params.get("gain"));
+        let content = circuit.to_str(true).unwrap().join("\n");
+        assert_eq!(circuit.params, Circuit::parse(&content).unwrap().params);
+    }
+
+    #[test]
+    fn errors() {
+        let err = Circuit::parse("title\nR1 1\n").unwrap_err();
+        assert_eq!("line 2: R1 expects 2 nodes and a value", err.1);
+        assert!(Circuit::parse("title\n.subckt amp 1 2\nR1 1 2 1k\n").is_err());
+        assert!(Circuit::parse("title\n.tran 1u\n").is_err());
+        assert!(Circuit::parse("title\nT1 1 0 2 0 z0=50\n").is_err());
+    }
+}
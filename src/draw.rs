@@ -6,6 +6,7 @@ use crate::{
     math::{self, pin_position},
     schema::{GlobalLabel, Instance, Junction, LocalLabel, NoConnect, SchemaItem, Symbol, Wire},
     sexp::constants::el,
+    value::Value,
     Drawable, Drawer, Error, Plot, Schema,
 };
 
@@ -24,6 +25,8 @@ pub enum Attribute {
     Dot(Vec<DotPosition>),
     At(At),
     Unit(u8),
+    ///The value of a symbol, it is written in the KiCad notation.
+    Value(Value),
 }
 
 ///Dot position
@@ -149,6 +152,16 @@ impl To {
         None
     }
 
+    ///Get the value, only used for symbols.
+    pub fn value(&self) -> Option<&Value> {
+        for i in &self.attributes {
+            if let Attribute::Value(value) = i {
+                return Some(value);
+            }
+        }
+        None
+    }
+
     //Get the dot positions.
     pub fn dot(&self) -> Option<&Vec<DotPosition>> {
         for i in &self.attributes {
@@ -432,10 +445,11 @@ impl Drawer<Symbol> for Schema {
             el::PROPERTY_REFERENCE,
             reference.as_str(),
         );
-        new_symbol.set_property(
-            el::PROPERTY_VALUE,
-            symbol.property(el::PROPERTY_VALUE).as_str(),
-        );
+        let value = match symbol.attrs.value() {
+            Some(value) => value.kicad(),
+            None => symbol.property(el::PROPERTY_VALUE),
+        };
+        new_symbol.set_property(el::PROPERTY_VALUE, value.as_str());
 
         //create the pins
         for pin in &lib.pins(selected_unit) {
@@ -521,6 +535,27 @@ mod tests {
 
 
     }
+
+    #[test]
+    fn test_symbol_value() {
+        use crate::{schema::Symbol, sexp::constants::el, value::{ESeries, Unit, Value}, Plot};
+
+        let mut schema = Schema::load(std::path::Path::new("tests/echo/echo.kicad_sch")).unwrap();
+        schema.items.clear();
+        schema.move_to(At::Pt(Pt { x: 50.8, y: 50.8 }));
+        let value = Value::new(98e-9).unit(Unit::Farad).snap(ESeries::E12);
+        schema.draw(Symbol::new("C1", "", "Device:C").attr(Attribute::Value(value))).unwrap();
+        schema.draw(Symbol::new("R1", Value::new(4700.0), "Device:R")).unwrap();
+        let values = schema
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                SchemaItem::Symbol(symbol) => Some(symbol.property(el::PROPERTY_VALUE)),
+                _ => None,
+            })
+            .collect::<Vec<String>>();
+        assert_eq!(vec!["100nF", "4.7k"], values);
+    }
 }
 
 
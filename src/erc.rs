@@ -0,0 +1,535 @@
+//! Electrical Rules Check.
+//!
+//! The [`Erc`] checks the pins of every net of a [`Netlist`] with the pin conflict
+//! matrix of KiCad. Every pair of connected pins is looked up in the [`PinMatrix`]:
+//! two outputs on the same net are an error, an unspecified pin is a warning.
+//! Every pin is reported once, with its worst conflict.
+//! Nets with input pins need a driving pin and power input pins need a power
+//! output pin, like the output of a regulator or a `PWR_FLAG`.
+//!
+//! The matrix and the rule severities can be changed for a project, either
+//! in code or loaded from the `erc` section of the KiCad project file.
+//!
+//! ```
+//! use std::path::Path;
+//! use recad_core::{
+//!     erc::{Erc, Rule, Severity},
+//!     netlist::Netlist,
+//!     plot::{PlotCommand, Plotter, SvgPlotter},
+//!     Plot, Schema,
+//! };
+//!
+//! let schema = Schema::load(Path::new("tests/spice/spice.kicad_sch")).unwrap();
+//! let netlist = Netlist::from(&schema).unwrap();
+//! let violations = Erc::default()
+//!     .severity(Rule::PowerPinNotDriven, Severity::Ignore)
+//!     .check(&netlist);
+//! for violation in &violations {
+//!     println!("{}", violation);
+//! }
+//!
+//! //draw the markers on the schema
+//! let mut svg = SvgPlotter::new();
+//! schema.plot(&mut svg, PlotCommand::default()).unwrap();
+//! recad_core::erc::plot(&violations, "/", &mut svg, PlotCommand::default());
+//! svg.save(Path::new("target/out/spice_erc.svg")).unwrap();
+//! ```
+use std::{fmt, path::Path};
+
+use crate::{
+    gr::{Pt, Pts},
+    netlist::{NetPin, Netlist},
+    plot::{
+        theme::{Style, Theme},
+        Paint, PlotCommand, Plotter,
+    },
+    symbols::ElectricalTypes,
+    Error,
+};
+
+/// The number of electrical pin types.
+const PIN_TYPES: usize = 12;
+
+/// The outline of the marker, the tip is at the position of the violation.
+const MARKER: [(f32, f32); 9] = [
+    (0.0, 0.0),
+    (8.0, 1.0),
+    (4.0, 3.0),
+    (13.0, 8.0),
+    (9.0, 9.0),
+    (8.0, 13.0),
+    (3.0, 4.0),
+    (1.0, 8.0),
+    (0.0, 0.0),
+];
+/// The scale of the marker outline in mm.
+const MARKER_SCALE: f32 = 0.15;
+
+/// The severity of a violation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The rule is not checked.
+    Ignore,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn from_kicad(name: &str) -> Option<Self> {
+        match name {
+            "ignore" => Some(Self::Ignore),
+            "warning" => Some(Self::Warning),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Ignore => "ignore",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The rules of the check, the names are the KiCad rule names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    /// Two connected pins are a conflict in the [`PinMatrix`].
+    PinToPin,
+    /// An input pin without a driving pin.
+    PinNotDriven,
+    /// A power input pin without a power output pin.
+    PowerPinNotDriven,
+}
+
+impl Rule {
+    const ALL: [Rule; 3] = [Rule::PinToPin, Rule::PinNotDriven, Rule::PowerPinNotDriven];
+
+    /// The rule name as used in the KiCad project file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PinToPin => "pin_to_pin",
+            Self::PinNotDriven => "pin_not_driven",
+            Self::PowerPinNotDriven => "power_pin_not_driven",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The index of the pin type in the [`PinMatrix`], this is the order of KiCad.
+fn index(pin: &ElectricalTypes) -> usize {
+    match pin {
+        ElectricalTypes::Input => 0,
+        ElectricalTypes::Output => 1,
+        ElectricalTypes::Bidirectional => 2,
+        ElectricalTypes::TriState => 3,
+        ElectricalTypes::Passive => 4,
+        ElectricalTypes::Free => 5,
+        ElectricalTypes::Unspecified => 6,
+        ElectricalTypes::PowerIn => 7,
+        ElectricalTypes::PowerOut => 8,
+        ElectricalTypes::OpenCollector => 9,
+        ElectricalTypes::OpenEmitter => 10,
+        ElectricalTypes::NoConnect => 11,
+    }
+}
+
+/// The pin type name for the messages.
+fn type_name(pin: &ElectricalTypes) -> &'static str {
+    match pin {
+        ElectricalTypes::Input => "Input",
+        ElectricalTypes::Output => "Output",
+        ElectricalTypes::Bidirectional => "Bidirectional",
+        ElectricalTypes::TriState => "Tri-state",
+        ElectricalTypes::Passive => "Passive",
+        ElectricalTypes::Free => "Free",
+        ElectricalTypes::Unspecified => "Unspecified",
+        ElectricalTypes::PowerIn => "Power input",
+        ElectricalTypes::PowerOut => "Power output",
+        ElectricalTypes::OpenCollector => "Open collector",
+        ElectricalTypes::OpenEmitter => "Open emitter",
+        ElectricalTypes::NoConnect => "Unconnected",
+    }
+}
+
+/// The pin types which drive an input pin.
+fn driving(pin: &ElectricalTypes) -> bool {
+    matches!(
+        pin,
+        ElectricalTypes::Output
+            | ElectricalTypes::Bidirectional
+            | ElectricalTypes::TriState
+            | ElectricalTypes::Passive
+            | ElectricalTypes::PowerOut
+    )
+}
+
+/// The severity for every combination of two connected pin types.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinMatrix([[Severity; PIN_TYPES]; PIN_TYPES]);
+
+impl Default for PinMatrix {
+    /// The default matrix of KiCad.
+    fn default() -> Self {
+        const O: Severity = Severity::Ignore;
+        const W: Severity = Severity::Warning;
+        const E: Severity = Severity::Error;
+        Self([
+            //I  O  Bi 3S Pas F  Un PI PO OC OE NC
+            [O, O, O, O, O, O, W, O, O, O, O, E], //Input
+            [O, E, O, W, O, O, W, O, E, E, E, E], //Output
+            [O, O, O, O, O, O, W, O, W, O, W, E], //Bidirectional
+            [O, W, O, O, O, O, W, W, E, W, W, E], //Tri-state
+            [O, O, O, O, O, O, W, O, O, O, O, E], //Passive
+            [O, O, O, O, O, O, O, O, O, O, O, E], //Free
+            [W, W, W, W, W, O, W, W, W, W, W, E], //Unspecified
+            [O, O, O, W, O, O, W, O, O, O, O, E], //Power input
+            [O, E, W, E, O, O, W, O, E, E, E, E], //Power output
+            [O, E, O, W, O, O, W, O, E, O, O, E], //Open collector
+            [O, E, W, W, O, O, W, O, E, O, O, E], //Open emitter
+            [E, E, E, E, E, E, E, E, E, E, E, E], //Unconnected
+        ])
+    }
+}
+
+impl PinMatrix {
+    /// Get the severity for two connected pins.
+    pub fn get(&self, a: &ElectricalTypes, b: &ElectricalTypes) -> Severity {
+        self.0[index(a)][index(b)]
+    }
+
+    /// Set the severity for two connected pins, the matrix stays symmetric.
+    pub fn set(mut self, a: &ElectricalTypes, b: &ElectricalTypes, severity: Severity) -> Self {
+        self.0[index(a)][index(b)] = severity;
+        self.0[index(b)][index(a)] = severity;
+        self
+    }
+
+    /// Create the matrix from the `pin_map` of a KiCad project file,
+    /// `0` is ok, `1` a warning and `2` an error.
+    fn from_kicad(pin_map: &serde_json::Value) -> Result<Self, Error> {
+        let invalid = || Error(String::from("erc"), String::from("invalid pin_map in project file"));
+        let rows = pin_map.as_array().filter(|r| r.len() == PIN_TYPES).ok_or_else(invalid)?;
+        let mut matrix = [[Severity::Ignore; PIN_TYPES]; PIN_TYPES];
+        for (row, values) in rows.iter().enumerate() {
+            let values = values.as_array().filter(|v| v.len() == PIN_TYPES).ok_or_else(invalid)?;
+            for (col, value) in values.iter().enumerate() {
+                matrix[row][col] = match value.as_u64() {
+                    Some(0) => Severity::Ignore,
+                    Some(1) => Severity::Warning,
+                    Some(2) => Severity::Error,
+                    _ => return Err(invalid()),
+                };
+            }
+        }
+        Ok(Self(matrix))
+    }
+}
+
+/// A violation of an ERC rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub message: String,
+    /// The name of the net.
+    pub net: String,
+    /// The human readable path of the sheet.
+    pub sheet: String,
+    /// The position of the violation in the sheet.
+    pub pos: Pt,
+    /// The pins involved in the violation.
+    pub items: Vec<NetPin>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {} (net {}, {} {}, {}",
+            self.severity, self.rule, self.message, self.net, self.sheet, self.pos.x, self.pos.y
+        )?;
+        for pin in &self.items {
+            write!(f, ", {}:{}", pin.reference, pin.pin)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The Electrical Rules Check with the settings of a project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Erc {
+    matrix: PinMatrix,
+    severities: [Severity; 3],
+}
+
+impl Default for Erc {
+    fn default() -> Self {
+        Self {
+            matrix: PinMatrix::default(),
+            severities: [Severity::Warning, Severity::Error, Severity::Error],
+        }
+    }
+}
+
+impl Erc {
+    /// Load the settings from the `erc` section of a KiCad project file,
+    /// missing settings use the KiCad defaults.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)?;
+        let project: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| Error(String::from("erc"), format!("{}: {}", path.display(), e)))?;
+        let mut erc = Self::default();
+        let Some(settings) = project.get("erc") else {
+            return Ok(erc);
+        };
+        if let Some(pin_map) = settings.get("pin_map") {
+            erc.matrix = PinMatrix::from_kicad(pin_map)?;
+        }
+        if let Some(severities) = settings.get("rule_severities") {
+            for rule in Rule::ALL {
+                if let Some(severity) = severities.get(rule.name()).and_then(|s| s.as_str()) {
+                    let severity = Severity::from_kicad(severity).ok_or_else(|| {
+                        Error(String::from("erc"), format!("invalid severity '{}'", severity))
+                    })?;
+                    erc = erc.severity(rule, severity);
+                }
+            }
+        }
+        Ok(erc)
+    }
+
+    /// Set the pin conflict matrix.
+    pub fn matrix(mut self, matrix: PinMatrix) -> Self {
+        self.matrix = matrix;
+        self
+    }
+
+    /// Set the severity of a rule. The severity of the [`Rule::PinToPin`]
+    /// violations is taken from the matrix, it is only checked when the
+    /// rule is not ignored.
+    pub fn severity(mut self, rule: Rule, severity: Severity) -> Self {
+        self.severities[rule as usize] = severity;
+        self
+    }
+
+    /// Check all nets of the netlist.
+    pub fn check(&self, netlist: &Netlist) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        for net in netlist.nets() {
+            let pins = net.pins.iter().chain(&net.power_pins).collect::<Vec<&NetPin>>();
+            let violation = |rule: Rule, severity: Severity, message: String, items: Vec<NetPin>| {
+                Violation {
+                    rule,
+                    severity,
+                    message,
+                    net: net.name.clone(),
+                    sheet: items[0].sheet.clone(),
+                    pos: items[0].pos,
+                    items,
+                }
+            };
+
+            if self.severities[Rule::PinToPin as usize] != Severity::Ignore {
+                //report the worst conflict of every pin, a pair is only reported once.
+                let mut reported: Vec<(usize, usize)> = Vec::new();
+                for (i, a) in pins.iter().enumerate() {
+                    let mut worst: Option<(usize, Severity)> = None;
+                    for (j, b) in pins.iter().enumerate() {
+                        if i == j || (a.reference == b.reference && a.pin == b.pin) {
+                            continue;
+                        }
+                        let severity = self.matrix.get(&a.electrical_type, &b.electrical_type);
+                        if severity != Severity::Ignore && worst.is_none_or(|(_, w)| severity > w) {
+                            worst = Some((j, severity));
+                        }
+                    }
+                    let Some((j, severity)) = worst else {
+                        continue;
+                    };
+                    if reported.contains(&(j, i)) {
+                        continue;
+                    }
+                    reported.push((i, j));
+                    let b = pins[j];
+                    violations.push(violation(
+                        Rule::PinToPin,
+                        severity,
+                        format!(
+                            "Pins of type {} and {} are connected",
+                            type_name(&a.electrical_type),
+                            type_name(&b.electrical_type)
+                        ),
+                        vec![(*a).clone(), b.clone()],
+                    ));
+                }
+            }
+
+            let undriven = |rule: Rule, driven: ElectricalTypes, driver: &dyn Fn(&ElectricalTypes) -> bool| {
+                let severity = self.severities[rule as usize];
+                let driven = pins
+                    .iter()
+                    .filter(|p| p.electrical_type == driven)
+                    .map(|p| (*p).clone())
+                    .collect::<Vec<NetPin>>();
+                if severity == Severity::Ignore
+                    || driven.is_empty()
+                    || pins.iter().any(|p| driver(&p.electrical_type))
+                {
+                    None
+                } else {
+                    Some((rule, severity, driven))
+                }
+            };
+            if let Some((rule, severity, items)) =
+                undriven(Rule::PinNotDriven, ElectricalTypes::Input, &driving)
+            {
+                violations.push(violation(
+                    rule,
+                    severity,
+                    String::from("Input pin not driven by any Output pins"),
+                    items,
+                ));
+            }
+            if let Some((rule, severity, items)) =
+                undriven(Rule::PowerPinNotDriven, ElectricalTypes::PowerIn, &|p| {
+                    *p == ElectricalTypes::PowerOut
+                })
+            {
+                violations.push(violation(
+                    rule,
+                    severity,
+                    String::from("Input Power pin not driven by any Output Power pins"),
+                    items,
+                ));
+            }
+        }
+        violations
+    }
+}
+
+/// Draw the markers of the violations on a sheet, the markers are drawn on
+/// top of the plotted schema.
+pub fn plot(violations: &[Violation], sheet: &str, plotter: &mut impl Plotter, command: PlotCommand) {
+    let theme = Theme::from(command.theme);
+    for violation in violations.iter().filter(|v| v.sheet == sheet) {
+        let style = match violation.severity {
+            Severity::Error => Style::ErcError,
+            _ => Style::ErcWarning,
+        };
+        let width = theme.width(0.0, style.clone());
+        let color = theme.color(None, style);
+        let pts = MARKER
+            .iter()
+            .map(|(x, y)| Pt {
+                x: violation.pos.x + x * MARKER_SCALE,
+                y: violation.pos.y + y * MARKER_SCALE,
+            })
+            .collect::<Vec<Pt>>();
+        plotter.polyline(
+            Pts(pts),
+            Paint {
+                color,
+                fill: Some(color),
+                width,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Erc, PinMatrix, Rule, Severity};
+    use crate::{
+        netlist::Netlist,
+        plot::{PlotCommand, SvgPlotter},
+        symbols::ElectricalTypes,
+        Plot, Schema,
+    };
+
+    #[test]
+    fn matrix() {
+        let matrix = PinMatrix::default();
+        for a in 0..super::PIN_TYPES {
+            for b in 0..super::PIN_TYPES {
+                assert_eq!(matrix.0[a][b], matrix.0[b][a]);
+            }
+        }
+        let output = ElectricalTypes::Output;
+        assert_eq!(Severity::Error, matrix.get(&output, &output));
+        assert_eq!(Severity::Ignore, matrix.get(&output, &ElectricalTypes::Input));
+        let matrix = matrix.set(&output, &ElectricalTypes::PowerOut, Severity::Warning);
+        assert_eq!(Severity::Warning, matrix.get(&ElectricalTypes::PowerOut, &output));
+        assert!(!super::driving(&ElectricalTypes::OpenCollector));
+        assert!(!super::driving(&ElectricalTypes::OpenEmitter));
+    }
+
+    #[test]
+    fn check() {
+        //the summe schema has power flags on all supply nets
+        let schema = Schema::load(Path::new("tests/summe/summe.kicad_sch")).unwrap();
+        assert!(Erc::default().check(&Netlist::from(&schema).unwrap()).is_empty());
+
+        let schema = Schema::load(Path::new("tests/spice/spice.kicad_sch")).unwrap();
+        let netlist = Netlist::from(&schema).unwrap();
+        let violations = Erc::default().check(&netlist);
+        assert_eq!(1, violations.len());
+        assert_eq!(Rule::PowerPinNotDriven, violations[0].rule);
+        assert_eq!(Severity::Error, violations[0].severity);
+        assert_eq!("GND", violations[0].net);
+        assert_eq!(4, violations[0].items.len());
+        assert_eq!(violations[0].items[0].pos, violations[0].pos);
+        assert!(Erc::default()
+            .severity(Rule::PowerPinNotDriven, Severity::Ignore)
+            .check(&netlist)
+            .is_empty());
+
+        let passive = ElectricalTypes::Passive;
+        let conflicts = Erc::default()
+            .matrix(PinMatrix::default().set(&passive, &passive, Severity::Warning))
+            .severity(Rule::PowerPinNotDriven, Severity::Ignore)
+            .check(&netlist);
+        assert!(!conflicts.is_empty());
+        assert!(conflicts.iter().all(|v| v.rule == Rule::PinToPin
+            && v.severity == Severity::Warning
+            && v.items.len() == 2));
+        //every pin is reported once with its worst conflict
+        for net in netlist.nets() {
+            let count = conflicts.iter().filter(|v| v.net == net.name).count();
+            assert!(count < (net.pins.len() + net.power_pins.len()).max(1));
+        }
+        let mut first = conflicts
+            .iter()
+            .map(|v| (v.net.clone(), v.items[0].reference.clone(), v.items[0].pin.clone()))
+            .collect::<Vec<_>>();
+        first.sort();
+        first.dedup();
+        assert_eq!(conflicts.len(), first.len());
+
+        let mut svg = SvgPlotter::new();
+        schema.plot(&mut svg, PlotCommand::default()).unwrap();
+        super::plot(&violations, "/", &mut svg, PlotCommand::default());
+    }
+
+    #[test]
+    fn load() {
+        let erc = Erc::load(Path::new("tests/erc/erc.kicad_pro")).unwrap();
+        let output = ElectricalTypes::Output;
+        assert_eq!(Severity::Warning, erc.matrix.get(&output, &output));
+        assert_eq!(Severity::Warning, erc.severities[Rule::PinNotDriven as usize]);
+        assert_eq!(Severity::Error, erc.severities[Rule::PowerPinNotDriven as usize]);
+        assert_eq!("io", Erc::load(Path::new("tests/erc/missing.kicad_pro")).unwrap_err().0);
+    }
+}
@@ -36,16 +36,24 @@ pub struct Pt {
     pub y: f32,
 }
 
+impl Pt {
+    /// The point on a grid of 0.01 mm, points are equal when they are on
+    /// the same grid point.
+    fn grid(&self) -> (i64, i64) {
+        ((self.x as f64 * 100.0).round() as i64, (self.y as f64 * 100.0).round() as i64)
+    }
+}
+
 impl PartialEq for Pt {
     fn eq(&self, other: &Self) -> bool {
-        format!("{:.2}x{:.2}", self.x, self.y) == format!("{:.2}x{:.2}", other.x, other.y)
+        self.grid() == other.grid()
     }
 }
 impl Eq for Pt {}
 
 impl std::hash::Hash for Pt {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        format!("{:.2}x{:.2}", self.x, self.y).hash(state);
+        self.grid().hash(state);
     }
 }
 
@@ -534,7 +542,7 @@ impl std::convert::From<PaperSize> for (f32, f32) {
 }
 
 ///The title_block token defines the contents of the title block.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct TitleBlock {
     pub title: Option<String>,
     pub date: Option<String>,
@@ -6,14 +6,23 @@ use {
     sexp::{parser::SexpParser, SexpTree},
 };
 
-mod circuit;
+pub mod chart;
+pub mod circuit;
+mod circuit_reader;
 pub mod draw;
+pub mod erc;
 pub mod gr;
 mod math;
-mod netlist;
+pub mod model_library;
+pub mod montecarlo;
+pub mod netlist;
 pub mod pcb;
 pub mod plot;
+pub mod project;
+pub mod rawfile;
 pub mod schema;
+pub mod simulator;
+pub mod value;
 pub mod footprint;
 pub mod symbols;
 mod symbols_reader;
@@ -64,9 +73,17 @@ pub struct Circuit {
     subcircuits: IndexMap<String, (Vec<String>, Circuit)>,
     pub controls: Vec<String>,
     pub options: IndexMap<String, String>,
+    params: IndexMap<String, String>,
+    models: IndexMap<String, String>,
+    include_files: Vec<String>,
+    lib_files: Vec<(String, String)>,
+    analyses: Vec<circuit::Analysis>,
+    steps: Vec<circuit::Step>,
+    measures: Vec<circuit::Measure>,
+    library: circuit::LibraryCache,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
 ///Define the `Schematic` file format.
 pub struct Schema {
     /// The Project Title
@@ -95,6 +112,20 @@ pub struct Schema {
     last_pos: draw::At,
 }
 
+///A hierarchical schematic project.
+///
+///The project contains the root [`Schema`] and all the
+///child sheets it references, see [`project`].
+#[derive(Debug)]
+pub struct Project {
+    /// The project name, taken from the file name of the root schema.
+    pub name: String,
+    /// The root sheet of the sheet tree.
+    pub root: project::Sheet,
+    /// The net settings from the project file.
+    pub net_settings: project::NetSettings,
+}
+
 ///Pcb file format for all versions of KiCad from 6.0.
 #[derive(Default)]
 pub struct Pcb {
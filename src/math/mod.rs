@@ -99,6 +99,82 @@ pub fn pin_position(symbol: &schema::Symbol, pin: &Pin) -> Pt {
     }
 }
 
+///Calculate the positions of the pins in a symbol, the same transformation as
+///[`pin_position`] without the matrices of [`Transform`].
+pub fn pin_positions(symbol: &schema::Symbol, pins: &[&Pin]) -> Vec<Pt> {
+    let (sin, cos) = symbol.pos.angle.to_radians().sin_cos();
+    let (mirror_x, mirror_y) = match symbol.mirror.as_deref() {
+        Some("x") => (1.0, 1.0),
+        Some("y") => (-1.0, -1.0),
+        Some("xy") => (-1.0, 1.0),
+        _ => (1.0, -1.0),
+    };
+    pins.iter()
+        .map(|p| {
+            let x = p.pos.x * mirror_x;
+            let y = p.pos.y * mirror_y;
+            Pt {
+                x: symbol.pos.x + x * cos + y * sin,
+                y: symbol.pos.y - x * sin + y * cos,
+            }
+        })
+        .collect()
+}
+
+/// Test if a point is on the line segment from `start` to `end`.
+pub fn on_segment(pt: Pt, start: Pt, end: Pt) -> bool {
+    const EPSILON: f32 = 0.01;
+    //the cheap bounding box test first, most segments are rejected here
+    if pt.x < start.x.min(end.x) - EPSILON
+        || pt.x > start.x.max(end.x) + EPSILON
+        || pt.y < start.y.min(end.y) - EPSILON
+        || pt.y > start.y.max(end.y) + EPSILON
+    {
+        return false;
+    }
+    let length = ((end.x - start.x).powi(2) + (end.y - start.y).powi(2)).sqrt();
+    if length < EPSILON {
+        return pt == start;
+    }
+    let cross = (end.x - start.x) * (pt.y - start.y) - (end.y - start.y) * (pt.x - start.x);
+    (cross / length).abs() < EPSILON
+}
+
+/// Test if a point is inside the polygon, the polygon is closed
+/// from the last to the first point.
+pub fn inside_polygon(pt: Pt, polygon: &[Pt]) -> bool {
+    let mut inside = false;
+    let mut last = match polygon.last() {
+        Some(last) => *last,
+        None => return false,
+    };
+    for current in polygon {
+        if (current.y > pt.y) != (last.y > pt.y)
+            && pt.x < (last.x - current.x) * (pt.y - current.y) / (last.y - current.y) + current.x
+        {
+            inside = !inside;
+        }
+        last = *current;
+    }
+    inside
+}
+
+/// Test if a line segment is inside the polygon or crosses the outline.
+pub fn segment_in_polygon(start: Pt, end: Pt, polygon: &[Pt]) -> bool {
+    if inside_polygon(start, polygon) || inside_polygon(end, polygon) {
+        return true;
+    }
+    let side = |a: Pt, b: Pt, pt: Pt| (b.x - a.x) * (pt.y - a.y) - (b.y - a.y) * (pt.x - a.x) > 0.0;
+    polygon.iter().zip(polygon.iter().cycle().skip(1)).any(|(a, b)| {
+        let crossing = side(*a, *b, start) != side(*a, *b, end)
+            && side(start, end, *a) != side(start, end, *b);
+        crossing
+            || on_segment(start, *a, *b)
+            || on_segment(end, *a, *b)
+            || on_segment(*a, start, end)
+    })
+}
+
 const PROP_SPACING: f32 = 1.25;
 const LINE_SPACING: f32 = 2.5;
 
@@ -249,5 +325,33 @@ mod tests {
         assert_eq!(res[0], *positions.first().unwrap());
         assert_eq!(res[1], *positions.get(1).unwrap());
         assert_eq!(res[2], *positions.get(2).unwrap());
+        assert_eq!(positions, super::pin_positions(symbol, &lib_symbol.pins(1)));
+
+        for mirror in [None, Some("x"), Some("y"), Some("xy")] {
+            for angle in [0.0, 90.0, 180.0, 270.0] {
+                let mut symbol = symbol.clone();
+                symbol.mirror = mirror.map(String::from);
+                symbol.pos.angle = angle;
+                let positions = lib_symbol
+                    .pins(1)
+                    .iter()
+                    .map(|p| super::pin_position(&symbol, p))
+                    .collect::<Vec<Pt>>();
+                assert_eq!(positions, super::pin_positions(&symbol, &lib_symbol.pins(1)));
+            }
+        }
+    }
+
+    #[test]
+    fn segment_in_polygon() {
+        let pt = |x: f32, y: f32| Pt { x, y };
+        let square = [pt(0.0, 0.0), pt(10.0, 0.0), pt(10.0, 10.0), pt(0.0, 10.0)];
+        assert!(super::segment_in_polygon(pt(2.0, 2.0), pt(4.0, 4.0), &square));
+        assert!(super::segment_in_polygon(pt(-5.0, 5.0), pt(5.0, 5.0), &square));
+        //crossing the area without an end inside
+        assert!(super::segment_in_polygon(pt(-5.0, 5.0), pt(15.0, 5.0), &square));
+        assert!(super::segment_in_polygon(pt(-5.0, 10.0), pt(15.0, 10.0), &square));
+        assert!(!super::segment_in_polygon(pt(-5.0, 15.0), pt(15.0, 15.0), &square));
+        assert!(!super::segment_in_polygon(pt(-5.0, -5.0), pt(-5.0, 15.0), &square));
     }
 }
@@ -0,0 +1,369 @@
+//! Index of the spice models and subcircuits in the library directories.
+//!
+//! The [`ModelLibrary`] reads all files in the library directories once and maps the
+//! names of the `.subckt` and `.model` definitions to the file and the `.lib` section
+//! which defines them. Included files are indexed as well. When a name is defined more
+//! than once, the first definition is used and a warning is recorded. The sections of
+//! a library file may define the same names, e.g. for the process corners.
+//!
+//! The index can be cached on disk, it is rebuilt when a library file or
+//! directory changes.
+//!
+//! ```
+//! use std::path::{Path, PathBuf};
+//! use recad_core::model_library::ModelLibrary;
+//!
+//! let library = ModelLibrary::new(&[PathBuf::from("tests/spice/models")]).unwrap();
+//! let definition = library.get("bs170").unwrap();
+//! assert_eq!(Path::new("tests/spice/models/models.lib"), definition.file);
+//! ```
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use indexmap::IndexMap;
+
+use crate::{circuit_reader::definitions, Error};
+
+/// The header of the cache file.
+const CACHE_HEADER: &str = "recad model library 1";
+
+/// The type of a library definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Subckt,
+    Model,
+}
+
+/// A `.subckt` or `.model` definition in a library file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelDefinition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    /// The library file which contains the definition.
+    pub file: PathBuf,
+    /// The `.lib` section which contains the definition.
+    pub section: Option<String>,
+}
+
+impl ModelDefinition {
+    /// The file and section of the definition.
+    fn location(&self) -> String {
+        match &self.section {
+            Some(section) => format!("{} ({})", self.file.display(), section),
+            None => self.file.display().to_string(),
+        }
+    }
+}
+
+/// The index of the library directories.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModelLibrary {
+    pathlist: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+    definitions: IndexMap<String, ModelDefinition>,
+    includes: IndexMap<PathBuf, Vec<PathBuf>>,
+    warnings: Vec<String>,
+}
+
+fn error<T>(msg: String) -> Result<T, Error> {
+    Err(Error(String::from("spice"), msg))
+}
+
+impl ModelLibrary {
+    /// Index all files in the library directories.
+    pub fn new(pathlist: &[PathBuf]) -> Result<Self, Error> {
+        let mut library = ModelLibrary {
+            pathlist: pathlist.to_vec(),
+            ..Default::default()
+        };
+        for path in pathlist {
+            let entries = match fs::read_dir(path) {
+                Ok(entries) => entries,
+                Err(e) => return error(format!("directory not found {} ({})", path.display(), e)),
+            };
+            let mut files = Vec::new();
+            for entry in entries {
+                let file = entry?.path();
+                if file.is_file() {
+                    files.push(file);
+                }
+            }
+            //the order of read_dir is not defined.
+            files.sort();
+            for file in files {
+                library.index(&file);
+            }
+        }
+        Ok(library)
+    }
+
+    /// Load the index from the cache file, or create the index and write
+    /// the cache file when it is missing or outdated.
+    pub fn cached(pathlist: &[PathBuf], cache: &Path) -> Result<Self, Error> {
+        if let Ok(library) = ModelLibrary::load(cache) {
+            if library.pathlist == pathlist && library.is_current(cache) {
+                return Ok(library);
+            }
+        }
+        let library = ModelLibrary::new(pathlist)?;
+        library.save(cache)?;
+        Ok(library)
+    }
+
+    /// Get the definition of a subcircuit or model, the name is not case sensitive.
+    pub fn get(&self, name: &str) -> Option<&ModelDefinition> {
+        self.definitions.get(&name.to_lowercase())
+    }
+
+    /// All definitions in the library.
+    pub fn definitions(&self) -> impl Iterator<Item = &ModelDefinition> {
+        self.definitions.values()
+    }
+
+    /// The files which are included by the library file, including
+    /// the files which are included by the included files.
+    pub fn includes(&self, file: &Path) -> Vec<PathBuf> {
+        let mut result = Vec::new();
+        let mut stack = vec![file.to_path_buf()];
+        while let Some(file) = stack.pop() {
+            for include in self.includes.get(&file).into_iter().flatten().rev() {
+                if *include != file && !result.contains(include) {
+                    result.push(include.clone());
+                    stack.push(include.clone());
+                }
+            }
+        }
+        result
+    }
+
+    /// The duplicate definitions and missing includes found while indexing.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    fn index(&mut self, file: &Path) {
+        if self.files.iter().any(|f| f == file) {
+            return;
+        }
+        self.files.push(file.to_path_buf());
+        let content = match fs::read(file) {
+            Ok(content) => String::from_utf8_lossy(&content).to_string(),
+            Err(e) => {
+                self.warn(format!("can not read {} ({})", file.display(), e));
+                return;
+            }
+        };
+        let definitions = definitions(&content);
+        let subckts = definitions.subckts.into_iter().map(|d| (DefinitionKind::Subckt, d));
+        let models = definitions.models.into_iter().map(|d| (DefinitionKind::Model, d));
+        for (kind, (name, section)) in subckts.chain(models) {
+            self.insert(ModelDefinition {
+                name,
+                kind,
+                file: file.to_path_buf(),
+                section,
+            });
+        }
+        //relative includes are relative to the library file.
+        let parent = file.parent().unwrap_or(Path::new(""));
+        let includes = definitions
+            .includes
+            .iter()
+            .map(|include| parent.join(include))
+            .collect::<Vec<PathBuf>>();
+        if !includes.is_empty() {
+            self.includes.insert(file.to_path_buf(), includes.clone());
+        }
+        for include in includes {
+            if include.is_file() {
+                self.index(&include);
+            } else {
+                self.warn(format!(
+                    "include {} not found in {}",
+                    include.display(),
+                    file.display()
+                ));
+            }
+        }
+    }
+
+    fn insert(&mut self, definition: ModelDefinition) {
+        let key = definition.name.to_lowercase();
+        if let Some(existing) = self.definitions.get(&key) {
+            //the sections of a library file define the corners of the same models.
+            if existing.file == definition.file && existing.section != definition.section {
+                return;
+            }
+            let warning = format!(
+                "duplicate definition of {} in {}, using {}",
+                definition.name,
+                definition.location(),
+                existing.location()
+            );
+            self.warn(warning);
+        } else {
+            self.definitions.insert(key, definition);
+        }
+    }
+
+    fn warn(&mut self, warning: String) {
+        log::warn!("{}", warning);
+        self.warnings.push(warning);
+    }
+
+    /// Test if the cache file is newer than the library directories and files.
+    fn is_current(&self, cache: &Path) -> bool {
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let Some(cached) = modified(cache) else {
+            return false;
+        };
+        self.pathlist.iter().chain(self.files.iter()).all(|path| {
+            modified(path).is_some_and(|time: SystemTime| time <= cached)
+        })
+    }
+
+    /// Write the index to a file.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut lines = vec![CACHE_HEADER.to_string()];
+        for path in &self.pathlist {
+            lines.push(format!("path\t{}", path.display()));
+        }
+        for file in &self.files {
+            lines.push(format!("file\t{}", file.display()));
+        }
+        for (file, includes) in &self.includes {
+            for include in includes {
+                lines.push(format!("include\t{}\t{}", file.display(), include.display()));
+            }
+        }
+        for definition in self.definitions.values() {
+            lines.push(format!(
+                "{}\t{}\t{}\t{}",
+                match definition.kind {
+                    DefinitionKind::Subckt => "subckt",
+                    DefinitionKind::Model => "model",
+                },
+                definition.name,
+                definition.file.display(),
+                definition.section.as_deref().unwrap_or_default()
+            ));
+        }
+        for warning in &self.warnings {
+            lines.push(format!("warning\t{}", warning));
+        }
+        fs::write(path, lines.join("\n") + "\n")?;
+        Ok(())
+    }
+
+    /// Read the index from a file written by [`ModelLibrary::save`].
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path)?;
+        let mut lines = content.lines();
+        if lines.next() != Some(CACHE_HEADER) {
+            return error(format!("invalid model library cache {}", path.display()));
+        }
+        let mut library = ModelLibrary::default();
+        for line in lines {
+            let tokens = line.split('\t').collect::<Vec<&str>>();
+            match tokens.as_slice() {
+                ["path", path] => library.pathlist.push(PathBuf::from(path)),
+                ["file", file] => library.files.push(PathBuf::from(file)),
+                ["include", file, include] => library
+                    .includes
+                    .entry(PathBuf::from(file))
+                    .or_default()
+                    .push(PathBuf::from(include)),
+                [kind @ ("subckt" | "model"), name, file, section] => {
+                    library.definitions.insert(
+                        name.to_lowercase(),
+                        ModelDefinition {
+                            name: name.to_string(),
+                            kind: if *kind == "subckt" {
+                                DefinitionKind::Subckt
+                            } else {
+                                DefinitionKind::Model
+                            },
+                            file: PathBuf::from(file),
+                            section: (!section.is_empty()).then(|| section.to_string()),
+                        },
+                    );
+                }
+                ["warning", warning] => library.warnings.push(warning.to_string()),
+                _ => return error(format!("invalid line in model library cache '{}'", line)),
+            }
+        }
+        Ok(library)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{DefinitionKind, ModelLibrary};
+
+    fn library() -> ModelLibrary {
+        ModelLibrary::new(&[
+            PathBuf::from("tests/spice/models"),
+            PathBuf::from("tests/spice/library"),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn index() {
+        let library = library();
+        let bs170 = library.get("BS170").unwrap();
+        assert_eq!(DefinitionKind::Model, bs170.kind);
+        assert_eq!(Path::new("tests/spice/models/models.lib"), bs170.file);
+
+        let opamp = library.get("opamp").unwrap();
+        assert_eq!(DefinitionKind::Subckt, opamp.kind);
+        assert_eq!(Path::new("tests/spice/library/opamp.lib"), opamp.file);
+        assert_eq!(None, opamp.section);
+        assert_eq!(
+            vec![
+                PathBuf::from("tests/spice/library/common/diodes.lib"),
+                PathBuf::from("tests/spice/library/common/switches.lib")
+            ],
+            library.includes(&opamp.file)
+        );
+        assert_eq!(
+            Path::new("tests/spice/library/common/diodes.lib"),
+            library.get("DMOD").unwrap().file
+        );
+
+        let fast = library.get("QFAST").unwrap();
+        assert_eq!(Path::new("tests/spice/library/corners.lib"), fast.file);
+        assert_eq!(Some(String::from("fast")), fast.section);
+    }
+
+    #[test]
+    fn duplicates() {
+        let library = library();
+        assert_eq!(
+            Path::new("tests/spice/models/models.lib"),
+            library.get("SMOD").unwrap().file
+        );
+        assert_eq!(
+            vec![
+                String::from("duplicate definition of SMOD in tests/spice/library/common/switches.lib, using tests/spice/models/models.lib"),
+            ],
+            library.warnings()
+        );
+    }
+
+    #[test]
+    fn cache() {
+        let cache = Path::new("target/model_library.cache");
+        let _ = std::fs::remove_file(cache);
+        let pathlist = [PathBuf::from("tests/spice/models"), PathBuf::from("tests/spice/library")];
+        let library = ModelLibrary::cached(&pathlist, cache).unwrap();
+        assert!(cache.exists());
+        assert_eq!(library, ModelLibrary::load(cache).unwrap());
+        assert_eq!(library, ModelLibrary::cached(&pathlist, cache).unwrap());
+    }
+}
@@ -0,0 +1,317 @@
+//! Monte-Carlo and worst-case variants of a [`Circuit`].
+//!
+//! The tolerances are taken from the `Tolerance` property of the schematic symbols,
+//! see [`crate::netlist::Netlist::tolerances`]. [`MonteCarlo::variants`] creates circuits
+//! with random values, [`MonteCarlo::corners`] creates a circuit for every combination
+//! of the minimum and maximum values.
+//!
+//! The random numbers are created from the seed, the same seed creates the same variants.
+//!
+//! ```
+//! use std::path::Path;
+//! use recad_core::{
+//!     montecarlo::{Distribution, MonteCarlo},
+//!     netlist::Netlist,
+//!     Circuit, Schema,
+//! };
+//!
+//! let schema = Schema::load(Path::new("tests/spice/spice.kicad_sch")).unwrap();
+//! let netlist = Netlist::from(&schema).unwrap();
+//! let mut circuit = Circuit::new(String::from("spice"), vec![]);
+//! netlist.circuit(&mut circuit).unwrap();
+//!
+//! let montecarlo = MonteCarlo::new(netlist.tolerances().unwrap())
+//!     .distribution(Distribution::Uniform)
+//!     .seed(42);
+//! let variants = montecarlo.variants(&circuit, 100).unwrap();
+//! assert_eq!(100, variants.len());
+//! assert_eq!(4, montecarlo.corners(&circuit).unwrap().len());
+//! ```
+use crate::{rawfile::RawFile, simulator::Simulator, value::Value, Circuit, Error};
+
+/// The maximum number of tolerances for the worst-case corners.
+const MAX_CORNERS: usize = 16;
+
+/// The relative tolerance of a circuit item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tolerance {
+    pub reference: String,
+    /// The relative tolerance, `0.05` for 5%.
+    pub tolerance: f64,
+}
+
+impl Tolerance {
+    /// Parse the tolerance from a percent value, e.g. `5%` or `±0.1 %`.
+    pub fn parse(reference: &str, value: &str) -> Result<Self, Error> {
+        let percent = value
+            .trim()
+            .trim_start_matches('±')
+            .strip_suffix('%')
+            .and_then(|v| v.trim().parse::<f64>().ok());
+        match percent {
+            Some(percent) if percent >= 0.0 => Ok(Self {
+                reference: reference.to_string(),
+                tolerance: percent / 100.0,
+            }),
+            _ => Err(Error(
+                String::from("spice"),
+                format!("invalid tolerance '{}' for {}", value, reference),
+            )),
+        }
+    }
+}
+
+/// The distribution of the random values.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Distribution {
+    /// Normal distribution, the tolerance is three standard deviations.
+    #[default]
+    Gaussian,
+    /// The values are equally distributed between the minimum and maximum value.
+    Uniform,
+}
+
+/// Random number generator (SplitMix64), the generated numbers only depend on the seed.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform random number in the range `[0, 1)`.
+    fn uniform(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal random number with the Box-Muller transform.
+    fn gaussian(&mut self) -> f64 {
+        let u1 = 1.0 - self.uniform();
+        let u2 = self.uniform();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+/// Create variants of a circuit from the tolerances.
+#[derive(Debug, Clone)]
+pub struct MonteCarlo {
+    tolerances: Vec<Tolerance>,
+    distribution: Distribution,
+    seed: u64,
+}
+
+impl MonteCarlo {
+    pub fn new(tolerances: Vec<Tolerance>) -> Self {
+        Self {
+            tolerances,
+            distribution: Distribution::default(),
+            seed: 0,
+        }
+    }
+
+    /// Set the distribution of the random values, the default is [`Distribution::Gaussian`].
+    pub fn distribution(mut self, distribution: Distribution) -> Self {
+        self.distribution = distribution;
+        self
+    }
+
+    /// Set the seed of the random numbers, the default is 0.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Create `runs` circuits with random values.
+    pub fn variants(&self, circuit: &Circuit, runs: usize) -> Result<Vec<Circuit>, Error> {
+        let nominals = self.nominals(circuit)?;
+        let mut random = Random(self.seed);
+        let mut result = Vec::with_capacity(runs);
+        for _ in 0..runs {
+            let mut variant = circuit.clone();
+            for (tolerance, nominal) in self.tolerances.iter().zip(&nominals) {
+                let deviation = match self.distribution {
+                    Distribution::Gaussian => random.gaussian() / 3.0,
+                    Distribution::Uniform => 2.0 * random.uniform() - 1.0,
+                };
+                let value = nominal * (1.0 + tolerance.tolerance * deviation);
+                variant.set_number(&tolerance.reference, Value::new(value))?;
+            }
+            result.push(variant);
+        }
+        Ok(result)
+    }
+
+    /// Create the worst-case corners, a circuit for every combination of the minimum
+    /// and maximum values. The first corner uses the minimum values.
+    pub fn corners(&self, circuit: &Circuit) -> Result<Vec<Circuit>, Error> {
+        if self.tolerances.len() > MAX_CORNERS {
+            return Err(Error(
+                String::from("spice"),
+                format!(
+                    "too many tolerances for the corners: {}, maximum is {}",
+                    self.tolerances.len(),
+                    MAX_CORNERS
+                ),
+            ));
+        }
+        let nominals = self.nominals(circuit)?;
+        let mut result = Vec::new();
+        for corner in 0..1usize << self.tolerances.len() {
+            let mut variant = circuit.clone();
+            for (index, (tolerance, nominal)) in self.tolerances.iter().zip(&nominals).enumerate() {
+                let sign = if corner & (1 << index) == 0 { -1.0 } else { 1.0 };
+                let value = nominal * (1.0 + sign * tolerance.tolerance);
+                variant.set_number(&tolerance.reference, Value::new(value))?;
+            }
+            result.push(variant);
+        }
+        Ok(result)
+    }
+
+    /// Simulate `runs` random variants of the circuit.
+    pub fn run(
+        &self,
+        circuit: &Circuit,
+        runs: usize,
+        simulator: &impl Simulator,
+    ) -> Result<Vec<RawFile>, Error> {
+        self.variants(circuit, runs)?
+            .iter()
+            .map(|variant| simulator.run(variant))
+            .collect()
+    }
+
+    /// Get the nominal values of the circuit items.
+    fn nominals(&self, circuit: &Circuit) -> Result<Vec<f64>, Error> {
+        self.tolerances
+            .iter()
+            .map(|tolerance| {
+                let Some(value) = circuit.value(&tolerance.reference) else {
+                    return Err(Error(
+                        String::from("spice"),
+                        format!("spice model not found {}", tolerance.reference),
+                    ));
+                };
+                Value::parse_spice(value).map(|v| v.value).map_err(|_| {
+                    Error(
+                        String::from("spice"),
+                        format!("value '{}' of {} is not a number", value, tolerance.reference),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Distribution, MonteCarlo, Tolerance};
+    use crate::{
+        netlist::Netlist, rawfile::RawFile, simulator::MockSimulator, value::Value, Circuit,
+        Schema,
+    };
+
+    fn circuit() -> Circuit {
+        let s = |s: &str| s.to_string();
+        let mut circuit = Circuit::new(s("divider"), vec![]);
+        circuit.resistor(s("R1"), s("in"), s("out"), s("10k"));
+        circuit.resistor(s("R2"), s("out"), s("0"), s("4k7"));
+        circuit
+    }
+
+    fn tolerances() -> Vec<Tolerance> {
+        vec![
+            Tolerance::parse("R1", "5%").unwrap(),
+            Tolerance::parse("R2", "±1 %").unwrap(),
+        ]
+    }
+
+    fn value(circuit: &Circuit, reference: &str) -> f64 {
+        Value::parse_spice(circuit.value(reference).unwrap()).unwrap().value
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(0.05, Tolerance::parse("R1", "5%").unwrap().tolerance);
+        assert!(Tolerance::parse("R1", "5").is_err());
+        assert!(Tolerance::parse("R1", "-5%").is_err());
+    }
+
+    #[test]
+    fn variants() {
+        for distribution in [Distribution::Gaussian, Distribution::Uniform] {
+            let montecarlo = MonteCarlo::new(tolerances()).distribution(distribution).seed(7);
+            let variants = montecarlo.variants(&circuit(), 500).unwrap();
+            assert_eq!(500, variants.len());
+            //the same seed creates the same variants
+            assert_eq!(variants, montecarlo.variants(&circuit(), 500).unwrap());
+            let values = variants.iter().map(|c| value(c, "R1")).collect::<Vec<f64>>();
+            let mean = values.iter().sum::<f64>() / values.len() as f64;
+            assert!((mean - 10_000.0).abs() < 50.0, "mean {}", mean);
+            if distribution == Distribution::Uniform {
+                assert!(values.iter().all(|v| (9500.0..=10500.0).contains(v)));
+            }
+            assert!(values.iter().any(|v| (*v - 10_000.0).abs() > 200.0));
+        }
+        assert_ne!(
+            MonteCarlo::new(tolerances()).seed(1).variants(&circuit(), 1).unwrap(),
+            MonteCarlo::new(tolerances()).seed(2).variants(&circuit(), 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn corners() {
+        let corners = MonteCarlo::new(tolerances()).corners(&circuit()).unwrap();
+        let values = corners
+            .iter()
+            .map(|c| (value(c, "R1"), value(c, "R2")))
+            .collect::<Vec<(f64, f64)>>();
+        assert_eq!(
+            vec![(9500.0, 4653.0), (10500.0, 4653.0), (9500.0, 4747.0), (10500.0, 4747.0)],
+            values
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let missing = MonteCarlo::new(vec![Tolerance::parse("R9", "5%").unwrap()]);
+        assert_eq!("spice model not found R9", missing.variants(&circuit(), 1).unwrap_err().1);
+        let mut source = circuit();
+        source.voltage(String::from("V1"), String::from("in"), String::from("0"), String::from("SIN(0 1 1k)"));
+        let invalid = MonteCarlo::new(vec![Tolerance::parse("V1", "5%").unwrap()]);
+        assert!(invalid.corners(&source).is_err());
+    }
+
+    #[test]
+    fn schema() {
+        let schema = Schema::load(Path::new("tests/spice/spice.kicad_sch")).unwrap();
+        let netlist = Netlist::from(&schema).unwrap();
+        assert_eq!(
+            vec![
+                Tolerance::parse("R1", "5%").unwrap(),
+                Tolerance::parse("C1", "10%").unwrap()
+            ],
+            netlist.tolerances().unwrap()
+        );
+
+        let mut circuit = Circuit::new(String::from("spice"), vec![]);
+        netlist.circuit(&mut circuit).unwrap();
+        let variants = MonteCarlo::new(netlist.tolerances().unwrap()).variants(&circuit, 2).unwrap();
+        assert_ne!(circuit.value("C1"), variants[0].value("C1"));
+    }
+
+    #[test]
+    fn run() {
+        let raw = RawFile::load(Path::new("tests/spice/rc.raw")).unwrap();
+        let simulator = MockSimulator::new(raw);
+        let results = MonteCarlo::new(tolerances()).run(&circuit(), 3, &simulator).unwrap();
+        assert_eq!(3, results.len());
+        assert_eq!(3, simulator.netlists().len());
+    }
+}
@@ -2,38 +2,469 @@
 //!
 //!  **Strategy for Extracting Netlist from Kicad Schematic File:**
 //!
-//! 1. Collect all wire endpoints (nodes) in the schematic file.
-//! 2. Identify and group together connections that share the same coordinates (junctions).
-//! 3. Iterate through each of the identified junctions.
-//! 4. For each junction, find the associated wire(s) at that point.
-//! 5. Traverse all wires connected to the current wire at the junction.
-//! 6. For each traversed wire endpoint, identify and group together connections with the same coordinates (junctions).
-//! 7. Assign net names to the identified groups of connections based on their connectivity; connections consisting of a single element are named NC (No Connection).
+//! 1. Collect the positions of the pins, labels, junctions and wire ends in the schematic file.
+//! 2. Group together the items that share the same coordinates.
+//! 3. Hash the wire segments by the grid cells they cross, this finds the wires at a
+//!    position without testing every wire.
+//! 4. Join every position that lies on a wire with the start of the wire in a union-find.
+//!    A wire end, junction, pin or label in the middle of a wire is connected to the wire.
+//!    Crossing wires are only connected with a junction.
+//! 5. The joined positions form the groups of connected nodes.
+//! 6. Join the groups with the same label names to nets, again with a union-find.
+//! 7. Assign net names to the identified groups of connections based on their labels, nets without labels
+//!    are named after a pin like KiCad does: `Net-(R1-Pad1)`, or `unconnected-(R1-Pad1)` for a single pin.
+//!
+//! **Hierarchical projects:**
+//!
+//! For a [`Project`] the steps above are done for every sheet. The groups are then
+//! joined across the sheet boundaries: the group with a [`HierarchicalPin`] of a sheet
+//! in the parent schema is connected to the group with the [`HierarchicalLabel`] of
+//! the same name in the child schema. Local and hierarchical label names are prefixed
+//! with the sheet path like KiCad does, e.g. `/VIN` on the root sheet or `/amp/B`.
+//! Global labels and power symbols are never prefixed.
+//!
+//! **Buses:**
+//!
+//! The bus segments are collected separately from the wires. The labels on a bus define
+//! the bus members, either as vector bus `D[0..7]` or as group bus `{SDA SCL}`. A net is a
+//! member of a bus when its label matches one of the member names. When the bus has a global
+//! label or is connected to a sheet pin, the members are connected to the members of the
+//! other buses in that scope. [`BusEntry`] items connect the wires to the bus.
+//!
+//! **Queries:**
+//!
+//! [`Netlist::nets`] returns every [`Net`] with the connected pins, labels and positions.
+//! [`Netlist::net`] and [`Netlist::pin_net`] look up a single net by name or by pin.
+//! [`Netlist::lint`] finds connectivity problems like dangling wires and unconnected pins.
+//!
+//! **Performance:**
+//!
+//! The nets are collected once when the netlist is created, the queries are index
+//! lookups. The connection keys are compared as enums and the points and grid cells
+//! use a fast integer hash. See `benches/netlist.rs` for the scaled up schemas.
+//!
+//! **Net classes:**
+//!
+//! The net class of a net is taken from the `Netclass` property of a [`NetclassFlag`]
+//! on the net. A flag on the border of a [`RuleArea`] applies to all nets with an item
+//! or a wire inside the area. Nets without a flag use the assignments and patterns of the
+//! [`NetSettings`] from the project file, all other nets are in the `Default` class.
+//! Nets with flags of different classes are reported by [`Netlist::netclass_conflicts`].
 
-use indexmap::IndexMap;
+use std::{collections::HashMap, io::Write, path::PathBuf};
+
+use indexmap::{IndexMap, IndexSet};
 
 use crate::{
-    gr::Pt, schema::{GlobalLabel, LocalLabel, SchemaItem, Symbol}, sexp::constants::el, symbols::Pin, Circuit, Error, Schema
+    gr::Pt,
+    project::{NetSettings, Sheet, DEFAULT_NETCLASS},
+    math::{inside_polygon, on_segment, segment_in_polygon},
+    montecarlo::Tolerance,
+    schema::{
+        BusEntry, GlobalLabel, HierarchicalLabel, HierarchicalPin, HierarchicalSheet,
+        LocalLabel, NetclassFlag, RuleArea, SchemaItem, Symbol,
+    },
+    sexp::constants::el,
+    symbols::{ElectricalTypes, Pin},
+    value::Value,
+    Circuit, Error, Project, Schema,
 };
 
+mod export;
+mod graph;
+mod lint;
+
+use graph::{FastHash, SpatialHash, UnionFind};
+
 #[derive(Clone, Debug, PartialEq)]
 enum NodePositions<'a> {
     Pin(Pt, &'a Pin, &'a Symbol),
     Label(Pt, &'a LocalLabel),
     GlobalLabel(Pt, &'a GlobalLabel),
+    HierarchicalLabel(Pt, &'a HierarchicalLabel),
+    SheetPin(Pt, &'a HierarchicalPin, &'a HierarchicalSheet),
+    BusEntry(Pt, &'a BusEntry),
+    NetclassFlag(Pt, &'a NetclassFlag),
     NoConnect(Pt),
     Junction(Pt),
 }
 
+impl NodePositions<'_> {
+    fn pos(&self) -> Pt {
+        match self {
+            NodePositions::Pin(pt, ..)
+            | NodePositions::Label(pt, _)
+            | NodePositions::GlobalLabel(pt, _)
+            | NodePositions::HierarchicalLabel(pt, _)
+            | NodePositions::SheetPin(pt, ..)
+            | NodePositions::BusEntry(pt, _)
+            | NodePositions::NetclassFlag(pt, _)
+            | NodePositions::NoConnect(pt)
+            | NodePositions::Junction(pt) => *pt,
+        }
+    }
+}
+
+/// The kind of item which names a net, ordered by the priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Driver {
+    Pin,
+    Local,
+    Hierarchical,
+    Power,
+    Global,
+}
+
+/// A connection key of a group, groups with the same key
+/// are connected, see [`Netlist::keys`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Key<'b> {
+    /// Global labels, power symbols and global bus members.
+    Global(&'b str),
+    /// A local label of the sheet.
+    Local(usize, &'b str),
+    /// A hierarchical label of the sheet or the sheet pin in the parent.
+    Hierarchical(usize, &'b str),
+    /// A hierarchical bus member of the sheet by the label and the member index.
+    Bus(usize, &'b str, usize),
+}
+
+/// A name candidate of a group of connected nodes.
+#[derive(Clone, Debug)]
+struct Candidate {
+    driver: Driver,
+    /// The label text or the pin name `R1-Pad1`.
+    text: String,
+}
+
+/// A group of connected items with more than one label name, see [`Netlist::conflicts`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NameConflict {
+    /// The name of the net.
+    pub net: String,
+    /// The human readable path of the sheet.
+    pub sheet: String,
+    /// The position of the group in the sheet.
+    pub pos: Pt,
+    /// The conflicting names, the name with the highest priority first.
+    pub names: Vec<String>,
+}
+
+/// A net with netclass flags of different classes, see [`Netlist::netclass_conflicts`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetclassConflict {
+    /// The name of the net.
+    pub net: String,
+    /// The human readable path of the sheet with the first flag.
+    pub sheet: String,
+    /// The position of the first flag in the sheet.
+    pub pos: Pt,
+    /// The conflicting classes, the class which is used for the net first.
+    pub classes: Vec<String>,
+}
+
+/// A rule area with the net class of the flag on the border.
+type Area<'a> = (&'a RuleArea, Option<&'a str>);
+
+/// A map by position with the fast hash of the netlister.
+type PtMap<V> = IndexMap<Pt, V, FastHash>;
+
+/// The nodes by their position, after [`Netlist::connect`] by the position of the group.
+type Groups<'a> = PtMap<Vec<NodePositions<'a>>>;
+
+/// The scope of a bus label.
+enum BusScope<'a> {
+    Local,
+    Global,
+    Hierarchical(&'a HierarchicalLabel),
+    SheetPin(&'a HierarchicalPin, &'a HierarchicalSheet),
+}
+
+/// A label on a bus with the names of the bus members.
+struct BusLabel<'a> {
+    members: Vec<String>,
+    scope: BusScope<'a>,
+}
+
+/// The connected segments of a bus.
+#[derive(Default)]
+struct BusNet<'a> {
+    segments: Vec<(Pt, Pt)>,
+    labels: Vec<BusLabel<'a>>,
+}
+
+impl BusNet<'_> {
+    fn contains(&self, pt: Pt) -> bool {
+        self.segments.iter().any(|(start, end)| on_segment(pt, *start, *end))
+    }
+}
+
+/// Test if the point is on the outline of a rule area.
+fn on_border(pt: Pt, area: &RuleArea) -> bool {
+    let pts = &area.polyline.pts.0;
+    pts.iter()
+        .zip(pts.iter().cycle().skip(1))
+        .any(|(start, end)| on_segment(pt, *start, *end))
+}
+
+/// Get the global net name of a power symbol, `PWR_FLAG` does not define a net.
+fn power_net(symbol: &Symbol) -> Option<String> {
+    if symbol.lib_id.starts_with("power:") && symbol.lib_id != "power:PWR_FLAG" {
+        Some(symbol.property(el::PROPERTY_VALUE))
+    } else {
+        None
+    }
+}
+
+/// Split a bus name into the names of its members.
+///
+/// Vector buses like `D[0..7]` and group buses like `{SDA SCL}` or `I2C{SDA SCL}`
+/// are supported, the members of a named group are prefixed with the group
+/// name: `I2C.SDA`. Returns `None` when the name is not a bus name.
+pub fn bus_members(name: &str) -> Option<Vec<String>> {
+    let name = name.trim();
+    let group = name.char_indices().find(|(i, c)| {
+        *c == '{' && (*i == 0 || !matches!(name.as_bytes()[i - 1], b'~' | b'^' | b'_'))
+    });
+    if let Some((open, _)) = group {
+        if !name.ends_with('}') {
+            return None;
+        }
+        let prefix = &name[..open];
+        let mut members = Vec::new();
+        for member in name[open + 1..name.len() - 1]
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|m| !m.is_empty())
+        {
+            for member in vector_members(member).unwrap_or(vec![member.to_string()]) {
+                if prefix.is_empty() {
+                    members.push(member);
+                } else {
+                    members.push(format!("{}.{}", prefix, member));
+                }
+            }
+        }
+        if members.is_empty() {
+            None
+        } else {
+            Some(members)
+        }
+    } else {
+        vector_members(name)
+    }
+}
+
+fn vector_members(name: &str) -> Option<Vec<String>> {
+    if !name.ends_with(']') {
+        return None;
+    }
+    let open = name.rfind('[')?;
+    let prefix = &name[..open];
+    let (start, end) = name[open + 1..name.len() - 1].split_once("..")?;
+    let start: i32 = start.trim().parse().ok()?;
+    let end: i32 = end.trim().parse().ok()?;
+    let range: Vec<i32> = if start <= end {
+        (start..=end).collect()
+    } else {
+        (end..=start).rev().collect()
+    };
+    Some(range.iter().map(|i| format!("{}{}", prefix, i)).collect())
+}
+
+/// The connected nodes of a single schema sheet.
+struct SheetNodes<'a> {
+    /// The human readable sheet path, `/` for the root sheet.
+    path: String,
+    /// The uuid path of the sheet instance, `/<root uuid>/<sheet uuid>`,
+    /// see [`Symbol::reference`].
+    instance: String,
+    /// The file the sheet was loaded from, unknown for a single schema.
+    filename: Option<PathBuf>,
+    /// The uuid of the sheet item in the parent schema.
+    uuid: Option<String>,
+    /// The index of the parent sheet.
+    parent: Option<usize>,
+    schema: &'a Schema,
+    buses: Vec<BusNet<'a>>,
+    node_positions: Groups<'a>,
+    /// The net class of the rule areas for the groups inside an area,
+    /// see [`Netlist::connect`].
+    areas: PtMap<&'a str>,
+    /// The number of wires at the node positions and wire ends,
+    /// positions without a wire are missing.
+    wires: PtMap<usize>,
+    /// The net index of the node positions and the groups, see [`Netlist::net_name`].
+    nets: PtMap<usize>,
+}
+
+impl<'a> SheetNodes<'a> {
+    fn new(
+        schema: &'a Schema,
+        path: &str,
+        instance: String,
+        filename: Option<PathBuf>,
+        uuid: Option<String>,
+        parent: Option<usize>,
+    ) -> Self {
+        let buses = Netlist::buses(schema);
+        let rule_areas = Netlist::rule_areas(schema);
+        let (node_positions, areas, wires) = Netlist::connect(schema, &buses, &rule_areas);
+        Self {
+            path: path.to_string(),
+            instance,
+            filename,
+            uuid,
+            parent,
+            schema,
+            buses,
+            node_positions,
+            areas,
+            wires,
+            nets: IndexMap::default(),
+        }
+    }
+
+    fn depth(&self) -> usize {
+        self.path.matches('/').count()
+    }
+
+    /// The reference of a symbol in this sheet instance.
+    fn reference<'b>(&self, symbol: &'b Symbol) -> &'b str {
+        symbol.reference(&self.instance)
+    }
+}
+
+/// A symbol pin which is connected to a net.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetPin {
+    pub reference: String,
+    /// The pin number.
+    pub pin: String,
+    /// The pin name, `~` or empty for unnamed pins.
+    pub name: String,
+    pub electrical_type: ElectricalTypes,
+    /// The symbol unit of the pin.
+    pub unit: u8,
+    /// The human readable path of the sheet.
+    pub sheet: String,
+    /// The position of the pin in the sheet.
+    pub pos: Pt,
+}
+
+/// The type of a [`NetLabel`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelKind {
+    Local,
+    Global,
+    Hierarchical,
+    /// The pin of a hierarchical sheet.
+    SheetPin,
+    /// A power symbol like `GND` or `+5V`.
+    Power,
+}
+
+/// A label which names a net.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetLabel {
+    pub text: String,
+    pub kind: LabelKind,
+    /// The human readable path of the sheet.
+    pub sheet: String,
+    pub pos: Pt,
+}
+
+/// A net with the connected pins and labels, see [`Netlist::nets`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Net {
+    pub name: String,
+    /// The symbol pins, power symbols are not included.
+    pub pins: Vec<NetPin>,
+    /// The pins of the power symbols and power flags.
+    pub power_pins: Vec<NetPin>,
+    pub labels: Vec<NetLabel>,
+    /// The positions of all connected items with the human readable sheet path.
+    pub positions: Vec<(String, Pt)>,
+    /// The name of the net class.
+    pub netclass: String,
+}
+
+/// The connectivity problems found by [`Netlist::lint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// A wire end which touches nothing.
+    DanglingWire,
+    /// A pin without connection and without a no connect flag.
+    UnconnectedPin,
+    /// A no connect flag on a connected pin.
+    ConnectedNoConnect,
+    /// A label which touches no wire or pin.
+    DanglingLabel,
+    /// A net with labels but only a single pin.
+    SingleNodeNet,
+}
+
+impl Lint {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DanglingWire => "dangling_wire",
+            Self::UnconnectedPin => "unconnected_pin",
+            Self::ConnectedNoConnect => "connected_no_connect",
+            Self::DanglingLabel => "dangling_label",
+            Self::SingleNodeNet => "single_node_net",
+        }
+    }
+}
+
+/// A connectivity problem with the location in the schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub lint: Lint,
+    pub message: String,
+    /// The human readable path of the sheet.
+    pub sheet: String,
+    pub pos: Pt,
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} ({} {})", self.lint.name(), self.message, self.sheet, self.pos)
+    }
+}
+
+/// The file formats of [`Netlist::export`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetlistFormat {
+    /// The KiCad XML netlist, `kicadxml` in `kicad-cli`.
+    KicadXml,
+    /// The KiCad s-expression netlist, `kicadsexpr` in `kicad-cli`. This is
+    /// the `.net` file which is imported by Pcbnew.
+    KicadSexpr,
+}
+
 /// create a netlist from a schematic file.
 pub struct Netlist<'a> {
-    node_positions: IndexMap<Pt, Vec<NodePositions<'a>>>,
-    names: IndexMap<Pt, String>,
+    sheets: Vec<SheetNodes<'a>>,
+    /// The groups of every net as sheet index and group position.
+    nets: Vec<Vec<(usize, Pt)>>,
+    /// The names of the nets in the order of the groups.
+    names: Vec<String>,
+    /// The nets with the connected items in the order of the groups.
+    items: Vec<Net>,
+    /// The index of the net by name, the first net of a name is used.
+    net_index: IndexMap<String, usize>,
+    /// The index of the net by symbol reference and pin number.
+    pin_index: IndexMap<(String, String), usize>,
+    net_settings: NetSettings,
 }
 
 impl<'a> Netlist<'a> {
-    fn collect_points(schema: &'a Schema) -> IndexMap<Pt, Vec<NodePositions<'a>>> {
-        let mut positions: IndexMap<Pt, Vec<NodePositions<'a>>> = IndexMap::new();
+    fn collect_points(
+        schema: &'a Schema,
+        buses: &[BusNet],
+        areas: &[Area],
+    ) -> Groups<'a> {
+        let mut positions: Groups<'a> = IndexMap::default();
+        let on_bus = |pt: Pt| buses.iter().any(|b| b.contains(pt));
 
         for item in &schema.items {
             match item {
@@ -45,8 +476,9 @@ impl<'a> Netlist<'a> {
                         .library_symbol(&symbol.lib_id)
                         .into_iter()
                         .for_each(|l| {
-                            for p in l.pins(symbol.unit) {
-                                let pin_pos = crate::math::pin_position(symbol, p);
+                            let pins = l.pins(symbol.unit);
+                            let pin_positions = crate::math::pin_positions(symbol, &pins);
+                            for (p, pin_pos) in pins.into_iter().zip(pin_positions) {
                                 positions
                                     .entry(pin_pos)
                                     .or_default()
@@ -67,328 +499,1336 @@ impl<'a> Netlist<'a> {
                         .push(NodePositions::Junction(junction.pos.into()));
                 }
                 SchemaItem::LocalLabel(label) => {
+                    if on_bus(label.pos.into()) {
+                        continue;
+                    }
                     positions
                         .entry(label.pos.into())
                         .or_default()
                         .push(NodePositions::Label(label.pos.into(), label));
                 }
                 SchemaItem::GlobalLabel(label) => {
+                    if on_bus(label.pos.into()) {
+                        continue;
+                    }
                     positions
                         .entry(label.pos.into())
                         .or_default()
                         .push(NodePositions::GlobalLabel(label.pos.into(), label));
                 }
-                SchemaItem::Bus(_) => {} //TODO
-                SchemaItem::BusEntry(_) => {}
-                SchemaItem::HierarchicalSheet(_) => {}
-                SchemaItem::HierarchicalLabel(_) => {}
-                SchemaItem::NetclassFlag(_) => {}
+                SchemaItem::HierarchicalLabel(label) => {
+                    if on_bus(label.pos.into()) {
+                        continue;
+                    }
+                    positions
+                        .entry(label.pos.into())
+                        .or_default()
+                        .push(NodePositions::HierarchicalLabel(label.pos.into(), label));
+                }
+                SchemaItem::HierarchicalSheet(sheet) => {
+                    for pin in &sheet.pins {
+                        if on_bus(pin.pos.into()) {
+                            continue;
+                        }
+                        positions
+                            .entry(pin.pos.into())
+                            .or_default()
+                            .push(NodePositions::SheetPin(pin.pos.into(), pin, sheet));
+                    }
+                }
+                SchemaItem::BusEntry(entry) => {
+                    let start: Pt = entry.pos.into();
+                    let end = Pt {
+                        x: entry.pos.x + entry.size.0,
+                        y: entry.pos.y + entry.size.1,
+                    };
+                    let pt = if on_bus(start) {
+                        end
+                    } else if on_bus(end) {
+                        start
+                    } else {
+                        continue;
+                    };
+                    positions
+                        .entry(pt)
+                        .or_default()
+                        .push(NodePositions::BusEntry(pt, entry));
+                }
+                SchemaItem::Bus(_) => {}
+                SchemaItem::NetclassFlag(flag) => {
+                    //flags on a rule area apply to the area
+                    let pt: Pt = flag.pos.into();
+                    if areas.iter().any(|(area, _)| on_border(pt, area)) {
+                        continue;
+                    }
+                    positions
+                        .entry(pt)
+                        .or_default()
+                        .push(NodePositions::NetclassFlag(pt, flag));
+                }
                 _ => {}
             }
         }
         positions
     }
 
-    /** This function takes a reference to a [`Schema`] and returns a `IndexMap<Pt, Pt>`.
-    It iterates through the items in the schema, filtering only `Wire` items. For
-    each `Wire`, it creates an entry in the map with the starting point as key
-    and the ending point as value, and also creates a reciprocal entry
-    to ensure bidirectionality. */
-    fn wires(schema: &Schema) -> IndexMap<Pt, Vec<Pt>> {
-        let mut wires: IndexMap<Pt, Vec<Pt>> = IndexMap::new();
+    /// Collect the bus segments of the schema, join the connected segments
+    /// and assign the bus labels.
+    fn buses(schema: &'a Schema) -> Vec<BusNet<'a>> {
+        let segments = schema
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                SchemaItem::Bus(bus) => Some((bus.pts.0[0], bus.pts.0[bus.pts.0.len() - 1])),
+                _ => None,
+            })
+            .collect::<Vec<(Pt, Pt)>>();
+        let touches = |a: &(Pt, Pt), b: &(Pt, Pt)| {
+            on_segment(a.0, b.0, b.1)
+                || on_segment(a.1, b.0, b.1)
+                || on_segment(b.0, a.0, a.1)
+                || on_segment(b.1, a.0, a.1)
+        };
+
+        let mut buses: Vec<BusNet<'a>> = Vec::new();
+        let mut visited = vec![false; segments.len()];
+        for start in 0..segments.len() {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut bus = BusNet {
+                segments: vec![segments[start]],
+                labels: Vec::new(),
+            };
+            let mut index = 0;
+            while index < bus.segments.len() {
+                let segment = bus.segments[index];
+                for (other, seen) in visited.iter_mut().enumerate() {
+                    if !*seen && touches(&segment, &segments[other]) {
+                        *seen = true;
+                        bus.segments.push(segments[other]);
+                    }
+                }
+                index += 1;
+            }
+            buses.push(bus);
+        }
+
+        for item in &schema.items {
+            let (pt, name, scope) = match item {
+                SchemaItem::LocalLabel(l) => (l.pos.into(), &l.text, BusScope::Local),
+                SchemaItem::GlobalLabel(l) => (l.pos.into(), &l.text, BusScope::Global),
+                SchemaItem::HierarchicalLabel(l) => {
+                    (l.pos.into(), &l.text, BusScope::Hierarchical(l))
+                }
+                SchemaItem::HierarchicalSheet(sheet) => {
+                    for pin in &sheet.pins {
+                        if let Some(bus) = buses.iter_mut().find(|b| b.contains(pin.pos.into())) {
+                            bus.labels.push(BusLabel {
+                                members: bus_members(&pin.name).unwrap_or(vec![pin.name.clone()]),
+                                scope: BusScope::SheetPin(pin, sheet),
+                            });
+                        }
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            if let Some(bus) = buses.iter_mut().find(|b| b.contains(pt)) {
+                bus.labels.push(BusLabel {
+                    members: bus_members(name).unwrap_or(vec![name.clone()]),
+                    scope,
+                });
+            }
+        }
+        buses
+    }
+
+    /// Get the start and end points of the wires.
+    fn segments(schema: &Schema) -> Vec<(Pt, Pt)> {
         schema
             .items
             .iter()
             .filter_map(|w| match w {
-                SchemaItem::Wire(w) => Some(w),
+                SchemaItem::Wire(w) => Some((w.pts.0[0], w.pts.0[1])),
                 _ => None,
             })
-            .for_each(|w| {
-                let pt0 = w.pts.0[0];
-                let pt1 = w.pts.0[1];
-                wires.entry(pt0).or_default().push(pt1);
-                wires.entry(pt1).or_default().push(pt0);
-            });
-        wires
+            .collect()
     }
 
-    fn get_wire(pt: Pt, wires: &IndexMap<Pt, Vec<Pt>>, visited: &mut Vec<Pt>) -> Option<Vec<Pt>> {
-        visited.push(pt);
-        let wires = wires.get(&pt);
-        wires.map(|wires| wires
-                    .iter()
-                    .filter_map(|point| {
-                        if visited.contains(point) {
-                            None
-                        } else {
-                            Some(*point)
+    /// Collect the name candidates of a group, the pins are skipped when `pins` is false.
+    fn candidates(sheet: &SheetNodes, pt: &Pt, pins: bool) -> Vec<Candidate> {
+        let mut candidates = Vec::new();
+        for item in sheet.node_positions[pt].iter() {
+            let (driver, text) = match item {
+                NodePositions::Pin(_, pin, symbol) => {
+                    if let Some(power) = power_net(symbol) {
+                        (Driver::Power, power)
+                    } else if !pins {
+                        continue;
+                    } else {
+                        let reference = sheet.reference(symbol);
+                        if reference.starts_with('#') {
+                            continue;
                         }
-                    })
-                    .collect::<Vec<Pt>>())
-    }
-
-    fn seek_wire(pt: Pt, wires: &IndexMap<Pt, Vec<Pt>>, visited: &mut Vec<Pt>) -> Vec<Pt> {
-        let mut found = vec![];
-        if let Some(wire) = Netlist::get_wire(pt, wires, visited) {
-            for wire in wire {
-                found.push(wire);
-                found.append(&mut Netlist::seek_wire(wire, wires, visited));
-            }
-        }
-        found
-    }
-
-    fn generate_names(results: &IndexMap<Pt, Vec<NodePositions>>) -> IndexMap<Pt, String> {
-        let mut names = IndexMap::new();
-        for (key, items) in results.iter() {
-            let mut name = String::new();
-            let mut label = None;
-            let mut positions = vec![key];
-            let mut first = true;
-            for item in items.iter() {
-                match item {
-                    NodePositions::Pin(pos, pin, symbol) => {
-                        positions.push(pos);
-                        if symbol.lib_id.starts_with("power:") {
-                            label = Some(symbol.property(el::PROPERTY_VALUE));
-                        } else if first {
-                            first = false;
-                            name.push_str(&symbol.property(el::PROPERTY_REFERENCE));
-                            name.push('_');
-                            name.push_str(&pin.number.name);
+                        let name = if pin.name.name.is_empty() || pin.name.name == "~" {
+                            format!("Pad{}", pin.number.name)
                         } else {
-                            name.push_str("__");
-                            name.push_str(&symbol.property(el::PROPERTY_REFERENCE));
-                            name.push('_');
-                            name.push_str(&pin.number.name);
-                        }
-                    },
-                    NodePositions::Label(pos, l) => {
-                        positions.push(pos);
-                        label = Some(l.text.clone());
-                    },
-                    NodePositions::GlobalLabel(pos, l) => {
-                        positions.push(pos);
-                        label = Some(l.text.clone());
-                    },
-                    NodePositions::NoConnect(pos) => {
-                        positions.push(pos);
-                    },
-                    NodePositions::Junction(pos) => {
-                        positions.push(pos);
-                    },
-                }
-            }
-
-            if let Some(label) = label {
-                for pos in positions {
-                    names.insert(*pos, label.clone());
+                            pin.name.name.clone()
+                        };
+                        (Driver::Pin, format!("{}-{}", reference, name))
+                    }
                 }
-            } else {
-                for pos in positions {
-                    names.insert(*pos, name.clone());
+                NodePositions::Label(_, l) => (Driver::Local, l.text.clone()),
+                NodePositions::GlobalLabel(_, l) => (Driver::Global, l.text.clone()),
+                NodePositions::HierarchicalLabel(_, l) => (Driver::Hierarchical, l.text.clone()),
+                _ => continue,
+            };
+            candidates.push(Candidate { driver, text });
+        }
+        candidates
+    }
+
+    /// The net name of a candidate on a sheet.
+    fn candidate_name(&self, sheet: usize, candidate: &Candidate) -> String {
+        match candidate.driver {
+            Driver::Pin => format!("Net-({})", candidate.text),
+            Driver::Local | Driver::Hierarchical => {
+                format!("{}{}", self.prefix(sheet), candidate.text)
+            }
+            Driver::Power | Driver::Global => candidate.text.clone(),
+        }
+    }
+
+    /// Select the name for the groups of a net, the groups must be
+    /// ordered by the sheet depth.
+    ///
+    /// The names are selected like KiCad does: the candidate with the highest
+    /// priority wins, global labels over power symbols over hierarchical labels
+    /// over local labels over pins. With the same priority the name from the upper
+    /// sheet and then the first name in alphabetical order is used. Nets without
+    /// labels are named after a pin, `Net-(R1-Pad1)`, a single pin
+    /// is named `unconnected-(R1-Pad1)`.
+    fn select_name(&self, groups: &[(usize, Pt)]) -> String {
+        //the pins are only needed when the net has no labels
+        let collect = |pins: bool| {
+            groups
+                .iter()
+                .flat_map(|(sheet, pt)| {
+                    Netlist::candidates(&self.sheets[*sheet], pt, pins)
+                        .into_iter()
+                        .map(|candidate| (*sheet, candidate))
+                })
+                .collect::<Vec<(usize, Candidate)>>()
+        };
+        let mut candidates = collect(false);
+        if candidates.is_empty() {
+            candidates = collect(true);
+        }
+
+        let pins = candidates.iter().filter(|(_, c)| c.driver == Driver::Pin).count();
+        //only the names of the candidates with the best priority are compared
+        let priority = |(sheet, candidate): &(usize, Candidate)| {
+            (candidate.driver, std::cmp::Reverse(self.sheets[*sheet].depth()))
+        };
+        let best = candidates.iter().map(priority).max();
+        candidates
+            .iter()
+            .filter(|candidate| Some(priority(candidate)) == best)
+            .map(|(sheet, candidate)| (candidate, self.candidate_name(*sheet, candidate)))
+            .min_by(|(_, a), (_, b)| a.cmp(b))
+            .map(|(candidate, name)| {
+                if pins == 1 && candidates.len() == 1 {
+                    format!("unconnected-({})", candidate.text)
+                } else {
+                    name
+                }
+            })
+            .unwrap_or_default()
+    }
+
+    /// Get the groups which are named by more than one label, the name
+    /// with the highest priority is used for the net.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use recad_core::{netlist::Netlist, Project};
+    ///
+    /// let project = Project::load(Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+    /// let netlist = Netlist::from_project(&project).unwrap();
+    /// for conflict in netlist.conflicts() {
+    ///     println!("{}: {}", conflict.net, conflict.names.join(", "));
+    /// }
+    /// ```
+    pub fn conflicts(&self) -> Vec<NameConflict> {
+        let mut conflicts = Vec::new();
+        for net in &self.nets {
+            for (sheet, pt) in net {
+                let mut candidates = Netlist::candidates(&self.sheets[*sheet], pt, false);
+                candidates.sort_by(|a, b| b.driver.cmp(&a.driver).then(a.text.cmp(&b.text)));
+                let mut names = candidates
+                    .iter()
+                    .map(|c| self.candidate_name(*sheet, c))
+                    .collect::<Vec<String>>();
+                names.dedup();
+                if names.len() > 1 {
+                    conflicts.push(NameConflict {
+                        net: self.net_name(&self.sheets[*sheet], pt).cloned().unwrap_or_default(),
+                        sheet: self.sheets[*sheet].path.clone(),
+                        pos: *pt,
+                        names,
+                    });
                 }
             }
         }
-        names
+        conflicts
     }
 
-    pub fn from(schema: &'a crate::Schema) -> Result<Self, Error> {
-        let wires = Netlist::wires(schema);
-        let positions = Netlist::collect_points(schema);
-
-        let mut final_positions = IndexMap::new();
-        let mut visited_positions = vec![];
-        let mut visited_wires = vec![];
-        for (pos, nodes) in positions.iter() {
-            let mut new_nodes = nodes.clone();
-            if !visited_positions.contains(pos) {
-                visited_positions.push(*pos);
-                for wire in Netlist::seek_wire(*pos, &wires, &mut visited_wires) {
-                    if let Some(other) = positions.get(&wire) {
-                        visited_positions.push(wire);
-                        for node in other {
-                            new_nodes.push(node.clone());
+    /// Get the nets with netclass flags of different classes, the class
+    /// of the first flag is used for the net.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use recad_core::{netlist::Netlist, Project};
+    ///
+    /// let project = Project::load(Path::new("tests/netclass/netclass.kicad_sch")).unwrap();
+    /// let netlist = Netlist::from_project(&project).unwrap();
+    /// for conflict in netlist.netclass_conflicts() {
+    ///     println!("{}: {}", conflict.net, conflict.classes.join(", "));
+    /// }
+    /// ```
+    pub fn netclass_conflicts(&self) -> Vec<NetclassConflict> {
+        let mut conflicts = Vec::new();
+        for (groups, net) in self.nets.iter().zip(&self.items) {
+            let mut first = None;
+            let mut classes: Vec<String> = Vec::new();
+            for (sheet, pt) in groups {
+                for node in &self.sheets[*sheet].node_positions[pt] {
+                    let NodePositions::NetclassFlag(pos, flag) = node else {
+                        continue;
+                    };
+                    let Some(netclass) = flag.netclass() else {
+                        continue;
+                    };
+                    first.get_or_insert((*sheet, *pos));
+                    if !classes.iter().any(|c| c == netclass) {
+                        classes.push(netclass.to_string());
+                    }
+                }
+            }
+            if let Some((sheet, pos)) = first.filter(|_| classes.len() > 1) {
+                conflicts.push(NetclassConflict {
+                    net: net.name.clone(),
+                    sheet: self.sheets[sheet].path.clone(),
+                    pos,
+                    classes,
+                });
+            }
+        }
+        conflicts
+    }
+
+    /// The net name prefix of a sheet, the human readable sheet path.
+    fn prefix(&self, sheet: usize) -> &str {
+        &self.sheets[sheet].path
+    }
+
+    /// Connect the nodes of a single schema.
+    ///
+    /// Every node and wire end which lies on a wire is joined with the start of
+    /// the wire, the joined nodes form a group. The group is stored with the
+    /// position of its first node.
+    ///
+    /// Returns the groups, the net class of the rule area of every group
+    /// which has a node or a wire inside a rule area and the number of
+    /// wires at every position which lies on a wire.
+    fn connect(
+        schema: &'a Schema,
+        buses: &[BusNet],
+        areas: &[Area<'a>],
+    ) -> (Groups<'a>, PtMap<&'a str>, PtMap<usize>) {
+        let positions = Netlist::collect_points(schema, buses, areas);
+        let hash = SpatialHash::new(Netlist::segments(schema));
+        let mut points = positions.keys().copied().collect::<IndexSet<Pt, FastHash>>();
+        for (start, end) in hash.segments() {
+            points.insert(*start);
+            points.insert(*end);
+        }
+
+        //the point index of every wire start, all wire ends are in the points
+        let starts = hash
+            .segments()
+            .iter()
+            .map(|(start, _)| points.get_index_of(start).unwrap_or_default())
+            .collect::<Vec<usize>>();
+        let mut sets = UnionFind::new(points.len());
+        let mut counts = IndexMap::default();
+        for (index, pt) in points.iter().enumerate() {
+            let mut count = 0;
+            for segment in hash.query(*pt) {
+                sets.union(index, starts[segment]);
+                count += 1;
+            }
+            if count > 0 {
+                counts.insert(*pt, count);
+            }
+        }
+
+        //the wires of every set for the rule areas
+        let mut wires: Vec<Vec<(Pt, Pt)>> = vec![Vec::new(); points.len()];
+        if areas.iter().any(|(_, netclass)| netclass.is_some()) {
+            for (start, end) in hash.segments() {
+                if let Some(index) = points.get_index_of(start) {
+                    wires[sets.find(index)].push((*start, *end));
+                }
+            }
+        }
+
+        let mut groups = IndexMap::default();
+        let mut netclasses = IndexMap::default();
+        for set in sets.sets() {
+            let mut nodes = set
+                .iter()
+                .filter_map(|index| positions.get_index(*index))
+                .peekable();
+            let Some((pos, _)) = nodes.peek() else {
+                continue;
+            };
+            let pos = **pos;
+            let nodes: Vec<NodePositions<'a>> =
+                nodes.flat_map(|(_, nodes)| nodes.iter().cloned()).collect();
+            let wires = &wires[sets.find(set[0])];
+            let netclass = areas.iter().find_map(|(area, netclass)| {
+                let polygon = &area.polyline.pts.0;
+                let inside = nodes.iter().any(|node| inside_polygon(node.pos(), polygon))
+                    || wires.iter().any(|(start, end)| segment_in_polygon(*start, *end, polygon));
+                inside.then_some(*netclass).flatten()
+            });
+            if let Some(netclass) = netclass {
+                netclasses.insert(pos, netclass);
+            }
+            groups.insert(pos, nodes);
+        }
+        (groups, netclasses, counts)
+    }
+
+    /// Join the groups of all sheets to nets and assign the names.
+    ///
+    /// Groups are joined when they share a connection key, the keys are
+    /// created from the label names, see [`Netlist::keys`].
+    fn generate_names(&mut self) {
+        let mut groups: Vec<(usize, Pt)> = Vec::new();
+        for (index, sheet) in self.sheets.iter().enumerate() {
+            for pt in sheet.node_positions.keys() {
+                groups.push((index, *pt));
+            }
+        }
+
+        //join every group with the first group of the same key
+        let mut sets = UnionFind::new(groups.len());
+        let mut keys: HashMap<Key, usize, FastHash> = HashMap::default();
+        for (index, (sheet, pt)) in groups.iter().enumerate() {
+            for key in self.keys(*sheet, *pt) {
+                sets.union(*keys.entry(key).or_insert(index), index);
+            }
+        }
+
+        for net in sets.sets() {
+            let mut net = net.iter().map(|i| groups[*i]).collect::<Vec<(usize, Pt)>>();
+            net.sort_by_key(|(sheet, _)| self.sheets[*sheet].depth());
+            let name = self.select_name(&net);
+            let index = self.nets.len();
+            for (sheet, pt) in &net {
+                let sheet = &mut self.sheets[*sheet];
+                for node in &sheet.node_positions[pt] {
+                    sheet.nets.insert(node.pos(), index);
+                }
+                sheet.nets.insert(*pt, index);
+            }
+            self.nets.push(net);
+            self.names.push(name);
+        }
+    }
+
+    /// Get the connection keys of a group. Groups with the same key are
+    /// connected:
+    ///
+    /// - local labels with the same name on a sheet.
+    /// - global labels and power symbols with the same name.
+    /// - sheet pins and the hierarchical labels in the child sheet.
+    /// - bus members with global or hierarchical scope.
+    fn keys(&self, sheet: usize, pt: Pt) -> Vec<Key<'_>> {
+        let mut keys = Vec::new();
+        for node in &self.sheets[sheet].node_positions[&pt] {
+            match node {
+                NodePositions::Pin(_, _, symbol) if power_net(symbol).is_some() => {
+                    if let Some(value) = symbol.props.iter().find(|p| p.key == el::PROPERTY_VALUE) {
+                        keys.push(Key::Global(&value.value));
+                    }
+                }
+                NodePositions::Label(_, l) => {
+                    keys.push(Key::Local(sheet, &l.text));
+                    keys.append(&mut self.bus_keys(sheet, &l.text));
+                }
+                NodePositions::GlobalLabel(_, l) => {
+                    keys.push(Key::Global(&l.text));
+                }
+                NodePositions::HierarchicalLabel(_, l) if self.sheets[sheet].parent.is_some() => {
+                    keys.push(Key::Hierarchical(sheet, &l.text));
+                }
+                NodePositions::SheetPin(_, pin, item) => {
+                    if let Some(child) = self.child(sheet, &item.uuid) {
+                        keys.push(Key::Hierarchical(child, &pin.name));
+                    }
+                }
+                _ => {}
+            }
+        }
+        keys
+    }
+
+    /// Get the connection keys for the net name when it is a member of
+    /// a bus with global or hierarchical scope.
+    fn bus_keys(&self, sheet: usize, name: &str) -> Vec<Key<'_>> {
+        let mut keys = Vec::new();
+        for bus in &self.sheets[sheet].buses {
+            for label in &bus.labels {
+                let Some(index) = label.members.iter().position(|m| m == name) else {
+                    continue;
+                };
+                for scope in &bus.labels {
+                    match scope.scope {
+                        BusScope::Local => {}
+                        BusScope::Global => {
+                            if let Some(member) = scope.members.get(index) {
+                                keys.push(Key::Global(member));
+                            }
+                        }
+                        BusScope::Hierarchical(l) => {
+                            if self.sheets[sheet].parent.is_some() {
+                                keys.push(Key::Bus(sheet, &l.text, index));
+                            }
+                        }
+                        BusScope::SheetPin(pin, item) => {
+                            if let Some(child) = self.child(sheet, &item.uuid) {
+                                keys.push(Key::Bus(child, &pin.name, index));
+                            }
                         }
                     }
                 }
-                final_positions.insert(*pos, new_nodes);
+            }
+        }
+        keys
+    }
+
+    /// Get the index of the child sheet for the sheet item uuid.
+    fn child(&self, sheet: usize, uuid: &str) -> Option<usize> {
+        self.sheets
+            .iter()
+            .position(|s| s.parent == Some(sheet) && s.uuid.as_deref() == Some(uuid))
+    }
+
+    /// Create the netlist for a single schema.
+    pub fn from(schema: &'a crate::Schema) -> Result<Self, Error> {
+        let mut netlist = Netlist {
+            sheets: vec![SheetNodes::new(
+                schema,
+                "/",
+                format!("/{}", schema.uuid),
+                None,
+                None,
+                None,
+            )],
+            nets: Vec::new(),
+            names: Vec::new(),
+            items: Vec::new(),
+            net_index: IndexMap::new(),
+            pin_index: IndexMap::new(),
+            net_settings: NetSettings::default(),
+        };
+        netlist.generate_names();
+        netlist.index_nets();
+        Ok(netlist)
+    }
+
+    /// Create the netlist for a hierarchical [`Project`].
+    pub fn from_project(project: &'a Project) -> Result<Self, Error> {
+        fn add<'a>(sheets: &mut Vec<SheetNodes<'a>>, sheet: &'a Sheet, parent: Option<usize>) {
+            let index = sheets.len();
+            sheets.push(SheetNodes::new(
+                &sheet.schema,
+                &sheet.name_path,
+                sheet.path.clone(),
+                Some(sheet.filename.clone()),
+                sheet.uuid.clone(),
+                parent,
+            ));
+            for child in &sheet.children {
+                add(sheets, child, Some(index));
             }
         }
 
-        Ok(Netlist {
-            names: Netlist::generate_names(&final_positions),
-            node_positions: final_positions,
-        })
+        let mut netlist = Netlist {
+            sheets: Vec::new(),
+            nets: Vec::new(),
+            names: Vec::new(),
+            items: Vec::new(),
+            net_index: IndexMap::new(),
+            pin_index: IndexMap::new(),
+            net_settings: project.net_settings.clone(),
+        };
+        add(&mut netlist.sheets, &project.root, None);
+        netlist.generate_names();
+        netlist.index_nets();
+        Ok(netlist)
+    }
+
+    /// Set the net settings for the net classes, the settings of a
+    /// [`Project`] are used by [`Netlist::from_project`].
+    pub fn net_settings(mut self, net_settings: &NetSettings) -> Self {
+        self.net_settings = net_settings.clone();
+        let netclasses = self
+            .items
+            .iter()
+            .zip(&self.nets)
+            .map(|(net, groups)| self.netclass(&net.name, groups))
+            .collect::<Vec<String>>();
+        for (net, netclass) in self.items.iter_mut().zip(netclasses) {
+            net.netclass = netclass;
+        }
+        self
+    }
+
+    /// Get the rule areas of the schema with the net class of the
+    /// flags on the border.
+    fn rule_areas(schema: &Schema) -> Vec<Area<'_>> {
+        schema
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                SchemaItem::RuleArea(area) => Some(area),
+                _ => None,
+            })
+            .map(|area| {
+                let netclass = schema.items.iter().find_map(|item| match item {
+                    SchemaItem::NetclassFlag(flag) if on_border(flag.pos.into(), area) => {
+                        flag.netclass()
+                    }
+                    _ => None,
+                });
+                (area, netclass)
+            })
+            .collect()
+    }
+
+    /// Get the net class of a net. A netclass flag on the net wins over
+    /// a rule area, the net settings are used for nets without a flag.
+    fn netclass(&self, name: &str, groups: &[(usize, Pt)]) -> String {
+        let flag = groups.iter().find_map(|(sheet, pt)| {
+            self.sheets[*sheet].node_positions[pt].iter().find_map(|node| match node {
+                NodePositions::NetclassFlag(_, flag) => flag.netclass(),
+                _ => None,
+            })
+        });
+        let area = || {
+            groups
+                .iter()
+                .find_map(|(sheet, pt)| self.sheets[*sheet].areas.get(pt).copied())
+        };
+        flag.or_else(area)
+            .or_else(|| self.net_settings.netclass(name))
+            .unwrap_or(DEFAULT_NETCLASS)
+            .to_string()
+    }
+
+    /// The name of the net at a node or group position of the sheet.
+    fn net_name(&self, sheet: &SheetNodes, pt: &Pt) -> Option<&String> {
+        sheet.nets.get(pt).map(|index| &self.names[*index])
     }
 
+    /// Get the net name at a position of the root sheet.
     pub fn netname(&self, pt: Pt) -> Option<String> {
-        self.names.get(&pt).cloned()
+        self.sheets.first().and_then(|s| self.net_name(s, &pt).cloned())
     }
 
+    /// Get the net name at a position of the sheet with the human readable path.
+    pub fn sheet_netname(&self, path: &str, pt: Pt) -> Option<String> {
+        self.sheets
+            .iter()
+            .find(|s| s.path == path)
+            .and_then(|s| self.net_name(s, &pt).cloned())
+    }
+
+    /// Get all nets of the netlist.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use recad_core::{netlist::Netlist, Project};
+    ///
+    /// let project = Project::load(Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+    /// let netlist = Netlist::from_project(&project).unwrap();
+    /// for net in netlist.nets() {
+    ///     println!("{}: {} pins", net.name, net.pins.len());
+    /// }
+    /// let net = netlist.pin_net("R2", "1").unwrap();
+    /// assert_eq!("/amp/A", net.name);
+    /// assert_eq!(2, netlist.net("/amp/A").unwrap().pins.len());
+    /// ```
+    pub fn nets(&self) -> &[Net] {
+        &self.items
+    }
+
+    /// Get the net class of every named net. The class definitions are
+    /// in the [`NetSettings`] of the project.
+    pub fn netclasses(&self) -> IndexMap<String, String> {
+        self.items
+            .iter()
+            .filter(|net| !net.name.is_empty())
+            .map(|net| (net.name.clone(), net.netclass.clone()))
+            .collect()
+    }
+
+    /// Get the net by name.
+    pub fn net(&self, name: &str) -> Option<&Net> {
+        self.net_index.get(name).map(|index| &self.items[*index])
+    }
+
+    /// Get the net which is connected to a symbol pin.
+    pub fn pin_net(&self, reference: &str, pin: &str) -> Option<&Net> {
+        self.pin_index
+            .get(&(reference.to_string(), pin.to_string()))
+            .map(|index| &self.items[*index])
+    }
+
+    /// Collect the items of every net and index the nets by
+    /// name and by pin.
+    fn index_nets(&mut self) {
+        self.items = self.nets.iter().map(|groups| self.net_items(groups)).collect();
+        for (index, net) in self.items.iter().enumerate() {
+            self.net_index.entry(net.name.clone()).or_insert(index);
+            for pin in &net.pins {
+                self.pin_index
+                    .entry((pin.reference.clone(), pin.pin.clone()))
+                    .or_insert(index);
+            }
+        }
+    }
+
+    /// Collect the pins and labels of the groups of a net.
+    fn net_items(&self, groups: &[(usize, Pt)]) -> Net {
+        let mut net = Net {
+            name: String::new(),
+            pins: Vec::new(),
+            power_pins: Vec::new(),
+            labels: Vec::new(),
+            positions: Vec::new(),
+            netclass: String::new(),
+        };
+        //the shared pins of the units are connected more than once
+        let mut seen: IndexSet<(&str, &str, &str), FastHash> = IndexSet::default();
+        for (sheet, pt) in groups {
+            let sheet = &self.sheets[*sheet];
+            if net.name.is_empty() {
+                if let Some(name) = self.net_name(sheet, pt) {
+                    net.name = name.clone();
+                }
+            }
+            for node in &sheet.node_positions[pt] {
+                let pos = node.pos();
+                net.positions.push((sheet.path.clone(), pos));
+                let label = |text: &str, kind: LabelKind| NetLabel {
+                    text: text.to_string(),
+                    kind,
+                    sheet: sheet.path.clone(),
+                    pos,
+                };
+                match node {
+                    NodePositions::Pin(_, pin, symbol) => {
+                        if let Some(power) = power_net(symbol) {
+                            net.labels.push(label(&power, LabelKind::Power));
+                        }
+                        let reference = sheet.reference(symbol);
+                        if !seen.insert((reference, &pin.number.name, &sheet.path)) {
+                            continue;
+                        }
+                        let pins = if reference.starts_with('#') {
+                            &mut net.power_pins
+                        } else {
+                            &mut net.pins
+                        };
+                        pins.push(NetPin {
+                            reference: reference.to_string(),
+                            pin: pin.number.name.clone(),
+                            name: pin.name.name.clone(),
+                            electrical_type: pin.electrical_type.clone(),
+                            unit: symbol.unit,
+                            sheet: sheet.path.clone(),
+                            pos,
+                        });
+                    }
+                    NodePositions::Label(_, l) => net.labels.push(label(&l.text, LabelKind::Local)),
+                    NodePositions::GlobalLabel(_, l) => {
+                        net.labels.push(label(&l.text, LabelKind::Global))
+                    }
+                    NodePositions::HierarchicalLabel(_, l) => {
+                        net.labels.push(label(&l.text, LabelKind::Hierarchical))
+                    }
+                    NodePositions::SheetPin(_, pin, _) => {
+                        net.labels.push(label(&pin.name, LabelKind::SheetPin))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        net.netclass = self.netclass(&net.name, groups);
+        net
+    }
+
+    /// Write the netlist in the formats of `kicad-cli sch export netlist`.
+    ///
+    /// The netlist contains the components with their fields, footprints and
+    /// timestamps, the library parts with the pins and electrical types, and the
+    /// nets with the connected pins. Power symbols are not exported. Both formats
+    /// contain the same elements.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use recad_core::{netlist::{Netlist, NetlistFormat}, Project};
+    ///
+    /// let project = Project::load(Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+    /// let netlist = Netlist::from_project(&project).unwrap();
+    /// let mut file = std::fs::File::create("target/hierarchy.xml").unwrap();
+    /// netlist.export(&mut file, NetlistFormat::KicadXml).unwrap();
+    ///
+    /// let mut file = std::fs::File::create("target/hierarchy.net").unwrap();
+    /// netlist.export(&mut file, NetlistFormat::KicadSexpr).unwrap();
+    /// ```
+    pub fn export(&self, writer: &mut dyn Write, format: NetlistFormat) -> Result<(), Error> {
+        match format {
+            NetlistFormat::KicadXml => self.write_xml(writer),
+            NetlistFormat::KicadSexpr => self.write_sexp(writer),
+        }
+    }
+
+
+    /// Fill the [`Circuit`] with the spice items of the schema symbols.
+    ///
+    /// The simulation model of a symbol is taken from the `Sim.Device`, `Sim.Type`,
+    /// `Sim.Pins` and `Sim.Params` properties. When they are not set, the legacy
+    /// `Spice_Primitive`, `Spice_Model` and `Spice_Node_Sequence` properties are used.
+    /// Resistors, capacitors, inductors and sources without simulation properties
+    /// are detected by the reference prefix.
+    ///
+    /// Power symbols and symbols which are excluded from the simulation
+    /// (`exclude_from_sim`, `Sim.Enable` or `Spice_Netlist_Enabled`) are skipped.
     pub fn circuit(&self, circuit: &mut Circuit) -> Result<(), Error> {
-        ////Create a spice entry for each referenca
-        //for (reference, symbols) in &self.symbols {
-        //    let lib_id: String = symbols.first().unwrap().value(el::LIB_ID).unwrap();
-        //    //but not for the power symbols
-        //    if lib_id.starts_with("power:") {
-        //        continue;
-        //    }
-        //
-        //    let first_symbol = &symbols.first().unwrap();
-        //
-        //    //skip symbol when Netlist_Enabled is 'N'
-        //    let netlist_enabled: Option<String> = first_symbol.property("Spice_Netlist_Enabled"); //TODO differenet
-        //                                                                                          //name in new
-        //                                                                                          //KiCAD verison
-        //    if let Some(enabled) = netlist_enabled {
-        //        if enabled == "N" {
-        //            continue;
-        //        }
-        //    }
-        //
-        //    //create the pin order
-        //    let lib_symbols = self
-        //        .schema
-        //        .root()
-        //        .unwrap()
-        //        .query(el::LIB_SYMBOLS)
-        //        .next()
-        //        .unwrap();
-        //    let lib = lib_symbols
-        //        .query(el::SYMBOL)
-        //        .find(|s| {
-        //            let name: String = s.get(0).unwrap();
-        //            name == lib_id
-        //        })
-        //        .unwrap();
-        //    let my_pins = pin_names(lib).unwrap();
-        //    let mut pin_sequence: Vec<String> = my_pins.keys().map(|s| s.to_string()).collect();
-        //    pin_sequence.sort_by_key(|x| x.parse::<i32>().unwrap()); //TODO could be string
-        //
-        //    //when Node_Sequence is defined, use it
-        //    let netlist_sequence: Option<String> = first_symbol.property("Spice_Node_Sequence"); //TODO
-        //    if let Some(sequence) = netlist_sequence {
-        //        pin_sequence.clear();
-        //        let splits: Vec<&str> = sequence.split(' ').collect();
-        //        for s in splits {
-        //            pin_sequence.push(s.to_string());
-        //        }
-        //    }
-        //
-        //    let mut nodes = Vec::new();
-        //    for n in pin_sequence {
-        //        let pin = my_pins.get(&n).unwrap();
-        //        for symbol in symbols {
-        //            let unit: usize = symbol.value(el::SYMBOL_UNIT).unwrap();
-        //            if unit == pin.1 {
-        //                let at = pin.0.query(el::AT).next().unwrap();
-        //                let x: f64 = at.get(0).unwrap();
-        //                let y: f64 = at.get(1).unwrap();
-        //                let pts = Shape::transform(*symbol, &arr1(&[x, y]));
-        //                let p0 = Point::new(pts[0], pts[1]);
-        //                if let Some(nn) = self.node_name(&p0) {
-        //                    nodes.push(nn);
-        //                } else {
-        //                    nodes.push(String::from("NF"));
-        //                }
-        //            }
-        //        }
-        //    }
-        //
-        //    //write the spice netlist item
-        //    let spice_primitive: Option<String> = first_symbol.property("Spice_Primitive"); //TODO
-        //    let spice_model = first_symbol.property("Spice_Model");
-        //    let spice_value = first_symbol.property("Value");
-        //    if let Some(primitive) = spice_primitive {
-        //        if primitive == "X" {
-        //            circuit.circuit(reference.to_string(), nodes, spice_model.unwrap())?;
-        //        } else if primitive == "Q" {
-        //            circuit.bjt(
-        //                reference.to_string(),
-        //                nodes[0].clone(),
-        //                nodes[1].clone(),
-        //                nodes[2].clone(),
-        //                spice_model.unwrap(),
-        //            );
-        //        } else if primitive == "J" {
-        //            circuit.jfet(
-        //                reference.to_string(),
-        //                nodes[0].clone(),
-        //                nodes[1].clone(),
-        //                nodes[2].clone(),
-        //                spice_model.unwrap(),
-        //            );
-        //        } else if primitive == "D" {
-        //            circuit.diode(
-        //                reference.to_string(),
-        //                nodes[0].clone(),
-        //                nodes[1].clone(),
-        //                spice_model.unwrap(),
-        //            );
-        //        } else {
-        //            println!(
-        //                "Other node with 'X' -> {}{} - - {}",
-        //                primitive,
-        //                reference,
-        //                spice_value.unwrap()
-        //            );
-        //        }
-        //    } else if reference.starts_with('R') {
-        //        circuit.resistor(
-        //            reference.clone(),
-        //            nodes[0].clone(),
-        //            nodes[1].clone(),
-        //            spice_value.unwrap(),
-        //        );
-        //    } else if reference.starts_with('C') {
-        //        circuit.capacitor(
-        //            reference.clone(),
-        //            nodes[0].clone(),
-        //            nodes[1].clone(),
-        //            spice_value.unwrap(),
-        //        );
-        //    // } else if std::env::var("ELEKTRON_DEBUG").is_ok() {
-        //    } else {
-        //        println!(
-        //            "Unkknwon Reference: {} ({:?}) {}",
-        //            reference,
-        //            nodes,
-        //            spice_value.unwrap()
-        //        );
-        //    }
-        //}
+        self.spice_items(circuit, |_| true, spice_net)
+    }
+
+    /// Create a `.subckt` definition from the sheet with the human readable path.
+    ///
+    /// The ports are the names of hierarchical or global labels of the sheet in the
+    /// order of the subcircuit nodes. The nets of the ports are renamed to the port
+    /// names. The subcircuit contains the symbols of the sheet and its child sheets.
+    /// Returns the nodes and the circuit for [`Circuit::subcircuit`].
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use recad_core::{netlist::Netlist, Circuit, Schema};
+    ///
+    /// let schema = Schema::load(Path::new("tests/hierarchy/amp.kicad_sch")).unwrap();
+    /// let netlist = Netlist::from(&schema).unwrap();
+    /// let (ports, amp) = netlist.subckt("/", &["A", "B"]).unwrap();
+    ///
+    /// let mut library = Circuit::new(String::from("amp"), vec![]);
+    /// library.subcircuit(String::from("amp"), ports, amp).unwrap();
+    /// library.save_library(Path::new("target/amp.lib")).unwrap();
+    /// ```
+    pub fn subckt(&self, path: &str, ports: &[&str]) -> Result<(Vec<String>, Circuit), Error> {
+        let Some(sheet) = self.sheets.iter().find(|s| s.path == path) else {
+            return Err(Error(
+                String::from("spice"),
+                format!("sheet {} not found", path),
+            ));
+        };
+        //the net names of the ports
+        let mut nets: IndexMap<String, String> = IndexMap::new();
+        for port in ports {
+            let net = sheet.node_positions.iter().find_map(|(pt, nodes)| {
+                nodes
+                    .iter()
+                    .find(|node| match node {
+                        NodePositions::HierarchicalLabel(_, l) => l.text == *port,
+                        NodePositions::GlobalLabel(_, l) => l.text == *port,
+                        _ => false,
+                    })
+                    .and_then(|node| self.net_name(sheet, &node.pos()).or(self.net_name(sheet, pt)))
+            });
+            let Some(net) = net else {
+                return Err(Error(
+                    String::from("spice"),
+                    format!("port {} not found in sheet {}", port, path),
+                ));
+            };
+            if spice_net(net) == "0" {
+                return Err(Error(
+                    String::from("spice"),
+                    format!("port {} is connected to ground", port),
+                ));
+            }
+            if let Some(other) = nets.insert(net.clone(), port.to_string()) {
+                return Err(Error(
+                    String::from("spice"),
+                    format!("ports {} and {} are connected", other, port),
+                ));
+            }
+        }
+
+        let mut circuit = Circuit::new(path.to_string(), vec![]);
+        self.spice_items(
+            &mut circuit,
+            |index| self.sheets[index].path.starts_with(path),
+            |name| nets.get(name).cloned().unwrap_or_else(|| spice_net(name)),
+        )?;
+        Ok((ports.iter().map(|p| p.to_string()).collect(), circuit))
+    }
+
+    /// Get the tolerances from the `Tolerance` property of the symbols, e.g. `5%`.
+    ///
+    /// Symbols which are excluded from the simulation are skipped.
+    pub fn tolerances(&self) -> Result<Vec<Tolerance>, Error> {
+        let mut tolerances: Vec<Tolerance> = Vec::new();
+        for sheet in &self.sheets {
+            for item in &sheet.schema.items {
+                let SchemaItem::Symbol(symbol) = item else {
+                    continue;
+                };
+                let reference = sheet.reference(symbol);
+                if reference.starts_with('#') || tolerances.iter().any(|t| t.reference == reference) {
+                    continue;
+                }
+                let Some(value) = symbol
+                    .props
+                    .iter()
+                    .find(|p| p.key == el::PROPERTY_TOLERANCE)
+                    .or_else(|| {
+                        sheet
+                            .schema
+                            .library_symbol(&symbol.lib_id)
+                            .and_then(|l| l.props.iter().find(|p| p.key == el::PROPERTY_TOLERANCE))
+                    })
+                    .map(|p| p.value.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                else {
+                    continue;
+                };
+                if SimModel::from(sheet.schema, symbol, reference)?.is_none() {
+                    continue;
+                }
+                tolerances.push(Tolerance::parse(reference, &value)?);
+            }
+        }
+        Ok(tolerances)
+    }
+
+    /// Add the spice items for the symbols of the selected sheets,
+    /// the net names are converted with `net`.
+    fn spice_items(
+        &self,
+        circuit: &mut Circuit,
+        sheets: impl Fn(usize) -> bool,
+        net: impl Fn(&str) -> String,
+    ) -> Result<(), Error> {
+        //collect the units of the symbols and the net names of the pins
+        type Units<'b> = (&'b Schema, &'b Symbol, IndexMap<String, String>);
+        let mut symbols: IndexMap<(usize, String), Units> = IndexMap::new();
+        for (index, sheet) in self.sheets.iter().enumerate() {
+            if !sheets(index) {
+                continue;
+            }
+            for item in &sheet.schema.items {
+                let SchemaItem::Symbol(symbol) = item else {
+                    continue;
+                };
+                let Some(lib) = sheet.schema.library_symbol(&symbol.lib_id) else {
+                    continue;
+                };
+                let reference = sheet.reference(symbol);
+                if lib.power || reference.starts_with('#') {
+                    continue;
+                }
+                let (_, _, nets) = symbols
+                    .entry((index, reference.to_string()))
+                    .or_insert_with(|| (sheet.schema, symbol, IndexMap::new()));
+                for pin in lib.pins(symbol.unit) {
+                    let pt = crate::math::pin_position(symbol, pin);
+                    if let Some(name) = self.net_name(sheet, &pt) {
+                        nets.insert(pin.number.name.clone(), net(name));
+                    }
+                }
+            }
+        }
+
+        for ((_, reference), (schema, symbol, nets)) in symbols {
+            let Some(model) = SimModel::from(schema, symbol, &reference)? else {
+                continue;
+            };
+            let pins = if model.pins.is_empty() {
+                let mut pins = nets.keys().cloned().collect::<Vec<String>>();
+                pins.sort_by(|a, b| match (a.parse::<u32>(), b.parse::<u32>()) {
+                    (Ok(a), Ok(b)) => a.cmp(&b),
+                    _ => a.cmp(b),
+                });
+                pins
+            } else {
+                model.pins
+            };
+            let nodes = pins
+                .iter()
+                .map(|pin| {
+                    nets.get(pin)
+                        .cloned()
+                        .unwrap_or_else(|| format!("{}_{}", reference, pin))
+                })
+                .collect::<Vec<String>>();
+
+            let expected = match model.primitive.as_str() {
+                "R" | "C" | "L" | "D" | "V" | "I" => 2,
+                "Q" | "J" | "M" => 3,
+                "X" => nodes.len(),
+                primitive => {
+                    return Err(Error(
+                        String::from("spice"),
+                        format!("unsupported spice primitive '{}' for {}", primitive, reference),
+                    ))
+                }
+            };
+            if nodes.len() < expected || (expected == 2 && nodes.len() != expected) {
+                return Err(Error(
+                    String::from("spice"),
+                    format!("{} has {} nodes, expected {}", reference, nodes.len(), expected),
+                ));
+            }
+            let n = |i: usize| nodes[i].clone();
+            match model.primitive.as_str() {
+                "R" => circuit.resistor(reference, n(0), n(1), model.value),
+                "C" => circuit.capacitor(reference, n(0), n(1), model.value),
+                "D" => circuit.diode(reference, n(0), n(1), model.value),
+                "L" => circuit.inductor(reference, n(0), n(1), model.value),
+                "V" => circuit.voltage(reference, n(0), n(1), model.value),
+                "I" => circuit.current(reference, n(0), n(1), model.value),
+                "Q" => circuit.bjt(reference, n(0), n(1), n(2), model.value),
+                "J" => circuit.jfet(reference, n(0), n(1), n(2), model.value),
+                //the bulk is connected to the source for three terminal mosfets.
+                "M" => circuit.mosfet(
+                    reference,
+                    n(0),
+                    n(1),
+                    n(2),
+                    nodes.get(3).cloned().unwrap_or(n(2)),
+                    model.value,
+                ),
+                _ => circuit.circuit(reference, nodes, model.value)?,
+            }
+        }
         Ok(())
     }
 }
 
+/// The ground net names are mapped to the spice ground node `0`, also the local
+/// labels on the root sheet. The parentheses and spaces of the net names are not
+/// allowed in spice.
+pub(crate) fn spice_net(name: &str) -> String {
+    if matches!(name.strip_prefix('/').unwrap_or(name), "GND" | "0") {
+        String::from("0")
+    } else {
+        name.replace(['(', ')', ' '], "_")
+    }
+}
+
+/// The simulation model of a symbol.
+struct SimModel {
+    /// The spice primitive, the first letter of the spice element.
+    primitive: String,
+    /// The symbol pin numbers in the order of the spice nodes,
+    /// when empty the pins are ordered by the pin number.
+    pins: Vec<String>,
+    /// The value or model name of the spice element.
+    value: String,
+}
+
+impl SimModel {
+    /// Get the simulation model from the symbol properties, returns `None`
+    /// when the symbol is excluded from the simulation.
+    fn from(schema: &Schema, symbol: &Symbol, reference: &str) -> Result<Option<Self>, Error> {
+        //the instance properties override the library properties.
+        let property = |key: &str| -> Option<String> {
+            symbol
+                .props
+                .iter()
+                .find(|p| p.key == key)
+                .or_else(|| {
+                    schema
+                        .library_symbol(&symbol.lib_id)
+                        .and_then(|l| l.props.iter().find(|p| p.key == key))
+                })
+                .map(|p| p.value.trim().to_string())
+        };
+        let value = property(el::PROPERTY_VALUE).unwrap_or_default();
+
+        if symbol.exclude_from_sim
+            || property(el::PROPERTY_SIM_ENABLE).is_some_and(|e| e == "0")
+            || property(el::PROPERTY_SPICE_NETLIST_ENABLED)
+                .is_some_and(|e| e.eq_ignore_ascii_case("n"))
+        {
+            return Ok(None);
+        }
+
+        if let Some(device) = property(el::PROPERTY_SIM_DEVICE) {
+            let params = sim_params(&property(el::PROPERTY_SIM_PARAMS).unwrap_or_default());
+            let model = property(el::PROPERTY_SIM_NAME).unwrap_or(value.clone());
+            let param = |key: &str| params.get(key).cloned().unwrap_or(value.clone());
+            let (primitive, value) = match device.to_uppercase().as_str() {
+                "SPICE" => (
+                    params.get("type").cloned().unwrap_or_else(|| {
+                        reference.chars().next().unwrap_or('X').to_string()
+                    }),
+                    params.get("model").cloned().unwrap_or(value.clone()),
+                ),
+                "R" => (String::from("R"), spice_value(param("r"))),
+                "C" => (String::from("C"), spice_value(param("c"))),
+                "L" => (String::from("L"), spice_value(param("l"))),
+                "D" => (String::from("D"), model),
+                "NPN" | "PNP" => (String::from("Q"), model),
+                "NJFET" | "PJFET" => (String::from("J"), model),
+                "NMOS" | "PMOS" => (String::from("M"), model),
+                "SUBCKT" => (String::from("X"), model),
+                "V" | "I" => (
+                    device.to_uppercase(),
+                    source_value(&property(el::PROPERTY_SIM_TYPE), &params, &value),
+                ),
+                device => {
+                    return Err(Error(
+                        String::from("spice"),
+                        format!("unsupported simulation device '{}' for {}", device, reference),
+                    ))
+                }
+            };
+            let primitive = primitive.to_uppercase();
+            return Ok(Some(Self {
+                pins: sim_pins(&property(el::PROPERTY_SIM_PINS).unwrap_or_default(), &primitive),
+                primitive,
+                value,
+            }));
+        }
+
+        if let Some(primitive) = property(el::PROPERTY_SPICE_PRIMITIVE) {
+            return Ok(Some(Self {
+                primitive: primitive.to_uppercase(),
+                pins: property(el::PROPERTY_SPICE_NODE_SEQUENCE)
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(|p| p.to_string())
+                    .collect(),
+                value: property(el::PROPERTY_SPICE_MODEL).unwrap_or(value),
+            }));
+        }
+
+        let pins = property(el::PROPERTY_SPICE_NODE_SEQUENCE)
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|p| p.to_string())
+            .collect();
+        match reference.chars().next() {
+            Some(primitive @ ('R' | 'C' | 'L')) => Ok(Some(Self {
+                primitive: primitive.to_string(),
+                pins,
+                value: spice_value(value),
+            })),
+            Some(primitive @ ('V' | 'I')) => Ok(Some(Self {
+                primitive: primitive.to_string(),
+                pins,
+                value,
+            })),
+            _ => Err(Error(
+                String::from("spice"),
+                format!("no simulation model for {} ({})", reference, symbol.lib_id),
+            )),
+        }
+    }
+}
+
+/// Write the value of a passive element in the spice notation, `4k7` is written as `4.7k`.
+/// Values which are not numbers, like parameters, are not changed.
+fn spice_value(value: String) -> String {
+    match value.parse::<Value>() {
+        Ok(number) => number.spice(),
+        Err(_) => value,
+    }
+}
+
+/// The model pin names of a spice primitive in the order of the spice nodes.
+fn model_pins(primitive: &str) -> &'static [&'static str] {
+    match primitive {
+        "D" => &["A", "K"],
+        "Q" => &["C", "B", "E", "S"],
+        "J" => &["D", "G", "S"],
+        "M" => &["D", "G", "S", "B"],
+        "R" | "C" | "L" | "V" | "I" => &["+", "-"],
+        _ => &[],
+    }
+}
+
+/// Get the symbol pin numbers in the order of the spice nodes from the `Sim.Pins`
+/// property, `1=K 2=A` maps the symbol pin to the model pin. The pins are ordered
+/// by the model pins of the primitive, subcircuits use the order of the property.
+fn sim_pins(pins: &str, primitive: &str) -> Vec<String> {
+    let pairs = pins
+        .split_whitespace()
+        .map(|p| {
+            let (pin, model) = p.split_once('=').unwrap_or((p, ""));
+            (pin.to_string(), model.to_string())
+        })
+        .collect::<Vec<(String, String)>>();
+    let order = model_pins(primitive);
+    let known = |model: &str| order.iter().any(|o| o.eq_ignore_ascii_case(model));
+    //unknown model pin names keep the order of the property
+    if order.is_empty() || !pairs.iter().all(|(_, model)| known(model)) {
+        return pairs.into_iter().map(|(pin, _)| pin).collect();
+    }
+    order
+        .iter()
+        .filter_map(|name| pairs.iter().find(|(_, model)| model.eq_ignore_ascii_case(name)))
+        .map(|(pin, _)| pin.clone())
+        .collect()
+}
+
+/// Parse the `Sim.Params` property, the values can be quoted: `type="X" model="TL072"`.
+fn sim_params(params: &str) -> IndexMap<String, String> {
+    let params = params.replace("\\\"", "\"");
+    let mut result = IndexMap::new();
+    let mut chars = params.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let key = std::iter::from_fn(|| chars.next_if(|c| *c != '=' && !c.is_whitespace()))
+            .collect::<String>();
+        if key.is_empty() {
+            break;
+        }
+        if chars.next_if_eq(&'=').is_none() {
+            result.insert(key, String::new());
+            continue;
+        }
+        let value = if chars.next_if_eq(&'"').is_some() {
+            let value = std::iter::from_fn(|| chars.next_if(|c| *c != '"')).collect::<String>();
+            chars.next();
+            value
+        } else {
+            std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace())).collect::<String>()
+        };
+        result.insert(key.to_lowercase(), value);
+    }
+    result
+}
+
+/// The parameter names of the source types in the order of the spice arguments.
+fn source_params(kind: &str) -> Option<&'static [&'static str]> {
+    match kind {
+        "SIN" => Some(&["dc", "ampl", "f", "td", "theta", "phase"]),
+        "PULSE" => Some(&["y1", "y2", "td", "tr", "tf", "tw", "per", "np"]),
+        "EXP" => Some(&["y1", "y2", "td1", "tau1", "td2", "tau2"]),
+        "SFFM" => Some(&["vo", "va", "fc", "mdi", "fs", "phasec", "phases"]),
+        "AM" => Some(&["va", "vo", "mf", "fc", "td", "phasem", "phasec"]),
+        _ => None,
+    }
+}
+
+/// Create the spice value of a voltage or current source from the `Sim.Type` and `Sim.Params`.
+///
+/// The arguments are ordered by the parameter names of the type, missing parameters at the
+/// end are skipped and missing parameters in between are `0`. The `dc` and `ac` values which
+/// are not arguments of the type are written as keywords: `DC 1 AC 1 SIN(0 1 1k)`.
+fn source_value(kind: &Option<String>, params: &IndexMap<String, String>, value: &str) -> String {
+    let kind = kind.as_deref().map(|k| k.to_uppercase()).unwrap_or(String::from("DC"));
+    let ac = params.get("ac").map(|ac| format!("AC {}", ac));
+    if kind == "DC" {
+        let dc = params.get("dc").cloned().unwrap_or(value.to_string());
+        return match ac {
+            Some(ac) => format!("DC {} {}", dc, ac),
+            None => dc,
+        };
+    }
+
+    let args = match source_params(&kind) {
+        Some(names) => {
+            let last = names.iter().rposition(|name| params.contains_key(*name));
+            names[..last.map(|l| l + 1).unwrap_or(0)]
+                .iter()
+                .map(|name| params.get(*name).cloned().unwrap_or(String::from("0")))
+                .collect::<Vec<String>>()
+        }
+        None => params
+            .iter()
+            .filter(|(key, _)| *key != "ac" && *key != "dc")
+            .map(|(_, value)| value.clone())
+            .collect(),
+    };
+    let dc = params
+        .get("dc")
+        .filter(|_| !source_params(&kind).is_some_and(|names| names.contains(&"dc")))
+        .map(|dc| format!("DC {}", dc));
+    dc.into_iter()
+        .chain(ac)
+        .chain(std::iter::once(format!("{}({})", kind, args.join(" "))))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
 //implemnt the dispaly trait for netlist
 impl<'a> std::fmt::Display for Netlist<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Netlist:")?;
-        for pt in &self.node_positions {
-            writeln!(f, "- Pt({}x{})", pt.0.x, pt.0.y)?;
-            for node in pt.1 {
-                match node {
-                    NodePositions::Pin(_, pin, symbol) => {
-                        writeln!(
-                            f,
-                            "    Pin({}:{})",
-                            symbol.property(el::PROPERTY_REFERENCE),
-                            pin.number.name
-                        )?;
-                    }
-                    NodePositions::Label(_, l) => {
-                        writeln!(f, "    LocalLabel({})", l.text)?;
-                    }
-                    NodePositions::GlobalLabel(_, l) => {
-                        writeln!(f, "    GlobalLabel({})", l.text)?;
-                    }
-                    NodePositions::NoConnect(_) => {
-                        writeln!(f, "    NoConnect()")?;
-                    }
-                    NodePositions::Junction(_) => {
-                        writeln!(f, "    Junction()")?;
+        for sheet in &self.sheets {
+            if self.sheets.len() > 1 {
+                writeln!(f, "Sheet({})", sheet.path)?;
+            }
+            for pt in &sheet.node_positions {
+                writeln!(f, "- Pt({}x{})", pt.0.x, pt.0.y)?;
+                for node in pt.1 {
+                    match node {
+                        NodePositions::Pin(_, pin, symbol) => {
+                            writeln!(
+                                f,
+                                "    Pin({}:{})",
+                                sheet.reference(symbol),
+                                pin.number.name
+                            )?;
+                        }
+                        NodePositions::Label(_, l) => {
+                            writeln!(f, "    LocalLabel({})", l.text)?;
+                        }
+                        NodePositions::GlobalLabel(_, l) => {
+                            writeln!(f, "    GlobalLabel({})", l.text)?;
+                        }
+                        NodePositions::HierarchicalLabel(_, l) => {
+                            writeln!(f, "    HierarchicalLabel({})", l.text)?;
+                        }
+                        NodePositions::SheetPin(_, pin, _) => {
+                            writeln!(f, "    SheetPin({})", pin.name)?;
+                        }
+                        NodePositions::BusEntry(..) => {
+                            writeln!(f, "    BusEntry()")?;
+                        }
+                        NodePositions::NetclassFlag(_, flag) => {
+                            writeln!(f, "    NetclassFlag({})", flag.netclass().unwrap_or_default())?;
+                        }
+                        NodePositions::NoConnect(_) => {
+                            writeln!(f, "    NoConnect()")?;
+                        }
+                        NodePositions::Junction(_) => {
+                            writeln!(f, "    Junction()")?;
+                        }
                     }
                 }
             }
@@ -404,175 +1844,407 @@ mod tests {
     #[test]
     fn test_wires() {
         let schema = crate::Schema::load(std::path::Path::new("tests/summe/summe.kicad_sch")).unwrap();
-        let wires = super::Netlist::wires(&schema);
-        let wire = wires
-            .get(&Pt {
-                x: 179.07,
-                y: 49.53,
-            })
-            .unwrap();
+        let netlist = super::Netlist::from(&schema).unwrap();
+        let pt = |x: f32, y: f32| Pt { x, y };
+        //three wires end at the junction, the other wire ends are connected
+        assert_eq!(Some(&3), netlist.sheets[0].wires.get(&pt(179.07, 49.53)));
+        let name = netlist.netname(pt(179.07, 49.53)).unwrap();
+        for end in [pt(179.07, 34.29), pt(180.34, 49.53), pt(167.64, 49.53)] {
+            assert_eq!(Some(&name), netlist.netname(end).as_ref());
+        }
+    }
+
+    /// Create a schema with wires and labels, a label without text is a junction.
+    fn wired(wires: &[(Pt, Pt)], labels: &[(&str, Pt)]) -> crate::Schema {
+        use crate::schema::{Junction, LocalLabel, SchemaItem, Wire};
+        let mut schema = crate::Schema::new("wired");
+        for (start, end) in wires {
+            let mut wire = Wire::new();
+            wire.pts = crate::gr::Pts(vec![*start, *end]);
+            schema.items.push(SchemaItem::Wire(wire));
+        }
+        for (text, pt) in labels {
+            let pos = crate::gr::Pos { x: pt.x, y: pt.y, angle: 0.0 };
+            if text.is_empty() {
+                let mut junction = Junction::new();
+                junction.pos = pos;
+                schema.items.push(SchemaItem::Junction(junction));
+            } else {
+                let mut label = LocalLabel::new(text);
+                label.pos = pos;
+                schema.items.push(SchemaItem::LocalLabel(label));
+            }
+        }
+        schema
+    }
+
+    #[test]
+    fn crossing_wires() {
+        let pt = |x: f32, y: f32| Pt { x, y };
+        let wires = [(pt(0.0, 0.0), pt(10.16, 0.0)), (pt(5.08, -5.08), pt(5.08, 5.08))];
+        let labels = [("A", pt(0.0, 0.0)), ("B", pt(5.08, -5.08)), ("", pt(5.08, 5.08))];
+        let schema = wired(&wires, &labels);
+        let netlist = super::Netlist::from(&schema).unwrap();
+        assert_eq!(Some(String::from("/B")), netlist.netname(pt(5.08, 5.08)));
+
+        //the junction connects the crossing wires
+        let schema = wired(&wires, &[("A", pt(0.0, 0.0)), ("B", pt(5.08, -5.08)), ("", pt(5.08, 0.0))]);
+        let netlist = super::Netlist::from(&schema).unwrap();
+        assert_eq!(Some(String::from("/A")), netlist.netname(pt(5.08, -5.08)));
+
+        //a wire which ends in the middle of another wire is connected
+        let schema = wired(&wires[..1], &[("A", pt(0.0, 0.0)), ("", pt(5.08, 0.0))]);
+        let netlist = super::Netlist::from(&schema).unwrap();
+        assert_eq!(Some(String::from("/A")), netlist.netname(pt(5.08, 0.0)));
+    }
+
+    #[test]
+    fn long_wire_chain() {
+        //a serpentine of 20000 wires, the end is connected to the label at the start
+        let (columns, rows) = (100, 200);
+        let pt = |column: usize, row: usize| Pt { x: column as f32 * 2.54, y: row as f32 * 2.54 };
+        let mut wires = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                let (a, b) = if row % 2 == 0 { (column, column + 1) } else { (columns - column, columns - column - 1) };
+                wires.push((pt(a, row), pt(b, row)));
+            }
+            let column = if row % 2 == 0 { columns } else { 0 };
+            wires.push((pt(column, row), pt(column, row + 1)));
+        }
+        let end = wires.last().unwrap().1;
+        let schema = wired(&wires, &[("A", pt(0, 0)), ("", end)]);
+        let netlist = super::Netlist::from(&schema).unwrap();
+        assert_eq!(20_200, wires.len());
+        assert_eq!(Some(String::from("/A")), netlist.netname(end));
+        assert_eq!(1, netlist.nets.len());
+    }
+
+    #[test]
+    fn check_positions() {
+        let schema = crate::Schema::load(std::path::Path::new(SCHEMA_SUMME)).unwrap();
+        let netlist = super::Netlist::from(&schema).unwrap();
+        assert_eq!(String::from("Net-(C9-Pad2)"), netlist.netname(crate::gr::Pt { x: 207.01, y: 52.07 }).unwrap());
+        assert_eq!(String::from("Net-(R7-Pad2)"), netlist.netname(crate::gr::Pt { x: 81.28, y: 102.87 }).unwrap());
+        assert_eq!(String::from("+15V"), netlist.netname(crate::gr::Pt { x: 153.67, y: 148.59 }).unwrap());
+    }
+
+    #[test]
+    fn hierarchical_nets() {
+        let project = crate::Project::load(std::path::Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from_project(&project).unwrap();
+        //local label on the root sheet
+        assert_eq!(String::from("/VIN"), netlist.netname(Pt { x: 50.8, y: 46.99 }).unwrap());
+        //sheet pin A is connected to the hierarchical label A
+        assert_eq!(String::from("/amp/A"), netlist.netname(Pt { x: 50.8, y: 54.61 }).unwrap());
+        assert_eq!(String::from("/amp/A"), netlist.sheet_netname("/amp/", Pt { x: 63.5, y: 50.8 }).unwrap());
+        //the hierarchical label B has priority over the local label FB in the sub sheet
+        assert_eq!(String::from("/amp/B"), netlist.netname(Pt { x: 101.6, y: 60.96 }).unwrap());
+        assert_eq!(String::from("/amp/B"), netlist.sheet_netname("/amp/", Pt { x: 63.5, y: 58.42 }).unwrap());
+    }
+
+    #[test]
+    fn net_names() {
+        let schema = crate::Schema::load(std::path::Path::new("tests/spice/spice.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from(&schema).unwrap();
+        for net in netlist.nets() {
+            if net.labels.is_empty() {
+                let mut names = net
+                    .pins
+                    .iter()
+                    .map(|p| format!("Net-({}-Pad{})", p.reference, p.pin))
+                    .collect::<Vec<String>>();
+                names.sort();
+                if names.len() == 1 {
+                    assert_eq!(names[0].replace("Net-", "unconnected-"), net.name);
+                } else {
+                    assert_eq!(names[0], net.name);
+                }
+            }
+        }
+
+        let project = crate::Project::load(std::path::Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from_project(&project).unwrap();
+        let conflicts = netlist.conflicts();
+        assert_eq!(1, conflicts.len());
+        assert_eq!("/amp/B", conflicts[0].net);
+        assert_eq!("/amp/", conflicts[0].sheet);
+        assert_eq!(vec!["/amp/B", "/amp/FB"], conflicts[0].names);
+    }
+
+    #[test]
+    fn spice_net() {
+        assert_eq!("0", super::spice_net("GND"));
+        assert_eq!("0", super::spice_net("/GND"));
+        assert_eq!("/amp/GND", super::spice_net("/amp/GND"));
+        assert_eq!("Net-_R1-Pad1_", super::spice_net("Net-(R1-Pad1)"));
+    }
+
+    #[test]
+    fn net_queries() {
+        use super::LabelKind;
+        let project = crate::Project::load(std::path::Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from_project(&project).unwrap();
+        let net = netlist.net("/amp/A").unwrap();
+        let mut pins = net.pins.iter().map(|p| format!("{}.{}", p.reference, p.pin)).collect::<Vec<String>>();
+        pins.sort();
+        assert_eq!(vec!["R1.2", "R2.1"], pins);
+        assert!(net.labels.iter().any(|l| l.kind == LabelKind::SheetPin && l.text == "A" && l.sheet == "/"));
+        assert!(net.labels.iter().any(|l| l.kind == LabelKind::Hierarchical && l.sheet == "/amp/"));
+        assert!(net.positions.contains(&(String::from("/"), Pt { x: 50.8, y: 54.61 })));
+
+        let r2 = net.pins.iter().find(|p| p.reference == "R2").unwrap();
+        assert_eq!("/amp/", r2.sheet);
+        assert_eq!(crate::symbols::ElectricalTypes::Passive, r2.electrical_type);
+        assert!(std::ptr::eq(net, netlist.pin_net("R2", "1").unwrap()));
+        assert_eq!("/VIN", netlist.pin_net("R1", "1").unwrap().name);
+        assert!(netlist.pin_net("R1", "9").is_none());
+        assert!(netlist.net("NONE").is_none());
+
+        assert_eq!(LabelKind::Local, netlist.net("/GND").unwrap().labels[0].kind);
+        assert_eq!(netlist.nets().len(), netlist.nets.len());
+
+        let schema = crate::Schema::load(std::path::Path::new("tests/spice/spice.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from(&schema).unwrap();
+        let gnd = netlist.net("GND").unwrap();
+        assert!(gnd.labels.iter().any(|l| l.kind == LabelKind::Power));
+        assert!(!gnd.pins.is_empty());
+        assert!(gnd.pins.iter().all(|p| !p.reference.starts_with('#')));
+    }
+
+    #[test]
+    fn sheet_instances() {
+        use crate::circuit::CircuitItem;
+        let project = crate::Project::load(std::path::Path::new("tests/hierarchy/twice.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from_project(&project).unwrap();
+        //the same sheet file has the reference R2 in amp1 and R4 in amp2
+        assert_eq!("/amp1/A", netlist.pin_net("R2", "1").unwrap().name);
+        assert_eq!("/amp1/B", netlist.pin_net("R4", "1").unwrap().name);
+        assert_eq!("/amp2/B", netlist.pin_net("R4", "2").unwrap().name);
+        let mut pins = netlist
+            .nets()
+            .iter()
+            .flat_map(|n| n.pins.iter().map(|p| format!("{}.{}", p.reference, p.pin)))
+            .collect::<Vec<String>>();
+        pins.sort();
         assert_eq!(
-            &vec![
-                Pt {
-                    x: 179.07,
-                    y: 34.29,
-                },
-                Pt {
-                    x: 180.34,
-                    y: 49.53,
-                },
-                Pt {
-                    x: 167.64,
-                    y: 49.53,
-                },
-            ],
-            wire
+            vec!["R1.1", "R1.2", "R2.1", "R2.2", "R3.1", "R3.2", "R4.1", "R4.2"],
+            pins
         );
+
+        let mut circuit = crate::Circuit::new(String::from("twice"), vec![]);
+        netlist.circuit(&mut circuit).unwrap();
+        let mut references = circuit
+            .items
+            .iter()
+            .filter_map(|i| match i {
+                CircuitItem::R(r, ..) => Some(r.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<&str>>();
+        references.sort();
+        assert_eq!(vec!["R1", "R2", "R3", "R4"], references);
     }
 
     #[test]
-    fn test_get_wires() {
-        let schema = crate::Schema::load(std::path::Path::new(SCHEMA_SUMME)).unwrap();
-        let wires = super::Netlist::wires(&schema);
-        let mut visited = vec![];
-        let wire = super::Netlist::get_wire(
-            Pt {
-                x: 179.07,
-                y: 49.53,
+    fn netclasses() {
+        let path = std::path::Path::new("tests/netclass/netclass.kicad_sch");
+        let netclass = |netlist: &super::Netlist, name: &str| netlist.net(name).unwrap().netclass.clone();
+        let project = crate::Project::load(path).unwrap();
+        let netlist = super::Netlist::from_project(&project).unwrap();
+        assert_eq!("Power", netclass(&netlist, "/VCC"));
+        assert_eq!("Analog", netclass(&netlist, "/SIG"));
+        assert_eq!("HighSpeed", netclass(&netlist, "/CLK"));
+        //the rule area wins over the assignment in the project file
+        assert_eq!("HighSpeed", netclass(&netlist, "/DATA"));
+
+        //without net settings only the flags are used
+        let schema = crate::Schema::load(path).unwrap();
+        let netlist = super::Netlist::from(&schema).unwrap();
+        assert_eq!("Default", netclass(&netlist, "/SIG"));
+        assert_eq!("Power", netclass(&netlist, "/VCC"));
+        let netlist = netlist.net_settings(&project.net_settings);
+        assert_eq!("Analog", netclass(&netlist, "/SIG"));
+        assert_eq!(Some(&String::from("Analog")), netlist.netclasses().get("/SIG"));
+    }
+
+    #[test]
+    fn netclass_areas() {
+        use crate::{
+            gr::{Polyline, Pos, Property, Pts},
+            schema::{NetclassFlag, RuleArea, SchemaItem},
+            sexp::constants::el,
+        };
+        let pt = |x: f32, y: f32| Pt { x, y };
+        let flag = |netclass: &str, pt: Pt| {
+            SchemaItem::NetclassFlag(NetclassFlag {
+                length: 2.54,
+                name: String::new(),
+                shape: None,
+                pos: Pos { x: pt.x, y: pt.y, angle: 0.0 },
+                fields_autoplaced: false,
+                effects: Default::default(),
+                props: vec![Property {
+                    key: el::PROPERTY_NETCLASS.to_string(),
+                    value: netclass.to_string(),
+                    ..Default::default()
+                }],
+                uuid: String::new(),
+            })
+        };
+        let wires = [(pt(0.0, 0.0), pt(50.8, 0.0)), (pt(0.0, 20.32), pt(10.16, 20.32))];
+        let mut schema = wired(&wires, &[("A", pt(0.0, 0.0)), ("B", pt(0.0, 20.32))]);
+        //the wire of A crosses the area, no item of A is inside
+        let area = [pt(20.32, -10.16), pt(30.48, -10.16), pt(30.48, 10.16), pt(20.32, 10.16)];
+        schema.items.push(SchemaItem::RuleArea(RuleArea {
+            polyline: Polyline {
+                pts: Pts(area.to_vec()),
+                ..Default::default()
             },
-            &wires,
-            &mut visited,
-        )
-        .unwrap();
+        }));
+        schema.items.push(flag("HighSpeed", pt(20.32, 5.08)));
+        schema.items.push(flag("Power", pt(10.16, 20.32)));
+        schema.items.push(flag("Analog", pt(5.08, 20.32)));
+
+        let netlist = super::Netlist::from(&schema).unwrap();
+        assert_eq!("HighSpeed", netlist.net("/A").unwrap().netclass);
+        assert_eq!("Power", netlist.net("/B").unwrap().netclass);
         assert_eq!(
-            vec![
-                Pt {
-                    x: 179.07,
-                    y: 34.29,
-                },
-                Pt {
-                    x: 180.34,
-                    y: 49.53,
-                },
-                Pt {
-                    x: 167.64,
-                    y: 49.53,
-                },
-            ],
-            wire
+            vec![super::NetclassConflict {
+                net: String::from("/B"),
+                sheet: String::from("/"),
+                pos: pt(10.16, 20.32),
+                classes: vec![String::from("Power"), String::from("Analog")],
+            }],
+            netlist.netclass_conflicts()
         );
+
+        let schema = crate::Schema::load(std::path::Path::new("tests/netclass/netclass.kicad_sch")).unwrap();
+        assert!(super::Netlist::from(&schema).unwrap().netclass_conflicts().is_empty());
     }
 
     #[test]
-    fn test_get_visited_wires() {
-        let schema = crate::Schema::load(std::path::Path::new("tests/summe/summe.kicad_sch")).unwrap();
-        let wires = super::Netlist::wires(&schema);
-        let mut visited = vec![Pt {
-            x: 180.34,
-            y: 49.53,
-        }];
-        let wire = super::Netlist::get_wire(
-            Pt {
-                x: 179.07,
-                y: 49.53,
-            },
-            &wires,
-            &mut visited,
-        )
-        .unwrap();
+    fn subckt() {
+        let project = crate::Project::load(std::path::Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from_project(&project).unwrap();
+        let (ports, amp) = netlist.subckt("/amp/", &["B", "A"]).unwrap();
+        assert_eq!(vec![String::from("B"), String::from("A")], ports);
+
+        let mut circuit = crate::Circuit::new(String::from("top"), vec![]);
+        circuit.subcircuit(String::from("amp"), ports, amp).unwrap();
+        circuit.circuit(String::from("U1"), vec![String::from("out"), String::from("in")], String::from("amp")).unwrap();
+        let netlist = circuit.to_str(true).unwrap();
         assert_eq!(
-            vec![
-                Pt {
-                    x: 179.07,
-                    y: 34.29,
-                },
-                Pt {
-                    x: 167.64,
-                    y: 49.53,
-                },
-            ],
-            wire
+            vec![".subckt amp B A", "R2 A B 4.7k", ".ends", "XU1 out in amp"],
+            netlist[1..5]
         );
+
+        assert_eq!("port C not found in sheet /amp/", netlist_error(&project, "/amp/", &["A", "C"]));
+        assert_eq!("ports A and A are connected", netlist_error(&project, "/amp/", &["A", "A"]));
+        assert_eq!("sheet /missing/ not found", netlist_error(&project, "/missing/", &["A"]));
+    }
+
+    fn netlist_error(project: &crate::Project, path: &str, ports: &[&str]) -> String {
+        super::Netlist::from_project(project)
+            .unwrap()
+            .subckt(path, ports)
+            .unwrap_err()
+            .1
     }
 
     #[test]
-    fn test_seek_wires() {
-        let schema = crate::Schema::load(std::path::Path::new(SCHEMA_SUMME)).unwrap();
-        let wires = super::Netlist::wires(&schema);
-        let mut visited = vec![];
-        let wire = super::Netlist::seek_wire(
-            Pt {
-                x: 179.07,
-                y: 49.53,
-            },
-            &wires,
-            &mut visited,
+    fn bus_nets() {
+        let schema = crate::Schema::load(std::path::Path::new("tests/bus/bus.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from(&schema).unwrap();
+        //the bus member is named by the label on the wire
+        assert_eq!(String::from("/D0"), netlist.netname(Pt { x: 73.66, y: 60.96 }).unwrap());
+        assert_eq!(String::from("/D1"), netlist.netname(Pt { x: 88.9, y: 60.96 }).unwrap());
+        //the member of the global bus is connected to the global label
+        assert_eq!(String::from("I2C.SDA"), netlist.netname(Pt { x: 63.5, y: 86.36 }).unwrap());
+        assert_eq!(String::from("I2C.SDA"), netlist.netname(Pt { x: 114.3, y: 86.36 }).unwrap());
+        let net = |pt: Pt| netlist.nets.iter().position(|n| n.iter().any(|(_, p)| *p == pt)).unwrap();
+        assert_eq!(net(Pt { x: 63.5, y: 78.74 }), net(Pt { x: 114.3, y: 81.28 }));
+        //an unlabeled bus entry does not connect the wire
+        assert_ne!(net(Pt { x: 96.52, y: 78.74 }), net(Pt { x: 63.5, y: 78.74 }));
+        assert_ne!(String::from("I2C.SDA"), netlist.netname(Pt { x: 96.52, y: 86.36 }).unwrap());
+    }
+
+    #[test]
+    fn test_bus_members() {
+        assert_eq!(Some(vec![String::from("D0"), String::from("D1"), String::from("D2")]), super::bus_members("D[0..2]"));
+        assert_eq!(Some(vec![String::from("A1"), String::from("A0")]), super::bus_members("A[1..0]"));
+        assert_eq!(Some(vec![String::from("SDA"), String::from("SCL")]), super::bus_members("{SDA SCL}"));
+        assert_eq!(
+            Some(vec![String::from("BUS.CLK"), String::from("BUS.D0"), String::from("BUS.D1")]),
+            super::bus_members("BUS{CLK D[0..1]}")
         );
+        assert_eq!(None, super::bus_members("~{RESET}"));
+        assert_eq!(None, super::bus_members("VIN"));
+    }
+
+    #[test]
+    fn t_junctions() {
+        let schema = crate::Schema::load(std::path::Path::new("tests/tjunction/tjunction.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from(&schema).unwrap();
+        //the label in the middle of the wire names the net,
+        //the wire ends in the middle of the other wire
+        assert_eq!(String::from("/OUT"), netlist.netname(Pt { x: 63.5, y: 60.96 }).unwrap());
+        //pin in the middle of the wire
+        assert_eq!(String::from("/OUT"), netlist.netname(Pt { x: 76.2, y: 50.8 }).unwrap());
+        //crossing wires with a junction
+        assert_eq!(String::from("/OUT"), netlist.netname(Pt { x: 57.15, y: 60.96 }).unwrap());
+        //crossing wires without a junction are not connected
+        assert_ne!(String::from("/OUT"), netlist.netname(Pt { x: 95.25, y: 60.96 }).unwrap());
+    }
+
+    #[test]
+    fn circuit() {
+        use crate::circuit::CircuitItem;
+        let schema = crate::Schema::load(std::path::Path::new("tests/spice/spice.kicad_sch")).unwrap();
+        let netlist = super::Netlist::from(&schema).unwrap();
+        let mut circuit = crate::Circuit::new(String::from("spice"), vec![]);
+        netlist.circuit(&mut circuit).unwrap();
+        let s = |s: &str| s.to_string();
         assert_eq!(
             vec![
-                Pt {
-                    x: 179.07,
-                    y: 34.29
-                },
-                Pt {
-                    x: 185.42,
-                    y: 34.29
-                },
-                Pt {
-                    x: 179.07,
-                    y: 22.86
-                },
-                Pt {
-                    x: 185.42,
-                    y: 22.86
-                },
-                Pt {
-                    x: 180.34,
-                    y: 49.53
-                },
-                Pt {
-                    x: 167.64,
-                    y: 49.53
-                },
-                Pt {
-                    x: 166.37,
-                    y: 49.53
-                },
-                Pt {
-                    x: 167.64,
-                    y: 41.91
-                },
-                Pt {
-                    x: 167.64,
-                    y: 34.29
-                },
-                Pt {
-                    x: 167.64,
-                    y: 26.67
-                },
-                Pt {
-                    x: 166.37,
-                    y: 26.67
-                },
-                Pt {
-                    x: 166.37,
-                    y: 34.29
-                },
-                Pt {
-                    x: 166.37,
-                    y: 41.91
-                }
+                CircuitItem::V(s("V1"), s("/IN"), s("0"), s("SIN(0 1 1k)")),
+                CircuitItem::R(s("R1"), s("/IN"), s("/OUT"), s("10k")),
+                CircuitItem::R(s("R2"), s("0"), s("/OUT"), s("2k")),
+                CircuitItem::C(s("C1"), s("/OUT"), s("0"), s("10n")),
+                CircuitItem::D(s("D1"), s("0"), s("/OUT"), s("1N4148")),
             ],
-            wire
+            circuit.items
         );
     }
 
     #[test]
-    fn check_positions() {
-        let schema = crate::Schema::load(std::path::Path::new(SCHEMA_SUMME)).unwrap();
-        let netlist = super::Netlist::from(&schema).unwrap();
-        assert_eq!(String::from("R33_2__U7_6__C9_2__R36_1"), netlist.netname(crate::gr::Pt { x: 207.01, y: 52.07 }).unwrap());
-        assert_eq!(String::from("R7_2__R8_1__U4_3__RV3_2"), netlist.netname(crate::gr::Pt { x: 81.28, y: 102.87 }).unwrap());
-        assert_eq!(String::from("+15V"), netlist.netname(crate::gr::Pt { x: 153.67, y: 148.59 }).unwrap());
+    fn sim_params() {
+        let params = super::sim_params("type=\\\"X\\\" model=\\\"TL072 c\\\" lib=\\\"\\\"");
+        assert_eq!(Some(&String::from("X")), params.get("type"));
+        assert_eq!(Some(&String::from("TL072 c")), params.get("model"));
+        assert_eq!(Some(&String::new()), params.get("lib"));
+        let params = super::sim_params("dc=0 ampl=1 f=1k");
+        assert_eq!(vec!["0", "1", "1k"], params.values().collect::<Vec<&String>>());
+    }
+
+    #[test]
+    fn sim_pins() {
+        assert_eq!(vec!["2", "1"], super::sim_pins("1=K 2=A", "D"));
+        assert_eq!(vec!["2", "1", "3"], super::sim_pins("1=B 2=C 3=E", "Q"));
+        assert_eq!(vec!["3", "1", "2"], super::sim_pins("3=out 1=in 2=gnd", "X"));
+        assert_eq!(vec!["1", "2"], super::sim_pins("1=+ 2=-", "V"));
+    }
+
+    #[test]
+    fn source_value() {
+        let value = |kind: &str, params: &str| {
+            super::source_value(&Some(kind.to_string()), &super::sim_params(params), "")
+        };
+        assert_eq!("SIN(0 1 1k)", value("SIN", "ampl=1 dc=0 f=1k"));
+        assert_eq!("SIN(0 1 1k 0 0 90)", value("sin", "phase=90 f=1k ampl=1 dc=0"));
+        assert_eq!("PULSE(0 5 0 1n 1n 5u 10u)", value("PULSE", "per=10u y2=5 tw=5u y1=0 tr=1n tf=1n td=0"));
+        assert_eq!("AC 1 SIN(0 1 1k)", value("SIN", "dc=0 ampl=1 f=1k ac=1"));
+        assert_eq!("DC 5 AC 1", value("DC", "ac=1 dc=5"));
+        assert_eq!("DC 1 PULSE(0 5)", value("PULSE", "y2=5 dc=1 y1=0"));
     }
 }
@@ -0,0 +1,586 @@
+//! Write the netlist in the KiCad netlist formats.
+use std::{cmp::Ordering, io::Write};
+
+use super::{Net, Netlist};
+use crate::{
+    schema::{SchemaItem, Symbol},
+    sexp::{builder::Builder, constants::el},
+    symbols::{LibrarySymbol, Pin},
+    Error, Schema,
+};
+
+/// The version of the KiCad netlist format.
+const VERSION: &str = "E";
+
+/// A component of the netlist, the units of a symbol are joined.
+struct Component<'a> {
+    reference: String,
+    sheet: usize,
+    symbol: &'a Symbol,
+    lib: &'a LibrarySymbol,
+    /// The uuids of the symbol units.
+    uuids: Vec<String>,
+}
+
+impl Component<'_> {
+    /// Get a property of the symbol, or from the library symbol when the
+    /// symbol does not define it.
+    fn property(&self, key: &str) -> String {
+        self.symbol
+            .props
+            .iter()
+            .chain(self.lib.props.iter())
+            .find(|p| p.key == key)
+            .map(|p| p.value.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Compare references and pin numbers in natural order, `R2` is before `R10`.
+fn natural(a: &str, b: &str) -> Ordering {
+    let split = |s: &str| {
+        let index = s.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        (s[..index].to_string(), s[index..].parse::<u64>().ok())
+    };
+    let (prefix_a, number_a) = split(a);
+    let (prefix_b, number_b) = split(b);
+    prefix_a
+        .cmp(&prefix_b)
+        .then(number_a.cmp(&number_b))
+        .then_with(|| a.cmp(b))
+}
+
+/// Split the library id into the library and the part name.
+fn lib_part(lib_id: &str) -> (&str, &str) {
+    lib_id.split_once(':').unwrap_or(("", lib_id))
+}
+
+/// An element of the netlist document.
+///
+/// The document is written as XML or as s-expression, the attributes are written
+/// as `(name "value")` lists and the text follows the attributes.
+struct Element {
+    name: &'static str,
+    attrs: Vec<(&'static str, String)>,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            attrs: Vec::new(),
+            text: String::new(),
+            children: Vec::new(),
+        }
+    }
+
+    fn attr(mut self, key: &'static str, value: &str) -> Self {
+        self.attrs.push((key, value.to_string()));
+        self
+    }
+
+    fn text(mut self, text: &str) -> Self {
+        self.text = text.to_string();
+        self
+    }
+
+    fn child(mut self, child: Element) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    fn children(mut self, children: impl IntoIterator<Item = Element>) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    fn write_xml(&self, writer: &mut dyn Write, indent: usize) -> Result<(), Error> {
+        let indent_str = "  ".repeat(indent);
+        let attrs = self
+            .attrs
+            .iter()
+            .map(|(key, value)| format!(" {}=\"{}\"", key, escape_xml(value)))
+            .collect::<String>();
+        if !self.children.is_empty() {
+            writeln!(writer, "{}<{}{}>", indent_str, self.name, attrs)?;
+            for child in &self.children {
+                child.write_xml(writer, indent + 1)?;
+            }
+            writeln!(writer, "{}</{}>", indent_str, self.name)?;
+        } else if self.text.is_empty() {
+            writeln!(writer, "{}<{}{}/>", indent_str, self.name, attrs)?;
+        } else {
+            writeln!(
+                writer,
+                "{}<{}{}>{}</{}>",
+                indent_str,
+                self.name,
+                attrs,
+                escape_xml(&self.text),
+                self.name
+            )?;
+        }
+        Ok(())
+    }
+
+    fn write_sexp(&self, builder: &mut Builder) {
+        builder.push(self.name);
+        for (key, value) in &self.attrs {
+            builder.push(key);
+            builder.text(&escape_sexp(value));
+            builder.end();
+        }
+        if !self.text.is_empty() {
+            builder.text(&escape_sexp(&self.text));
+        }
+        for child in &self.children {
+            child.write_sexp(builder);
+        }
+        builder.end();
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn escape_sexp(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl<'a> Netlist<'a> {
+    /// The components of all sheets ordered by the reference,
+    /// power symbols are skipped.
+    fn components(&self) -> Vec<Component<'a>> {
+        let mut components: Vec<Component<'a>> = Vec::new();
+        for (index, sheet) in self.sheets.iter().enumerate() {
+            let schema: &'a Schema = sheet.schema;
+            for item in &schema.items {
+                let SchemaItem::Symbol(symbol) = item else {
+                    continue;
+                };
+                let Some(lib) = schema.library_symbol(&symbol.lib_id) else {
+                    continue;
+                };
+                let reference = sheet.reference(symbol);
+                if lib.power || reference.starts_with('#') {
+                    continue;
+                }
+                match components
+                    .iter_mut()
+                    .find(|c| c.sheet == index && c.reference == reference)
+                {
+                    Some(component) => component.uuids.push(symbol.uuid.clone()),
+                    None => components.push(Component {
+                        reference: reference.to_string(),
+                        sheet: index,
+                        symbol,
+                        lib,
+                        uuids: vec![symbol.uuid.clone()],
+                    }),
+                }
+            }
+        }
+        components.sort_by(|a, b| natural(&a.reference, &b.reference));
+        components
+    }
+
+    /// The nets with the connected pins ordered by the net name,
+    /// nets without pins are skipped.
+    fn pin_nets(&self) -> Vec<Net> {
+        let mut nets = self
+            .nets()
+            .iter()
+            .filter(|net| !net.pins.is_empty())
+            .cloned()
+            .collect::<Vec<Net>>();
+        for net in &mut nets {
+            net.pins.sort_by(|a, b| {
+                natural(&a.reference, &b.reference).then_with(|| natural(&a.pin, &b.pin))
+            });
+            //the shared pins of the units are connected more than once.
+            net.pins.dedup_by(|a, b| a.reference == b.reference && a.pin == b.pin);
+        }
+        nets.sort_by(|a, b| a.name.cmp(&b.name));
+        nets
+    }
+
+    /// The uuid path of a sheet as used by KiCad, `/` for the root sheet.
+    fn tstamps(&self, sheet: usize) -> String {
+        let mut uuids = Vec::new();
+        let mut current = Some(sheet);
+        while let Some(index) = current {
+            if let Some(uuid) = &self.sheets[index].uuid {
+                uuids.insert(0, uuid.as_str());
+            }
+            current = self.sheets[index].parent;
+        }
+        uuids.iter().map(|uuid| format!("/{}", uuid)).collect::<String>() + "/"
+    }
+
+    /// The sheet name from the human readable path, `Root` for the root sheet.
+    fn sheet_name(&self, sheet: usize) -> String {
+        let path = self.sheets[sheet].path.trim_end_matches('/');
+        match path.rsplit_once('/') {
+            Some((_, name)) if !name.is_empty() => name.to_string(),
+            _ => String::from("Root"),
+        }
+    }
+
+    /// The file name of a sheet.
+    fn sheet_file(&self, sheet: usize) -> String {
+        self.sheets[sheet]
+            .filename
+            .as_ref()
+            .and_then(|f| f.file_name())
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Create the netlist document.
+    fn document(&self) -> Element {
+        let source = self
+            .sheets
+            .first()
+            .and_then(|s| s.filename.as_ref())
+            .map(|f| f.display().to_string())
+            .unwrap_or_default();
+        let mut design = Element::new("design")
+            .child(Element::new("source").text(&source))
+            .child(Element::new("tool").text(&format!("recad {}", env!("CARGO_PKG_VERSION"))));
+        for (index, sheet) in self.sheets.iter().enumerate() {
+            let title_block = &sheet.schema.title_block;
+            let text = |value: &Option<String>| value.clone().unwrap_or_default();
+            let comments = (1..=9u8).map(|number| {
+                let value = title_block
+                    .comment
+                    .iter()
+                    .find(|(n, _)| *n == number)
+                    .map(|(_, c)| c.as_str())
+                    .unwrap_or_default();
+                Element::new("comment")
+                    .attr("number", &number.to_string())
+                    .attr("value", value)
+            });
+            design = design.child(
+                Element::new("sheet")
+                    .attr("number", &(index + 1).to_string())
+                    .attr("name", &sheet.path)
+                    .attr("tstamps", &self.tstamps(index))
+                    .child(
+                        Element::new("title_block")
+                            .child(Element::new("title").text(&text(&title_block.title)))
+                            .child(Element::new("company").text(&text(&title_block.company_name)))
+                            .child(Element::new("rev").text(&text(&title_block.revision)))
+                            .child(Element::new("date").text(&text(&title_block.date)))
+                            .child(Element::new("source").text(&self.sheet_file(index)))
+                            .children(comments),
+                    ),
+            );
+        }
+
+        let components = self.components();
+        let mut comps = Element::new("components");
+        for component in &components {
+            let mut comp = Element::new("comp")
+                .attr("ref", &component.reference)
+                .child(Element::new("value").text(&component.property(el::PROPERTY_VALUE)));
+            for (name, key) in [
+                ("footprint", el::PROPERTY_FOOTPRINT),
+                ("datasheet", el::PROPERTY_DATASHEET),
+                ("description", el::PROPERTY_DESCRIPTION),
+            ] {
+                let value = component.property(key);
+                if !value.is_empty() {
+                    comp = comp.child(Element::new(name).text(&value));
+                }
+            }
+            let fields = component
+                .symbol
+                .props
+                .iter()
+                .filter(|p| {
+                    p.key != el::PROPERTY_REFERENCE
+                        && p.key != el::PROPERTY_VALUE
+                        && !p.key.starts_with("ki_")
+                })
+                .map(|p| Element::new("field").attr("name", &p.key).text(&p.value));
+            let (lib, part) = lib_part(&component.symbol.lib_id);
+            let property = |name: &str, value: &str| {
+                Element::new("property").attr("name", name).attr("value", value)
+            };
+            comp = comp
+                .child(Element::new("fields").children(fields))
+                .child(
+                    Element::new("libsource")
+                        .attr("lib", lib)
+                        .attr("part", part)
+                        .attr("description", &component.property(el::PROPERTY_DESCRIPTION)),
+                )
+                .child(property("Sheetname", &self.sheet_name(component.sheet)))
+                .child(property("Sheetfile", &self.sheet_file(component.sheet)))
+                .children(
+                    component
+                        .lib
+                        .props
+                        .iter()
+                        .filter(|p| p.key.starts_with("ki_"))
+                        .map(|p| property(&p.key, &p.value)),
+                );
+            if !component.symbol.in_bom {
+                comp = comp.child(Element::new("property").attr("name", "exclude_from_bom"));
+            }
+            if component.symbol.dnp {
+                comp = comp.child(Element::new("property").attr("name", "dnp"));
+            }
+            comps = comps.child(
+                comp.child(
+                    Element::new("sheetpath")
+                        .attr("names", &self.sheets[component.sheet].path)
+                        .attr("tstamps", &self.tstamps(component.sheet)),
+                )
+                .child(Element::new("tstamps").text(&component.uuids.join(" "))),
+            );
+        }
+
+        let mut libs: Vec<&LibrarySymbol> = Vec::new();
+        for component in &components {
+            if !libs.iter().any(|l| l.lib_id == component.lib.lib_id) {
+                libs.push(component.lib);
+            }
+        }
+        libs.sort_by(|a, b| a.lib_id.cmp(&b.lib_id));
+        let mut libparts = Element::new("libparts");
+        for lib in libs {
+            let (name, part) = lib_part(&lib.lib_id);
+            let property = |key: &str| {
+                lib.props
+                    .iter()
+                    .find(|p| p.key == key)
+                    .map(|p| p.value.clone())
+                    .unwrap_or_default()
+            };
+            let mut libpart = Element::new("libpart")
+                .attr("lib", name)
+                .attr("part", part)
+                .child(Element::new("description").text(&property(el::PROPERTY_DESCRIPTION)))
+                .child(Element::new("docs").text(&property(el::PROPERTY_DATASHEET)));
+            let filters = property(el::PROPERTY_FP_FILTERS);
+            if !filters.trim().is_empty() {
+                libpart = libpart.child(
+                    Element::new("footprints").children(
+                        filters
+                            .split_whitespace()
+                            .map(|filter| Element::new("fp").text(filter)),
+                    ),
+                );
+            }
+            let fields = lib
+                .props
+                .iter()
+                .filter(|p| !p.key.starts_with("ki_"))
+                .map(|p| Element::new("field").attr("name", &p.key).text(&p.value));
+            let mut pins: Vec<&Pin> = Vec::new();
+            for pin in lib.units.iter().flat_map(|unit| unit.pins.iter()) {
+                if !pins.iter().any(|p| p.number.name == pin.number.name) {
+                    pins.push(pin);
+                }
+            }
+            pins.sort_by(|a, b| natural(&a.number.name, &b.number.name));
+            let pins = pins.iter().map(|pin| {
+                Element::new("pin")
+                    .attr("num", &pin.number.name)
+                    .attr("name", if pin.name.name.is_empty() { "~" } else { &pin.name.name })
+                    .attr("type", &pin.electrical_type.to_string())
+            });
+            libparts = libparts.child(
+                libpart
+                    .child(Element::new("fields").children(fields))
+                    .child(Element::new("pins").children(pins)),
+            );
+        }
+
+        let mut nets = Element::new("nets");
+        for (index, net) in self.pin_nets().iter().enumerate() {
+            let nodes = net.pins.iter().map(|pin| {
+                let element = Element::new("node")
+                    .attr("ref", &pin.reference)
+                    .attr("pin", &pin.pin);
+                if pin.name.is_empty() || pin.name == "~" {
+                    element
+                } else {
+                    element.attr("pinfunction", &pin.name)
+                }
+                .attr("pintype", &pin.electrical_type.to_string())
+            });
+            nets = nets.child(
+                Element::new("net")
+                    .attr("code", &(index + 1).to_string())
+                    .attr("name", &net.name)
+                    .attr("class", &net.netclass)
+                    .children(nodes),
+            );
+        }
+
+        Element::new("export")
+            .attr("version", VERSION)
+            .child(design)
+            .child(comps)
+            .child(libparts)
+            .child(nets)
+    }
+
+    pub(super) fn write_xml(&self, writer: &mut dyn Write) -> Result<(), Error> {
+        writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+        self.document().write_xml(writer, 0)
+    }
+
+    pub(super) fn write_sexp(&self, writer: &mut dyn Write) -> Result<(), Error> {
+        let mut builder = Builder::new();
+        self.document().write_sexp(&mut builder);
+        builder.sexp()?.write(writer)?;
+        writer.write_all("\n".as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{escape_sexp, natural};
+    use crate::{
+        netlist::{Netlist, NetlistFormat},
+        sexp::{parser::SexpParser, SexpTree, SexpValue},
+        Project,
+    };
+
+    fn export(format: NetlistFormat) -> String {
+        export_project("tests/hierarchy/hierarchy.kicad_sch", format)
+    }
+
+    fn export_project(path: &str, format: NetlistFormat) -> String {
+        let project = Project::load(Path::new(path)).unwrap();
+        let netlist = Netlist::from_project(&project).unwrap();
+        let mut output = Vec::new();
+        netlist.export(&mut output, format).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn sheet_instances() {
+        let output = export_project("tests/hierarchy/twice.kicad_sch", NetlistFormat::KicadXml);
+        let comps = output
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| l.starts_with("<comp "))
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            vec!["<comp ref=\"R1\">", "<comp ref=\"R2\">", "<comp ref=\"R3\">", "<comp ref=\"R4\">"],
+            comps
+        );
+    }
+
+    #[test]
+    fn natural_order() {
+        let mut references = vec!["R10", "C1", "R2", "R1", "U1A"];
+        references.sort_by(|a, b| natural(a, b));
+        assert_eq!(vec!["C1", "R1", "R2", "R10", "U1A"], references);
+    }
+
+    #[test]
+    fn xml() {
+        let output = export(NetlistFormat::KicadXml);
+        let lines = output.lines().map(|l| l.trim()).collect::<Vec<&str>>();
+
+        assert_eq!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>", lines[0]);
+        assert_eq!("<export version=\"E\">", lines[1]);
+        assert_eq!("</export>", lines[lines.len() - 1]);
+        assert!(lines.contains(&"<source>tests/hierarchy/hierarchy.kicad_sch</source>"));
+        assert!(lines.contains(&"<sheet number=\"2\" name=\"/amp/\" tstamps=\"/1d2e3f40-5a6b-4c7d-8e9f-0a1b2c3d4e5f/\">"));
+
+        let comps = lines
+            .iter()
+            .filter(|l| l.starts_with("<comp "))
+            .copied()
+            .collect::<Vec<&str>>();
+        assert_eq!(
+            vec!["<comp ref=\"R1\">", "<comp ref=\"R2\">", "<comp ref=\"R3\">"],
+            comps
+        );
+        assert!(lines.contains(&"<value>4k7</value>"));
+        assert!(lines.contains(&"<libsource lib=\"Device\" part=\"R\" description=\"Resistor\"/>"));
+        assert!(lines.contains(&"<property name=\"Sheetname\" value=\"amp\"/>"));
+        assert!(lines.contains(&"<property name=\"Sheetfile\" value=\"amp.kicad_sch\"/>"));
+        assert!(lines.contains(&"<property name=\"ki_keywords\" value=\"R res resistor\"/>"));
+
+        assert_eq!(1, lines.iter().filter(|l| l.starts_with("<libpart ")).count());
+        assert!(lines.contains(&"<pin num=\"1\" name=\"~\" type=\"passive\"/>"));
+        assert!(lines.contains(&"<fp>R_*</fp>"));
+
+        let start = lines.iter().position(|l| l.contains("name=\"/amp/A\"")).unwrap();
+        assert_eq!(
+            vec![
+                "<node ref=\"R1\" pin=\"2\" pintype=\"passive\"/>",
+                "<node ref=\"R2\" pin=\"1\" pintype=\"passive\"/>",
+                "</net>"
+            ],
+            lines[start + 1..start + 4]
+        );
+    }
+
+    #[test]
+    fn sexp() {
+        let output = export(NetlistFormat::KicadSexpr);
+        let tree = SexpTree::from(SexpParser::from(output).iter()).unwrap();
+        let root = tree.root().unwrap();
+        assert_eq!("export", root.name);
+        let text = |node: &crate::sexp::Sexp, name: &str| -> String {
+            node.query(name).next().unwrap().get(0).unwrap()
+        };
+        assert_eq!("E", text(root, "version"));
+
+        let components = root.query("components").next().unwrap();
+        let comps = components.query("comp").collect::<Vec<_>>();
+        assert_eq!(
+            vec!["R1", "R2", "R3"],
+            comps.iter().map(|c| text(c, "ref")).collect::<Vec<String>>()
+        );
+        assert_eq!("4k7", text(comps[1], "value"));
+        assert_eq!("5eed0000-0000-4000-8000-000000000016", text(comps[1], "tstamps"));
+        let sheetpath = comps[1].query("sheetpath").next().unwrap();
+        assert_eq!("/amp/", text(sheetpath, "names"));
+        assert_eq!("/1d2e3f40-5a6b-4c7d-8e9f-0a1b2c3d4e5f/", text(sheetpath, "tstamps"));
+
+        let nets = root.query("nets").next().unwrap().query("net").collect::<Vec<_>>();
+        assert_eq!(4, nets.len());
+        assert_eq!("1", text(nets[0], "code"));
+        assert_eq!("/GND", text(nets[0], "name"));
+        assert_eq!("/amp/A", text(nets[2], "name"));
+        let nodes = nets[2]
+            .query("node")
+            .map(|n| format!("{}.{}", text(n, "ref"), text(n, "pin")))
+            .collect::<Vec<String>>();
+        assert_eq!(vec!["R1.2", "R2.1"], nodes);
+    }
+
+    #[test]
+    fn netclass() {
+        let output = export_project("tests/netclass/netclass.kicad_sch", NetlistFormat::KicadXml);
+        assert!(output.contains("<net code=\"1\" name=\"/CLK\" class=\"HighSpeed\">"));
+        assert!(output.contains("name=\"/VCC\" class=\"Power\">"));
+        let output = export_project("tests/netclass/netclass.kicad_sch", NetlistFormat::KicadSexpr);
+        assert!(output.contains("(class \"Analog\")"));
+    }
+
+    #[test]
+    fn escape() {
+        assert_eq!("10\\\" \\\\", escape_sexp("10\" \\"));
+    }
+}
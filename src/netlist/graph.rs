@@ -0,0 +1,178 @@
+//! Connectivity structures of the netlister.
+//!
+//! [`UnionFind`] joins the connected points and groups, [`SpatialHash`] finds
+//! the wire segments at a point without testing every wire of the schema.
+use std::{
+    collections::HashMap,
+    hash::{BuildHasherDefault, Hasher},
+};
+
+use crate::{gr::Pt, math::on_segment};
+
+/// The size of a hash cell in mm, four times the default grid of 1.27 mm.
+const CELL: f32 = 5.08;
+
+/// The tolerance of [`on_segment`], segments are added to all cells
+/// they touch within this distance.
+const EPSILON: f32 = 0.01;
+
+/// A multiplicative hasher for the integer keys of the grid cells and points,
+/// the default hasher is slow for the many small keys of the netlister.
+#[derive(Default)]
+pub(super) struct FastHasher(u64);
+
+impl Hasher for FastHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.write_u64(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        for byte in chunks.remainder() {
+            self.write_u64(*byte as u64);
+        }
+    }
+
+    fn write_u32(&mut self, value: u32) {
+        self.write_u64(value as u64);
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = (self.0.rotate_left(5) ^ value).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+
+    fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The hasher for the maps and sets of the netlister.
+pub(super) type FastHash = BuildHasherDefault<FastHasher>;
+
+/// Disjoint sets of indices with path halving and union by size.
+pub(super) struct UnionFind {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl UnionFind {
+    pub(super) fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    /// Get the representative of the set which contains the index.
+    pub(super) fn find(&mut self, mut index: usize) -> usize {
+        while self.parent[index] != index {
+            self.parent[index] = self.parent[self.parent[index]];
+            index = self.parent[index];
+        }
+        index
+    }
+
+    /// Join the sets of both indices.
+    pub(super) fn union(&mut self, a: usize, b: usize) {
+        let (mut a, mut b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        if self.size[a] < self.size[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        self.parent[b] = a;
+        self.size[a] += self.size[b];
+    }
+
+    /// Get all sets, the sets and the indices are ordered by the first index.
+    pub(super) fn sets(&mut self) -> Vec<Vec<usize>> {
+        let mut sets: Vec<Vec<usize>> = Vec::new();
+        let mut positions: HashMap<usize, usize> = HashMap::new();
+        for index in 0..self.parent.len() {
+            let root = self.find(index);
+            let position = *positions.entry(root).or_insert_with(|| {
+                sets.push(Vec::new());
+                sets.len() - 1
+            });
+            sets[position].push(index);
+        }
+        sets
+    }
+}
+
+/// The cell of a coordinate.
+fn cell(value: f32) -> i32 {
+    (value / CELL).floor() as i32
+}
+
+/// Line segments hashed by the grid cells they cross.
+pub(super) struct SpatialHash {
+    segments: Vec<(Pt, Pt)>,
+    cells: HashMap<(i32, i32), Vec<usize>, FastHash>,
+}
+
+impl SpatialHash {
+    pub(super) fn new(segments: Vec<(Pt, Pt)>) -> Self {
+        let mut cells: HashMap<(i32, i32), Vec<usize>, FastHash> = HashMap::default();
+        for (index, (start, end)) in segments.iter().enumerate() {
+            for x in cell(start.x.min(end.x) - EPSILON)..=cell(start.x.max(end.x) + EPSILON) {
+                for y in cell(start.y.min(end.y) - EPSILON)..=cell(start.y.max(end.y) + EPSILON) {
+                    cells.entry((x, y)).or_default().push(index);
+                }
+            }
+        }
+        Self { segments, cells }
+    }
+
+    pub(super) fn segments(&self) -> &[(Pt, Pt)] {
+        &self.segments
+    }
+
+    /// Get the indices of the segments which contain the point.
+    pub(super) fn query(&self, pt: Pt) -> impl Iterator<Item = usize> + '_ {
+        self.cells
+            .get(&(cell(pt.x), cell(pt.y)))
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(move |index| {
+                let (start, end) = self.segments[*index];
+                on_segment(pt, start, end)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpatialHash, UnionFind};
+    use crate::gr::Pt;
+
+    #[test]
+    fn union_find() {
+        let mut sets = UnionFind::new(6);
+        sets.union(4, 1);
+        sets.union(2, 5);
+        sets.union(1, 5);
+        assert_eq!(sets.find(4), sets.find(2));
+        assert_ne!(sets.find(0), sets.find(1));
+        assert_eq!(vec![vec![0], vec![1, 2, 4, 5], vec![3]], sets.sets());
+    }
+
+    #[test]
+    fn spatial_hash() {
+        let pt = |x: f32, y: f32| Pt { x, y };
+        let hash = SpatialHash::new(vec![
+            (pt(0.0, 0.0), pt(100.0, 0.0)),
+            (pt(50.8, -10.16), pt(50.8, 10.16)),
+            (pt(0.0, 0.0), pt(10.16, 10.16)),
+        ]);
+        assert_eq!(vec![0, 1], hash.query(pt(50.8, 0.0)).collect::<Vec<usize>>());
+        assert_eq!(vec![0, 2], hash.query(pt(0.0, 0.0)).collect::<Vec<usize>>());
+        assert_eq!(vec![2], hash.query(pt(5.08, 5.08)).collect::<Vec<usize>>());
+        assert_eq!(0, hash.query(pt(50.8, 20.32)).count());
+    }
+}
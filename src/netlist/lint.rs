@@ -0,0 +1,244 @@
+//! Find connectivity problems in the schema.
+use std::collections::HashSet;
+
+use super::{Finding, Lint, Netlist, NodePositions, SheetNodes};
+use crate::{gr::Pt, symbols::ElectricalTypes};
+
+/// Create the finding for a sheet.
+fn finding(sheet: &SheetNodes, lint: Lint, message: String, pos: Pt) -> Finding {
+    Finding {
+        lint,
+        message,
+        sheet: sheet.path.clone(),
+        pos,
+    }
+}
+
+/// The name of a pin for the messages, `R1:2`.
+fn pin_name(sheet: &SheetNodes, node: &NodePositions) -> String {
+    match node {
+        NodePositions::Pin(_, pin, symbol) => {
+            format!("{}:{}", sheet.reference(symbol), pin.number.name)
+        }
+        _ => String::new(),
+    }
+}
+
+/// The nodes which connect to other nodes, junctions,
+/// no connect and netclass flags are not counted.
+fn connectable(node: &NodePositions) -> bool {
+    !matches!(
+        node,
+        NodePositions::NoConnect(_) | NodePositions::Junction(_) | NodePositions::NetclassFlag(..)
+    )
+}
+
+impl Netlist<'_> {
+    /// Find the connectivity problems of all sheets.
+    ///
+    /// - wire ends which touch nothing.
+    /// - pins without connection and without a no connect flag.
+    /// - no connect flags on connected pins.
+    /// - labels which touch no wire or pin.
+    /// - nets with labels but only a single pin.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use recad_core::{netlist::Netlist, Schema};
+    ///
+    /// let schema = Schema::load(Path::new("tests/summe/summe.kicad_sch")).unwrap();
+    /// let netlist = Netlist::from(&schema).unwrap();
+    /// for finding in netlist.lint() {
+    ///     println!("{}", finding);
+    /// }
+    /// ```
+    pub fn lint(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for sheet in &self.sheets {
+            findings.append(&mut Netlist::dangling_wires(sheet));
+            findings.append(&mut Netlist::unconnected(sheet));
+            findings.append(&mut Netlist::dangling_labels(sheet));
+        }
+        findings.append(&mut self.single_nodes());
+        findings
+    }
+
+    /// Find the wire ends without a connection, the positions
+    /// with a single wire and without a node.
+    fn dangling_wires(sheet: &SheetNodes) -> Vec<Finding> {
+        let items = sheet
+            .node_positions
+            .values()
+            .flatten()
+            .map(|node| node.pos())
+            .collect::<HashSet<Pt>>();
+        sheet
+            .wires
+            .iter()
+            .filter(|(pt, count)| **count == 1 && !items.contains(pt))
+            .map(|(pt, _)| {
+                finding(sheet, Lint::DanglingWire, String::from("Wire end is not connected"), *pt)
+            })
+            .collect()
+    }
+
+    /// Find the unconnected pins and the no connect flags on connected pins.
+    fn unconnected(sheet: &SheetNodes) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for nodes in sheet.node_positions.values() {
+            let no_connect = nodes.iter().any(|n| matches!(n, NodePositions::NoConnect(_)));
+            let count = nodes.iter().filter(|n| connectable(n)).count();
+            for node in nodes {
+                match node {
+                    NodePositions::Pin(pos, pin, _)
+                        if count == 1
+                            && !no_connect
+                            && pin.electrical_type != ElectricalTypes::NoConnect =>
+                    {
+                        findings.push(finding(
+                            sheet,
+                            Lint::UnconnectedPin,
+                            format!("Pin {} is not connected", pin_name(sheet, node)),
+                            *pos,
+                        ));
+                    }
+                    NodePositions::NoConnect(pos) if count > 1 => {
+                        let pins = nodes
+                            .iter()
+                            .filter(|n| matches!(n, NodePositions::Pin(..)))
+                            .map(|n| pin_name(sheet, n))
+                            .collect::<Vec<String>>();
+                        findings.push(finding(
+                            sheet,
+                            Lint::ConnectedNoConnect,
+                            format!("No connect flag is connected to {}", pins.join(", ")),
+                            *pos,
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        findings
+    }
+
+    /// Find the labels which touch no wire or pin.
+    fn dangling_labels(sheet: &SheetNodes) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for nodes in sheet.node_positions.values() {
+            for node in nodes {
+                let text = match node {
+                    NodePositions::Label(_, l) => &l.text,
+                    NodePositions::GlobalLabel(_, l) => &l.text,
+                    NodePositions::HierarchicalLabel(_, l) => &l.text,
+                    _ => continue,
+                };
+                let pos = node.pos();
+                let touches = sheet.wires.contains_key(&pos)
+                    || nodes.iter().any(|other| {
+                        !std::ptr::eq(other, node) && connectable(other) && other.pos() == pos
+                    });
+                if !touches {
+                    findings.push(finding(
+                        sheet,
+                        Lint::DanglingLabel,
+                        format!("Label {} is not connected", text),
+                        pos,
+                    ));
+                }
+            }
+        }
+        findings
+    }
+
+    /// Find the nets with labels or sheet pins but only a single pin.
+    fn single_nodes(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        for groups in &self.nets {
+            let nodes = groups
+                .iter()
+                .flat_map(|(sheet, pt)| {
+                    self.sheets[*sheet].node_positions[pt].iter().map(move |n| (*sheet, n))
+                })
+                .collect::<Vec<(usize, &NodePositions)>>();
+            let pins = nodes
+                .iter()
+                .filter(|(_, n)| matches!(n, NodePositions::Pin(..)))
+                .collect::<Vec<&(usize, &NodePositions)>>();
+            let named = nodes.iter().any(|(_, n)| {
+                matches!(
+                    n,
+                    NodePositions::Label(..)
+                        | NodePositions::GlobalLabel(..)
+                        | NodePositions::HierarchicalLabel(..)
+                        | NodePositions::SheetPin(..)
+                )
+            });
+            let no_connect = nodes.iter().any(|(_, n)| matches!(n, NodePositions::NoConnect(_)));
+            if let [(sheet, pin)] = pins.as_slice() {
+                if named && !no_connect {
+                    let (sheet_index, pt) = groups[0];
+                    let name = self.net_name(&self.sheets[sheet_index], &pt).cloned().unwrap_or_default();
+                    findings.push(finding(
+                        &self.sheets[*sheet],
+                        Lint::SingleNodeNet,
+                        format!("Net {} has only the pin {}", name, pin_name(&self.sheets[*sheet], pin)),
+                        pin.pos(),
+                    ));
+                }
+            }
+        }
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{
+        gr::Pt,
+        netlist::{Lint, Netlist},
+        Project, Schema,
+    };
+
+    #[test]
+    fn clean() {
+        for path in ["tests/summe/summe.kicad_sch", "tests/echo/echo.kicad_sch"] {
+            let schema = Schema::load(Path::new(path)).unwrap();
+            let findings = Netlist::from(&schema).unwrap().lint();
+            assert!(findings.is_empty(), "{}: {:?}", path, findings);
+        }
+    }
+
+    #[test]
+    fn findings() {
+        let schema = Schema::load(Path::new("tests/lint/lint.kicad_sch")).unwrap();
+        let findings = Netlist::from(&schema)
+            .unwrap()
+            .lint()
+            .into_iter()
+            .map(|f| (f.lint, f.pos, f.message))
+            .collect::<Vec<(Lint, Pt, String)>>();
+        let pt = |x: f32, y: f32| Pt { x, y };
+        assert_eq!(
+            vec![
+                (Lint::DanglingWire, pt(69.85, 30.48), String::from("Wire end is not connected")),
+                (
+                    Lint::ConnectedNoConnect,
+                    pt(50.8, 46.99),
+                    String::from("No connect flag is connected to R1:1, R2:1")
+                ),
+                (Lint::UnconnectedPin, pt(50.8, 54.61), String::from("Pin R1:2 is not connected")),
+                (Lint::DanglingLabel, pt(101.6, 60.96), String::from("Label FLOAT is not connected")),
+                (Lint::SingleNodeNet, pt(76.2, 54.61), String::from("Net /OUT has only the pin R2:2")),
+            ],
+            findings
+        );
+
+        let project = Project::load(Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+        let findings = Netlist::from_project(&project).unwrap().lint();
+        assert_eq!(2, findings.len());
+        assert!(findings.iter().all(|f| f.lint == Lint::SingleNodeNet && f.sheet == "/"));
+    }
+}
@@ -101,6 +101,12 @@ impl Theme {
         }
     }
 
+    ///Get the color for the trace with the index,
+    ///the colors are repeated when there are more traces than colors.
+    pub fn trace(&self, index: usize) -> Color {
+        TRACES[index % TRACES.len()]
+    }
+
     ///Get the stroke width for the style.
     ///
     ///rule:
@@ -128,6 +134,11 @@ pub enum Style {
     Label,
     PinName,
     PinNumber,
+    Axis,
+    Grid,
+    Trace,
+    ErcError,
+    ErcWarning,
     Todo,
 }
 
@@ -141,6 +152,11 @@ impl From<String> for Style {
             "noconnect" => Self::NoConnect,
             "outline" => Self::Outline,
             el::PROPERTY => Self::Property,
+            "axis" => Self::Axis,
+            "grid" => Self::Grid,
+            "trace" => Self::Trace,
+            "erc_error" => Self::ErcError,
+            "erc_warning" => Self::ErcWarning,
             "todo" => Self::Todo,
             _ => Self::Wire,
         }
@@ -159,13 +175,18 @@ impl std::fmt::Display for Style {
             Self::Label => "label",
             Self::PinName => "pinname",
             Self::PinNumber => "pinnumber",
+            Self::Axis => "axis",
+            Self::Grid => "grid",
+            Self::Trace => "trace",
+            Self::ErcError => "erc_error",
+            Self::ErcWarning => "erc_warning",
             Self::Todo => "todo",
         };
         write!(f, "{}", s)
     }
 }
 
-const COLORS: [(Style, Color); 7] = [
+const COLORS: [(Style, Color); 11] = [
     (Style::Wire, Color::Rgba(0, 150, 0, 255)),
     (Style::NoConnect, Color::Rgba(0, 0, 132, 255)),
     (Style::Junction, Color::Rgba(0, 150, 0, 255)),
@@ -173,6 +194,10 @@ const COLORS: [(Style, Color); 7] = [
     (Style::PinName, Color::Rgba(132, 0, 0, 255)),
     (Style::PinNumber, Color::Rgba(132, 0, 0, 255)),
     (Style::Property, Color::Rgba(5, 105, 12, 255)),
+    (Style::Axis, Color::Rgba(0, 0, 0, 255)),
+    (Style::Grid, Color::Rgba(200, 200, 200, 255)),
+    (Style::ErcError, Color::Rgba(255, 0, 0, 204)),
+    (Style::ErcWarning, Color::Rgba(209, 146, 0, 204)),
 ];
 
 const FILLS: [(Style, Color); 2] = [
@@ -180,16 +205,32 @@ const FILLS: [(Style, Color); 2] = [
     (Style::Outline, Color::Rgba(200, 98, 194, 255)),
 ];
 
-const WIDTHS: [(Style, f32); 4] = [
+const WIDTHS: [(Style, f32); 9] = [
     (Style::Wire, 0.35),
     (Style::NoConnect, 0.25),
     (Style::Junction, 0.1),
     (Style::Outline, 0.35),
+    (Style::Axis, 0.25),
+    (Style::Grid, 0.1),
+    (Style::Trace, 0.3),
+    (Style::ErcError, 0.1),
+    (Style::ErcWarning, 0.1),
 ];
 
-const FONT_SIZES: [(Style, (f32, f32)); 4] = [
+const FONT_SIZES: [(Style, (f32, f32)); 5] = [
     (Style::Property, (1.75, 1.75)),
     (Style::PinNumber, (0.25, 0.25)),
     (Style::PinName, (0.35, 0.35)),
     (Style::Label, (1.75, 1.75)),
+    (Style::Axis, (1.75, 1.75)),
+];
+
+/// The colors of the traces in a chart.
+const TRACES: [Color; 6] = [
+    Color::Rgba(0, 114, 189, 255),
+    Color::Rgba(217, 83, 25, 255),
+    Color::Rgba(119, 172, 48, 255),
+    Color::Rgba(126, 47, 142, 255),
+    Color::Rgba(237, 177, 32, 255),
+    Color::Rgba(77, 190, 238, 255),
 ];
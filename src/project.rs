@@ -0,0 +1,369 @@
+//! Load a hierarchical schematic project.
+//!
+//! A KiCad design can be split into several schematic files. The root
+//! schematic references its child schematics with [`HierarchicalSheet`]
+//! items. The [`Project`] loads the root schematic and resolves all the
+//! sheets recursively into a tree of [`Sheet`]s.
+//!
+//! The `path` of a sheet is the same uuid path that is used in the
+//! symbol instances, e.g. `/<root uuid>/<sheet uuid>`. The `name_path`
+//! is the human readable form of the path, e.g. `/amp/`.
+//!
+//! The [`NetSettings`] with the net classes are loaded from the KiCad
+//! project file next to the root schema.
+
+use std::path::{Path, PathBuf};
+
+use indexmap::IndexMap;
+
+use crate::{
+    schema::{HierarchicalSheet, SchemaItem},
+    sexp::constants::el,
+    Error, Project, Schema,
+};
+
+/// A schematic sheet within the hierarchy of a [`Project`].
+#[derive(Debug)]
+pub struct Sheet {
+    /// The name of the sheet, the root sheet uses the project name.
+    pub name: String,
+    /// The uuid path of the sheet as used in the symbol instances.
+    pub path: String,
+    /// The human readable path of the sheet, the root sheet is `/`.
+    pub name_path: String,
+    /// The page number of the sheet instance.
+    pub page: String,
+    /// The file the sheet was loaded from.
+    pub filename: PathBuf,
+    /// The uuid of the sheet item in the parent schema, `None` for the root sheet.
+    pub uuid: Option<String>,
+    /// The loaded schema of the sheet.
+    pub schema: Schema,
+    /// The child sheets in the order they are defined in the schema.
+    pub children: Vec<Sheet>,
+}
+
+impl Sheet {
+    /// Iterate over this sheet and all its children, depth first.
+    pub fn iter(&self) -> impl Iterator<Item = &Sheet> {
+        let mut sheets = vec![self];
+        for child in &self.children {
+            sheets.extend(child.iter());
+        }
+        sheets.into_iter()
+    }
+
+    fn load_children(&mut self, stack: &mut Vec<PathBuf>) -> Result<(), Error> {
+        let dir = self
+            .filename
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let sheets = self
+            .schema
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                SchemaItem::HierarchicalSheet(sheet) => Some(sheet),
+                _ => None,
+            })
+            .collect::<Vec<&HierarchicalSheet>>();
+
+        let mut children = Vec::new();
+        for sheet in sheets {
+            let Some(file) = sheet.property(el::PROPERTY_SHEET_FILE) else {
+                return Err(Error(
+                    String::from("project"),
+                    format!("sheet {} has no sheet file", sheet.uuid),
+                ));
+            };
+            let filename = dir.join(&file);
+            let Ok(canonical) = filename.canonicalize() else {
+                return Err(Error(
+                    String::from("project"),
+                    format!("sheet file not found: {}", filename.display()),
+                ));
+            };
+            if stack.contains(&canonical) {
+                return Err(Error(
+                    String::from("project"),
+                    format!("recursive sheet: {}", filename.display()),
+                ));
+            }
+
+            let name = sheet
+                .property(el::PROPERTY_SHEET_NAME)
+                .unwrap_or_else(|| file.trim_end_matches(".kicad_sch").to_string());
+            let page = sheet
+                .instances
+                .iter()
+                .find(|i| i.path == self.path)
+                .or(sheet.instances.first())
+                .map(|i| i.page_number.clone())
+                .unwrap_or_default();
+
+            let mut child = Sheet {
+                path: format!("{}/{}", self.path, sheet.uuid),
+                name_path: format!("{}{}/", self.name_path, name),
+                name,
+                page,
+                schema: Schema::load(&filename)?,
+                filename,
+                uuid: Some(sheet.uuid.clone()),
+                children: Vec::new(),
+            };
+
+            stack.push(canonical);
+            child.load_children(stack)?;
+            stack.pop();
+
+            children.push(child);
+        }
+        self.children = children;
+        Ok(())
+    }
+}
+
+/// The name of the default net class.
+pub const DEFAULT_NETCLASS: &str = "Default";
+
+/// A net class definition of the project file, the sizes are in mm.
+/// Missing values are taken from the default net class by KiCad.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NetClass {
+    pub name: String,
+    pub clearance: Option<f32>,
+    pub track_width: Option<f32>,
+    pub via_diameter: Option<f32>,
+    pub via_drill: Option<f32>,
+    pub diff_pair_width: Option<f32>,
+    pub diff_pair_gap: Option<f32>,
+}
+
+/// The net class settings of the project file.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct NetSettings {
+    /// The net class definitions.
+    pub classes: Vec<NetClass>,
+    /// The net classes assigned to a net name.
+    pub assignments: IndexMap<String, String>,
+    /// The net classes assigned by a net name pattern, `*` and `?` are wildcards.
+    pub patterns: Vec<(String, String)>,
+}
+
+impl NetSettings {
+    /// Load the `net_settings` from a KiCad project file.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let content = std::fs::read_to_string(path)?;
+        let project: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| Error(String::from("project"), format!("{}: {}", path.display(), e)))?;
+        let mut settings = Self::default();
+        let Some(net_settings) = project.get("net_settings") else {
+            return Ok(settings);
+        };
+        let text = |value: &serde_json::Value, key: &str| {
+            value.get(key).and_then(|v| v.as_str()).map(String::from)
+        };
+        for class in net_settings.get("classes").and_then(|c| c.as_array()).into_iter().flatten() {
+            let size = |key: &str| class.get(key).and_then(|v| v.as_f64()).map(|v| v as f32);
+            settings.classes.push(NetClass {
+                name: text(class, "name").unwrap_or_default(),
+                clearance: size("clearance"),
+                track_width: size("track_width"),
+                via_diameter: size("via_diameter"),
+                via_drill: size("via_drill"),
+                diff_pair_width: size("diff_pair_width"),
+                diff_pair_gap: size("diff_pair_gap"),
+            });
+        }
+        if let Some(assignments) = net_settings.get("netclass_assignments").and_then(|a| a.as_object()) {
+            for (net, class) in assignments {
+                //newer versions assign a list of net classes
+                let class = class
+                    .as_str()
+                    .or_else(|| class.as_array().and_then(|c| c.first()).and_then(|c| c.as_str()));
+                if let Some(class) = class {
+                    settings.assignments.insert(net.clone(), class.to_string());
+                }
+            }
+        }
+        for pattern in net_settings.get("netclass_patterns").and_then(|p| p.as_array()).into_iter().flatten() {
+            if let (Some(pattern), Some(class)) = (text(pattern, "pattern"), text(pattern, "netclass")) {
+                settings.patterns.push((pattern, class));
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Get the net class definition by name.
+    pub fn class(&self, name: &str) -> Option<&NetClass> {
+        self.classes.iter().find(|c| c.name == name)
+    }
+
+    /// Get the net class which is assigned to the net name, either
+    /// directly or by the first matching pattern.
+    pub fn netclass(&self, net: &str) -> Option<&str> {
+        self.assignments
+            .get(net)
+            .map(String::as_str)
+            .or_else(|| {
+                self.patterns
+                    .iter()
+                    .find(|(pattern, _)| wildcard(pattern, net))
+                    .map(|(_, class)| class.as_str())
+            })
+    }
+}
+
+/// Match the text with a wildcard pattern, `*` matches any
+/// characters and `?` a single character.
+fn wildcard(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    let text = text.chars().collect::<Vec<char>>();
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+impl Project {
+    ///Load a project from the path of the root schema.
+    ///
+    ///```
+    ///use recad_core::Project;
+    ///use std::path::Path;
+    ///
+    ///let project = Project::load(Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+    ///assert_eq!(2, project.sheets().count());
+    ///```
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let schema = Schema::load(path)?;
+        let mut root = Sheet {
+            name: name.clone(),
+            path: format!("/{}", schema.uuid),
+            name_path: String::from("/"),
+            page: schema
+                .sheet_instances
+                .first()
+                .map(|i| i.reference.clone())
+                .unwrap_or(String::from("1")),
+            filename: path.to_path_buf(),
+            uuid: None,
+            schema,
+            children: Vec::new(),
+        };
+
+        let mut stack = vec![path.canonicalize()?];
+        root.load_children(&mut stack)?;
+
+        let project_file = path.with_extension("kicad_pro");
+        let net_settings = if project_file.exists() {
+            NetSettings::load(&project_file)?
+        } else {
+            NetSettings::default()
+        };
+
+        Ok(Self { name, root, net_settings })
+    }
+
+    /// Iterate over all sheets of the project, depth first starting with the root sheet.
+    pub fn sheets(&self) -> impl Iterator<Item = &Sheet> {
+        self.root.iter()
+    }
+
+    /// Get a sheet by its uuid path.
+    pub fn sheet(&self, path: &str) -> Option<&Sheet> {
+        self.sheets().find(|s| s.path == path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{wildcard, NetSettings};
+    use crate::Project;
+
+    #[test]
+    fn load_hierarchy() {
+        let project = Project::load(Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+        assert_eq!("hierarchy", project.name);
+        assert_eq!("/", project.root.name_path);
+        assert_eq!("1", project.root.page);
+        assert_eq!(1, project.root.children.len());
+
+        let child = &project.root.children[0];
+        assert_eq!("amp", child.name);
+        assert_eq!("/amp/", child.name_path);
+        assert_eq!("2", child.page);
+        assert_eq!(
+            "/8f4b2a8e-3c1d-4e5f-9a6b-7c8d9e0f1a2b/1d2e3f40-5a6b-4c7d-8e9f-0a1b2c3d4e5f",
+            child.path
+        );
+        assert!(child.filename.ends_with("amp.kicad_sch"));
+        assert!(child.schema.symbol("R2", 1).is_some());
+        assert!(project.sheet(&child.path).is_some());
+    }
+
+    #[test]
+    fn recursive_sheet() {
+        let project = Project::load(Path::new("tests/hierarchy/cycle.kicad_sch"));
+        let err = project.unwrap_err();
+        assert_eq!("project", err.0);
+        assert!(err.1.starts_with("recursive sheet"));
+    }
+
+    #[test]
+    fn missing_sheet() {
+        let project = Project::load(Path::new("tests/hierarchy/missing.kicad_sch"));
+        let err = project.unwrap_err();
+        assert_eq!("project", err.0);
+        assert!(err.1.starts_with("sheet file not found"));
+    }
+
+    #[test]
+    fn net_settings() {
+        let settings = NetSettings::load(Path::new("tests/netclass/netclass.kicad_pro")).unwrap();
+        assert_eq!(4, settings.classes.len());
+        assert_eq!(Some(1.0), settings.class("Power").unwrap().track_width);
+        assert_eq!(None, settings.class("Analog").unwrap().clearance);
+        assert_eq!(Some("Power"), settings.netclass("/DATA"));
+        assert_eq!(Some("Analog"), settings.netclass("/SIG"));
+        assert_eq!(None, settings.netclass("/CLK"));
+
+        let project = Project::load(Path::new("tests/echo/echo.kicad_sch")).unwrap();
+        assert_eq!(1, project.net_settings.classes.len());
+        assert_eq!(Some("Default"), project.net_settings.netclass("+15V"));
+        let project = Project::load(Path::new("tests/hierarchy/hierarchy.kicad_sch")).unwrap();
+        assert_eq!(NetSettings::default(), project.net_settings);
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(wildcard("/SI*", "/SIG"));
+        assert!(wildcard("*", ""));
+        assert!(wildcard("Net-(?1-*)", "Net-(R1-Pad2)"));
+        assert!(wildcard("*CLK*", "/amp/CLK_IN"));
+        assert!(!wildcard("/SI?", "/SIGNAL"));
+        assert!(!wildcard("/CV", "/CV1"));
+    }
+}
@@ -0,0 +1,388 @@
+//! Read the simulation results from ngspice/SPICE3 rawfiles.
+//!
+//! A rawfile contains one or more plots, every plot has a list of variables
+//! and the values for every point. The first variable is the scale of the plot,
+//! the time for a transient analysis, the frequency for an AC analysis or the
+//! swept value for a DC analysis. The values are written as text or binary
+//! data, real or complex.
+//!
+//! The voltages can be queried with the net names from the [`crate::netlist::Netlist`]:
+//!
+//! ```
+//! use std::path::Path;
+//! use recad_core::rawfile::RawFile;
+//!
+//! let raw = RawFile::load(Path::new("tests/spice/rc.raw")).unwrap();
+//! let tran = raw.plot("Transient Analysis").unwrap();
+//! let time = tran.scale().unwrap().real().unwrap();
+//! let out = tran.voltage("OUT").unwrap().real().unwrap();
+//! assert_eq!(time.len(), out.len());
+//! ```
+use std::path::Path;
+
+use crate::{netlist::spice_net, Error};
+
+/// The values of a variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vector {
+    Real(Vec<f64>),
+    /// The real and imaginary part of the values.
+    Complex(Vec<(f64, f64)>),
+}
+
+impl Vector {
+    /// The number of values.
+    pub fn len(&self) -> usize {
+        match self {
+            Vector::Real(values) => values.len(),
+            Vector::Complex(values) => values.len(),
+        }
+    }
+
+    /// Test if the vector has no values.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the real values, `None` for a complex vector.
+    pub fn real(&self) -> Option<&[f64]> {
+        match self {
+            Vector::Real(values) => Some(values),
+            Vector::Complex(_) => None,
+        }
+    }
+
+    /// Get the complex values, `None` for a real vector.
+    pub fn complex(&self) -> Option<&[(f64, f64)]> {
+        match self {
+            Vector::Real(_) => None,
+            Vector::Complex(values) => Some(values),
+        }
+    }
+
+    /// Get the magnitude of the values.
+    pub fn magnitude(&self) -> Vec<f64> {
+        match self {
+            Vector::Real(values) => values.iter().map(|v| v.abs()).collect(),
+            Vector::Complex(values) => values.iter().map(|(re, im)| re.hypot(*im)).collect(),
+        }
+    }
+
+    /// Get the phase of the values in degrees.
+    pub fn phase(&self) -> Vec<f64> {
+        match self {
+            Vector::Real(values) => values
+                .iter()
+                .map(|v| if *v < 0.0 { 180.0 } else { 0.0 })
+                .collect(),
+            Vector::Complex(values) => values
+                .iter()
+                .map(|(re, im)| im.atan2(*re).to_degrees())
+                .collect(),
+        }
+    }
+}
+
+/// A variable of a plot.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variable {
+    /// The variable name as written by the simulator, e.g. `v(out)` or `v1#branch`.
+    pub name: String,
+    /// The type of the variable: `time`, `frequency`, `voltage` or `current`.
+    pub kind: String,
+    pub values: Vector,
+}
+
+/// A plot of the rawfile with the results of one analysis.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RawPlot {
+    pub title: String,
+    pub date: String,
+    /// The name of the plot, e.g. `Transient Analysis`.
+    pub name: String,
+    pub variables: Vec<Variable>,
+}
+
+impl RawPlot {
+    /// The scale of the plot, the first variable.
+    pub fn scale(&self) -> Option<&Vector> {
+        self.variables.first().map(|v| &v.values)
+    }
+
+    /// Get the values of a variable, the name is not case sensitive.
+    pub fn vector(&self, name: &str) -> Option<&Vector> {
+        self.variables
+            .iter()
+            .find(|v| v.name.eq_ignore_ascii_case(name))
+            .map(|v| &v.values)
+    }
+
+    /// Get the voltage of a net.
+    ///
+    /// The net name of the schema is mapped to the spice node like in the netlist,
+    /// e.g. `Net-(R1-Pad1)` to `net-_r1-pad1_` and `GND` to `0`. The simulator
+    /// writes the voltages as `v(net)` or just as the lower case net name.
+    pub fn voltage(&self, net: &str) -> Option<&Vector> {
+        let net = spice_net(net);
+        self.vector(&format!("v({})", net)).or_else(|| {
+            self.variables
+                .iter()
+                .find(|v| v.kind == "voltage" && v.name.eq_ignore_ascii_case(&net))
+                .map(|v| &v.values)
+        })
+    }
+
+    /// Get the current through a voltage source or inductor,
+    /// written as `i(v1)` or `v1#branch`.
+    pub fn current(&self, reference: &str) -> Option<&Vector> {
+        self.vector(&format!("i({})", reference))
+            .or_else(|| self.vector(&format!("{}#branch", reference)))
+    }
+}
+
+/// The content of a rawfile.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RawFile {
+    pub plots: Vec<RawPlot>,
+}
+
+/// Read the lines and the binary data of the rawfile.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn line(&mut self) -> Option<&'a str> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let rest = &self.data[self.pos..];
+        let end = rest.iter().position(|b| *b == b'\n').unwrap_or(rest.len());
+        self.pos += end + 1;
+        Some(std::str::from_utf8(&rest[..end]).unwrap_or_default().trim_end_matches('\r'))
+    }
+
+    fn f64(&mut self) -> Option<f64> {
+        let bytes = self.data.get(self.pos..self.pos + 8)?;
+        self.pos += 8;
+        Some(f64::from_le_bytes(bytes.try_into().ok()?))
+    }
+}
+
+fn error<T>(msg: String) -> Result<T, Error> {
+    Err(Error(String::from("rawfile"), msg))
+}
+
+impl RawFile {
+    /// Load a rawfile.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        RawFile::parse(&std::fs::read(path)?)
+    }
+
+    /// Parse the content of a rawfile in ascii or binary format.
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        let mut reader = Reader { data, pos: 0 };
+        let mut raw = RawFile::default();
+        let mut plot = RawPlot::default();
+        let mut complex = false;
+        let mut variables = 0;
+        let mut points = 0;
+        while let Some(line) = reader.line() {
+            let Some((key, value)) = line.split_once(':') else {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return error(format!("unexpected line '{}'", line));
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "title" => plot.title = value.to_string(),
+                "date" => plot.date = value.to_string(),
+                "plotname" => plot.name = value.to_string(),
+                "flags" => complex = value.split_whitespace().any(|f| f == "complex"),
+                "no. variables" => variables = parse_count(value)?,
+                "no. points" => points = parse_count(value)?,
+                "variables" => {
+                    for _ in 0..variables {
+                        let Some(line) = reader.line() else {
+                            return error(String::from("missing variables"));
+                        };
+                        let tokens = line.split_whitespace().collect::<Vec<&str>>();
+                        if tokens.len() < 3 {
+                            return error(format!("invalid variable '{}'", line));
+                        }
+                        plot.variables.push(Variable {
+                            name: tokens[1].to_string(),
+                            kind: tokens[2].to_string(),
+                            values: if complex {
+                                Vector::Complex(Vec::with_capacity(points))
+                            } else {
+                                Vector::Real(Vec::with_capacity(points))
+                            },
+                        });
+                    }
+                }
+                "values" => {
+                    read_ascii(&mut reader, &mut plot, points)?;
+                    raw.plots.push(std::mem::take(&mut plot));
+                }
+                "binary" => {
+                    read_binary(&mut reader, &mut plot, points);
+                    raw.plots.push(std::mem::take(&mut plot));
+                }
+                //Command, Option and unknown header lines.
+                _ => {}
+            }
+        }
+        Ok(raw)
+    }
+
+    /// Get the plot by the plot name, the name is not case sensitive.
+    pub fn plot(&self, name: &str) -> Option<&RawPlot> {
+        self.plots.iter().find(|p| p.name.eq_ignore_ascii_case(name))
+    }
+}
+
+fn parse_count(value: &str) -> Result<usize, Error> {
+    match value.parse::<usize>() {
+        Ok(count) => Ok(count),
+        Err(_) => error(format!("invalid count '{}'", value)),
+    }
+}
+
+fn parse_value(token: &str) -> Result<f64, Error> {
+    match token.parse::<f64>() {
+        Ok(value) => Ok(value),
+        Err(_) => error(format!("invalid value '{}'", token)),
+    }
+}
+
+/// Read the ascii values, every point starts with the point index
+/// followed by one value per variable. Complex values are written as `re,im`.
+fn read_ascii(reader: &mut Reader, plot: &mut RawPlot, points: usize) -> Result<(), Error> {
+    let mut tokens = Vec::new();
+    for point in 0..points {
+        for variable in 0..=plot.variables.len() {
+            while tokens.is_empty() {
+                let Some(line) = reader.line() else {
+                    //the simulation was interrupted, use the complete points.
+                    truncate(plot, point);
+                    return Ok(());
+                };
+                tokens = line.split_whitespace().rev().map(|t| t.to_string()).collect();
+            }
+            let token = tokens.pop().unwrap();
+            if variable == 0 {
+                continue;
+            }
+            match &mut plot.variables[variable - 1].values {
+                Vector::Real(values) => values.push(parse_value(&token)?),
+                Vector::Complex(values) => {
+                    let Some((re, im)) = token.split_once(',') else {
+                        return error(format!("invalid complex value '{}'", token));
+                    };
+                    values.push((parse_value(re)?, parse_value(im)?));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read the binary values, the values are little endian doubles. Complex values
+/// are written as two doubles.
+fn read_binary(reader: &mut Reader, plot: &mut RawPlot, points: usize) {
+    for point in 0..points {
+        let start = reader.pos;
+        for variable in plot.variables.iter_mut() {
+            let value = match &mut variable.values {
+                Vector::Real(values) => reader.f64().map(|v| values.push(v)),
+                Vector::Complex(values) => reader
+                    .f64()
+                    .and_then(|re| reader.f64().map(|im| values.push((re, im)))),
+            };
+            if value.is_none() {
+                //the simulation was interrupted, use the complete points.
+                reader.pos = start;
+                truncate(plot, point);
+                return;
+            }
+        }
+    }
+}
+
+fn truncate(plot: &mut RawPlot, points: usize) {
+    for variable in plot.variables.iter_mut() {
+        match &mut variable.values {
+            Vector::Real(values) => values.truncate(points),
+            Vector::Complex(values) => values.truncate(points),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{RawFile, Vector};
+
+    #[test]
+    fn ascii() {
+        let raw = RawFile::load(Path::new("tests/spice/rc.raw")).unwrap();
+        assert_eq!(2, raw.plots.len());
+        let tran = raw.plot("Transient Analysis").unwrap();
+        assert_eq!(Some(&Vector::Real(vec![0.0, 1e-3, 2e-3])), tran.scale());
+        assert_eq!(Some(&Vector::Real(vec![0.0, 0.5, 0.75])), tran.voltage("OUT"));
+        assert_eq!(Some(&Vector::Real(vec![0.0, -1e-3, -5e-4])), tran.current("V1"));
+        let ac = raw.plot("AC Analysis").unwrap();
+        assert_eq!(Some(&Vector::Complex(vec![(1.0, 0.0), (0.5, -0.5)])), ac.voltage("out"));
+        assert_eq!(vec![0.0, -45.0], ac.voltage("out").unwrap().phase());
+    }
+
+    #[test]
+    fn schema_net() {
+        let schema = crate::Schema::load(Path::new("tests/spice/spice.kicad_sch")).unwrap();
+        let netlist = crate::netlist::Netlist::from(&schema).unwrap();
+        let data = [
+            "Title: spice",
+            "Date: Fri Oct 16 10:00:00  2026",
+            "Plotname: Operating Point",
+            "Flags: real",
+            "No. Variables: 3",
+            "No. Points: 1",
+            "Variables:",
+            "\t0\tv(/out)\tvoltage",
+            "\t1\tv(unconnected-_r3-pad1_)\tvoltage",
+            "\t2\tv(0)\tvoltage",
+            "Values:",
+            " 0\t2.5",
+            "\t1.5",
+            "\t0",
+            "",
+        ]
+        .join("\n");
+        let raw = RawFile::parse(data.as_bytes()).unwrap();
+        let op = raw.plot("Operating Point").unwrap();
+        assert!(netlist.net("/OUT").is_some());
+        assert_eq!(Some(&Vector::Real(vec![2.5])), op.voltage("/OUT"));
+        assert!(netlist.net("unconnected-(R3-Pad1)").is_some());
+        assert_eq!(Some(&Vector::Real(vec![1.5])), op.voltage("unconnected-(R3-Pad1)"));
+        assert!(netlist.net("GND").is_some());
+        assert_eq!(Some(&Vector::Real(vec![0.0])), op.voltage("GND"));
+    }
+
+    #[test]
+    fn binary() {
+        let ascii = RawFile::load(Path::new("tests/spice/rc.raw")).unwrap();
+        let binary = RawFile::load(Path::new("tests/spice/rc_binary.raw")).unwrap();
+        assert_eq!(ascii, binary);
+    }
+
+    #[test]
+    fn truncated() {
+        let data = std::fs::read("tests/spice/rc_binary.raw").unwrap();
+        let raw = RawFile::parse(&data[..data.len() - 12]).unwrap();
+        assert_eq!(1, raw.plot("AC Analysis").unwrap().scale().unwrap().len());
+    }
+}
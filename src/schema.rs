@@ -11,13 +11,14 @@ use crate::{
     math::{bbox::Bbox, ToNdarray},
     sexp::{builder::Builder, constants::el},
     symbols::LibrarySymbol,
+    value::Value,
     Error, Schema, SexpWrite,
 };
 
 // TODO A schema text has the `exclude_from_sim` field, which is not included in `gr:Text`
 
 ///A `Text`in the schema
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Text {
     /// X and Y coordinates of the text.
     pub pos: Pos,
@@ -32,7 +33,7 @@ pub struct Text {
 }
 
 ///A `TextBox`in the schema
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TextBox {
     /// X and Y coordinates of the text.
     pub pos: Pos,
@@ -57,7 +58,7 @@ pub struct TextBox {
 /// A junction represents a connection point where multiple wires
 /// or components intersect, allowing electrical current to
 /// flow between them.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Junction {
     /// `Pos` defines the X and Y coordinates of the junction.
     pub pos: Pos,
@@ -83,7 +84,7 @@ impl Junction {
 ///A `Bus` is a group of interconnected wires or connections that distribute
 ///signals among multiple devices or components, allowing them to share the
 ///same signal source.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Bus {
     /// The list of X and Y coordinates of start and end points of the bus.
     pub pts: Pts,
@@ -95,7 +96,7 @@ pub struct Bus {
 
 /// `BusEentry` is a component representing an individual pin within
 /// a multi-pin connection in a [`Bus`]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct BusEntry {
     /// The X and Y coordinates of the junction.
     pub pos: Pos,
@@ -109,7 +110,7 @@ pub struct BusEntry {
 
 /// Wires represent electrical connections between components or points,
 /// showing the circuit's interconnections and paths for electric current flow.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct Wire {
     /// The list of X and Y coordinates of start and end points of the wire.
     pub pts: Pts,
@@ -194,7 +195,7 @@ pub struct GlobalLabel {
 /// It's used for clarity in cases where there should be no path but
 /// one isn't explicitly shown. Proper usage ensures correct net
 /// connections, avoiding errors, and passes ERC checks.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct NoConnect {
     /// The X and Y coordinates of the no-connect within the schematic.
     pub pos: Pos,
@@ -240,6 +241,16 @@ pub struct HierarchicalSheet {
     pub instances: Vec<ProjectInstance>,
 }
 
+impl HierarchicalSheet {
+    /// Get a property value by key
+    pub fn property(&self, key: &str) -> Option<String> {
+        self.props
+            .iter()
+            .find(|p| p.key == key)
+            .map(|p| p.value.to_string())
+    }
+}
+
 /// Represents an instance of a hierarchical sheet within a specific project.
 #[derive(Debug, Clone, PartialEq)]
 pub struct ProjectInstance {
@@ -309,6 +320,25 @@ pub struct NetclassFlag {
     pub uuid: String,
 }
 
+impl NetclassFlag {
+    /// The name of the net class from the `Netclass` property.
+    pub fn netclass(&self) -> Option<&str> {
+        self.props
+            .iter()
+            .find(|p| p.key == el::PROPERTY_NETCLASS)
+            .map(|p| p.value.as_str())
+            .filter(|v| !v.is_empty())
+    }
+}
+
+/// A rule area, the netclass flags on the border of the area
+/// apply to all nets inside the area.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleArea {
+    /// The outline of the area.
+    pub polyline: Polyline,
+}
+
 /// Defines the type of electrical connection made by the sheet pin.
 #[derive(Debug, Clone, PartialEq)]
 pub enum ConnectionType {
@@ -350,6 +380,17 @@ impl fmt::Display for ConnectionType {
     }
 }
 
+/// A line of the bill of materials, see [`Schema::bom`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BomItem {
+    /// The references of the symbols in the group.
+    pub references: Vec<String>,
+    /// The value of the first symbol in the group.
+    pub value: String,
+    pub lib_id: String,
+    pub footprint: String,
+}
+
 /// The instances token defines a symbol instance.
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Instance {
@@ -395,7 +436,8 @@ pub struct Symbol {
 }
 
 impl Symbol {
-    pub fn new(reference: &str, value: &str, lib_id: &str) -> Self {
+    /// Create a symbol, the value can be a string or a [`Value`].
+    pub fn new(reference: &str, value: impl ToString, lib_id: &str) -> Self {
         Self {
             lib_id: lib_id.to_string(),
             pos: Pos::default(),
@@ -426,6 +468,12 @@ impl Symbol {
         }
     }
 
+    /// Get the value property as [`Value`], returns an error when
+    /// the value is not a number like `TL072`.
+    pub fn value(&self) -> Result<Value, Error> {
+        self.property(el::PROPERTY_VALUE).parse()
+    }
+
     /// Get a property value by key
     pub fn property(&self, key: &str) -> String {
         self.props
@@ -440,6 +488,24 @@ impl Symbol {
             .collect::<String>()
     }
 
+    /// Get the reference of the symbol for the sheet instance path like
+    /// `/<root uuid>/<sheet uuid>`. A sheet which is used more than once
+    /// has a reference per instance, the `Reference` property is used when
+    /// there is no instance for the path.
+    pub fn reference(&self, path: &str) -> &str {
+        self.instances
+            .iter()
+            .find(|i| i.path == path)
+            .map(|i| i.reference.as_str())
+            .or_else(|| {
+                self.props
+                    .iter()
+                    .find(|p| p.key == el::PROPERTY_REFERENCE)
+                    .map(|p| p.value.as_str())
+            })
+            .unwrap_or_default()
+    }
+
     /// Set a property value by key
     pub fn set_property(&mut self, key: &str, value: &str) {
         self.props.iter_mut().for_each(|p| {
@@ -499,9 +565,46 @@ impl Schema {
         let tree = crate::sexp::SexpTree::from(parser.iter())?;
         tree.into()
     }
+
     ///Save a schema to a path
-    pub fn save(&self) {
-        //TODO
+    ///
+    ///The schema is first written to a temporary file next to the
+    ///target, which is then renamed into place. An existing file
+    ///is left untouched when writing fails.
+    ///
+    ///```
+    ///use recad_core::Schema;
+    ///use std::path::Path;
+    ///
+    ///let schema = Schema::load(Path::new("tests/summe/summe.kicad_sch")).unwrap();
+    ///std::fs::create_dir_all("target/out").unwrap();
+    ///assert!(schema.save(Path::new("target/out/save_summe.kicad_sch")).is_ok());
+    ///```
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let Some(filename) = path.file_name() else {
+            return Err(Error(
+                String::from("io"),
+                format!("not a file path: {}", path.display()),
+            ));
+        };
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(filename);
+        tmp_name.push(".tmp");
+        let tmp = path.with_file_name(tmp_name);
+
+        let res = std::fs::File::create(&tmp)
+            .map_err(Error::from)
+            .and_then(|mut file| {
+                self.write(&mut file)?;
+                file.sync_all()?;
+                Ok(())
+            })
+            .and_then(|_| std::fs::rename(&tmp, path).map_err(Error::from));
+
+        if res.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        res
     }
 
     ///Get a Symbol by reference and unit number.
@@ -623,12 +726,59 @@ impl Schema {
     /// assert!(symbol.is_some());
     ///
     pub fn library_symbol(&self, lib_id: &str) -> Option<&LibrarySymbol> {
-        self.library_symbols
-            .iter()
-            .filter(|s| s.lib_id == lib_id)
-            .collect::<Vec<&LibrarySymbol>>()
-            .first()
-            .copied()
+        self.library_symbols.iter().find(|s| s.lib_id == lib_id)
+    }
+
+    /// Get the bill of materials.
+    ///
+    /// The symbols with the same library symbol, footprint and value are grouped,
+    /// the values are compared as numbers when possible: `100nF` and `0.1u` are the
+    /// same value. Power symbols and symbols which are not in the BOM or not populated
+    /// are skipped.
+    ///
+    ///```
+    /// use recad_core::Schema;
+    /// use std::path::Path;
+    ///
+    /// let schema = Schema::load(Path::new("tests/summe/summe.kicad_sch")).unwrap();
+    /// let bom = schema.bom();
+    /// let opamps = bom.iter().find(|item| item.value == "TL072").unwrap();
+    /// assert_eq!(vec!["U5", "U7", "U6", "U2", "U3", "U4"], opamps.references);
+    ///
+    pub fn bom(&self) -> Vec<BomItem> {
+        let mut bom: Vec<BomItem> = Vec::new();
+        for symbol in self.items.iter().filter_map(|item| match item {
+            SchemaItem::Symbol(symbol) => Some(symbol),
+            _ => None,
+        }) {
+            let reference = symbol.property(el::PROPERTY_REFERENCE);
+            if !symbol.in_bom || symbol.dnp || reference.starts_with('#') {
+                continue;
+            }
+            let value = symbol.property(el::PROPERTY_VALUE);
+            let footprint = symbol.property(el::PROPERTY_FOOTPRINT);
+            let same_value = |other: &str| match (value.parse::<Value>(), other.parse::<Value>()) {
+                (Ok(value), Ok(other)) => value == other,
+                _ => value == other,
+            };
+            match bom.iter_mut().find(|item| {
+                item.lib_id == symbol.lib_id && item.footprint == footprint && same_value(&item.value)
+            }) {
+                Some(item) => {
+                    //the units of a symbol have the same reference.
+                    if !item.references.contains(&reference) {
+                        item.references.push(reference);
+                    }
+                }
+                None => bom.push(BomItem {
+                    references: vec![reference],
+                    value,
+                    lib_id: symbol.lib_id.clone(),
+                    footprint,
+                }),
+            }
+        }
+        bom
     }
 
     /// Returns the outline of this [`Schema`].
@@ -738,6 +888,11 @@ impl Schema {
             builder.text(rev);
             builder.end();
         }
+        if let Some(company) = &self.title_block.company_name {
+            builder.push(el::TITLE_BLOCK_COMPANY);
+            builder.text(company);
+            builder.end();
+        }
         for c in &self.title_block.comment {
             builder.push(el::TITLE_BLOCK_COMMENT);
             builder.value(&c.0.to_string());
@@ -770,6 +925,7 @@ impl Schema {
                 crate::schema::SchemaItem::NoConnect(item) => item.write(&mut builder)?,
                 crate::schema::SchemaItem::Polyline(item) => item.write(&mut builder)?,
                 crate::schema::SchemaItem::Rectangle(item) => item.write(&mut builder)?,
+                crate::schema::SchemaItem::RuleArea(item) => item.write(&mut builder)?,
                 crate::schema::SchemaItem::Symbol(item) => item.write(&mut builder)?,
                 crate::schema::SchemaItem::Text(item) => item.write(&mut builder)?,
                 crate::schema::SchemaItem::Wire(item) => item.write(&mut builder)?,
@@ -810,7 +966,7 @@ impl fmt::Display for Schema {
 }
 
 /// Abstraction of the schema items for iteration
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum SchemaItem {
     Arc(Arc),
     Bus(Bus),
@@ -827,6 +983,7 @@ pub enum SchemaItem {
     NoConnect(NoConnect),
     Polyline(Polyline),
     Rectangle(Rectangle),
+    RuleArea(RuleArea),
     Symbol(Symbol),
     Text(Text),
     TextBox(TextBox),
@@ -838,7 +995,8 @@ mod tests {
     use std::path::Path;
 
     use crate::{
-        schema::{SchemaItem, Symbol}, sexp::constants::test::SCHEMA_SUMME, Schema
+        schema::{SchemaItem, Symbol}, sexp::constants::test::SCHEMA_SUMME,
+        value::{Unit, Value}, Schema
     };
 
     #[test]
@@ -855,6 +1013,59 @@ mod tests {
         assert_eq!("J2".to_string(), symbol.property("Reference"));
     }
 
+    #[test]
+    fn symbol_value() {
+        let symbol = Symbol::new("C1", Value::new(1e-7).unit(Unit::Farad), "Device:C");
+        assert_eq!("100nF", symbol.property("Value"));
+        assert_eq!(Value::new(1e-7), symbol.value().unwrap());
+        assert!(Symbol::new("U1", "TL072", "Amplifier_Operational:TL072").value().is_err());
+    }
+
+    #[test]
+    fn bom() {
+        let mut schema = Schema::new("bom");
+        for (reference, value, lib_id) in [
+            ("C1", "100nF", "Device:C"),
+            ("C2", "0.1u", "Device:C"),
+            ("C3", "10n", "Device:C"),
+            ("R1", "4k7", "Device:R"),
+            ("R2", "4.7k", "Device:R"),
+            ("#PWR01", "GND", "power:GND"),
+        ] {
+            schema.items.push(SchemaItem::Symbol(Symbol::new(reference, value, lib_id)));
+        }
+        let bom = schema.bom();
+        assert_eq!(3, bom.len());
+        assert_eq!(vec!["C1", "C2"], bom[0].references);
+        assert_eq!("100nF", bom[0].value);
+        assert_eq!(vec!["C3"], bom[1].references);
+        assert_eq!(vec!["R1", "R2"], bom[2].references);
+    }
+
+    #[test]
+    fn rule_area() {
+        let schema = Schema::load(Path::new("tests/netclass/netclass.kicad_sch")).unwrap();
+        let areas = |schema: &Schema| {
+            schema
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    SchemaItem::RuleArea(area) => Some(area.clone()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let area = areas(&schema);
+        assert_eq!(1, area.len());
+        assert_eq!(4, area[0].polyline.pts.0.len());
+
+        let mut buffer = Vec::new();
+        schema.write(&mut buffer).unwrap();
+        let path = std::env::temp_dir().join("recad_rule_area.kicad_sch");
+        std::fs::write(&path, buffer).unwrap();
+        assert_eq!(area, areas(&Schema::load(&path).unwrap()));
+    }
+
     #[test]
     fn get_symbol() {
         let schema = Schema::load(Path::new(SCHEMA_SUMME)).unwrap();
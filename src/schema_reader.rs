@@ -1,6 +1,6 @@
 use crate::{
     draw::To, gr::{self, Arc, Circle, Color, GraphicItem, Line, PaperSize, Polyline, Property, Rectangle}, schema::{
-        Bus, BusEntry, ConnectionType, GlobalLabel, HierarchicalLabel, HierarchicalPin, HierarchicalSheet, Instance, Junction, LocalLabel, NetclassFlag, NoConnect, ProjectInstance, SchemaItem, Symbol, Text, TextBox, Wire
+        Bus, BusEntry, ConnectionType, GlobalLabel, HierarchicalLabel, HierarchicalPin, HierarchicalSheet, Instance, Junction, LocalLabel, NetclassFlag, NoConnect, ProjectInstance, RuleArea, SchemaItem, Symbol, Text, TextBox, Wire
     }, sexp::{constants::el, Sexp, SexpQuery, SexpString, SexpStringList, SexpTree, SexpValue}, symbols::{ElectricalTypes, LibrarySymbol, Pin, PinGraphicalStyle, PinProperty}, Error, Schema
 };
 
@@ -83,6 +83,9 @@ impl std::convert::From<SexpTree> for Result<Schema, Error> {
                 el::NETCLASS_FLAG => schema
                     .items
                     .push(SchemaItem::NetclassFlag(Into::<Result<NetclassFlag, Error>>::into(node)?)),
+                el::RULE_AREA => schema
+                    .items
+                    .push(SchemaItem::RuleArea(Into::<Result<RuleArea, Error>>::into(node)?)),
                 el::SHEET_INSTANCES => {
                     let path = node.query(el::PATH).next().unwrap();
                     schema.sheet_instances = vec![Instance {
@@ -365,6 +368,15 @@ impl std::convert::From<&Sexp> for Result<Polyline, Error> {
     }
 }
 
+impl std::convert::From<&Sexp> for Result<RuleArea, Error> {
+    fn from(sexp: &Sexp) -> Self {
+        let polyline = error_if_none!(sexp.query(el::POLYLINE).next(), "rule_area without polyline")?;
+        Ok(RuleArea {
+            polyline: Into::<Result<Polyline, Error>>::into(polyline)?,
+        })
+    }
+}
+
 impl std::convert::From<&Sexp> for Result<Rectangle, Error> {
     fn from(sexp: &Sexp) -> Self {
         Ok(Rectangle {
@@ -553,16 +565,19 @@ impl std::convert::From<&Sexp> for Result<Symbol, Error> {
                 .map(|p| (p.get(0).unwrap(), p.first(el::UUID).unwrap()))
                 .collect(),
             instances: {
-                if let Some(instances) = sexp.query(el::INSTANCES).next() {
-                    let project = instances.query(el::PROJECT).next().unwrap();
-                    let path = project.query(el::PATH).next().unwrap();
-                    vec![Instance {
-                        project: project.get(0).expect("mandatory field"),
-                        path: path.get(0).expect("mandatory field"),
-                        reference: path.first(el::REFERENCE).expect("mandatory field"),
-                        unit: path.first(el::SYMBOL_UNIT).expect("mandatory field"),
-                    }]
-                } else { vec![] }
+                //a sheet which is used more than once has a path per sheet instance
+                let mut instances = Vec::new();
+                for project in sexp.query(el::INSTANCES).flat_map(|i| i.query(el::PROJECT)) {
+                    for path in project.query(el::PATH) {
+                        instances.push(Instance {
+                            project: project.get(0).expect("mandatory field"),
+                            path: path.get(0).expect("mandatory field"),
+                            reference: path.first(el::REFERENCE).expect("mandatory field"),
+                            unit: path.first(el::SYMBOL_UNIT).expect("mandatory field"),
+                        });
+                    }
+                }
+                instances
             },
             attrs: To::new(),
         })
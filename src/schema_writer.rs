@@ -1,7 +1,7 @@
 use crate::{
     gr::{Color, Property}, round, schema::{
         Bus, BusEntry, GlobalLabel, HierarchicalLabel, HierarchicalPin, HierarchicalSheet,
-        Junction, LocalLabel, NetclassFlag, NoConnect, Symbol, Text, TextBox, Wire,
+        Junction, LocalLabel, NetclassFlag, NoConnect, RuleArea, Symbol, Text, TextBox, Wire,
     }, sexp::{builder::Builder, constants::el}, symbols::{LibrarySymbol, Pin}, yes_or_no, Error, SexpWrite
 };
 
@@ -246,7 +246,7 @@ impl SexpWrite for NetclassFlag {
         builder.push(el::AT);
         builder.value(&round(self.pos.x).to_string());
         builder.value(&round(self.pos.y).to_string());
-        builder.value(&(self.pos.angle / 255.0).to_string());
+        builder.value(&round(self.pos.angle).to_string());
         builder.end();
         if self.fields_autoplaced {
             builder.push(el::FIELDS_AUTOPLACED);
@@ -265,6 +265,15 @@ impl SexpWrite for NetclassFlag {
     }
 }
 
+impl SexpWrite for RuleArea {
+    fn write(&self, builder: &mut Builder) -> Result<(), Error> {
+        builder.push(el::RULE_AREA);
+        self.polyline.write(builder)?;
+        builder.end();
+        Ok(())
+    }
+}
+
 impl SexpWrite for NoConnect {
     fn write(&self, builder: &mut Builder) -> Result<(), Error> {
         builder.push(el::NO_CONNECT);
@@ -328,20 +337,30 @@ impl SexpWrite for Symbol {
             builder.end();
         }
 
-        for instance in &self.instances {
+        if !self.instances.is_empty() {
             builder.push(el::INSTANCES);
-            builder.push(el::PROJECT);
-            builder.text(&instance.project);
-            builder.push(el::PATH);
-            builder.text(&instance.path);
-            builder.push(el::REFERENCE);
-            builder.text(&instance.reference);
-            builder.end();
-            builder.push(el::SYMBOL_UNIT);
-            builder.value(&instance.unit.to_string());
-            builder.end();
-            builder.end();
-            builder.end();
+            let mut projects: Vec<&str> = Vec::new();
+            for instance in &self.instances {
+                if !projects.contains(&instance.project.as_str()) {
+                    projects.push(&instance.project);
+                }
+            }
+            for project in projects {
+                builder.push(el::PROJECT);
+                builder.text(project);
+                for instance in self.instances.iter().filter(|i| i.project == project) {
+                    builder.push(el::PATH);
+                    builder.text(&instance.path);
+                    builder.push(el::REFERENCE);
+                    builder.text(&instance.reference);
+                    builder.end();
+                    builder.push(el::SYMBOL_UNIT);
+                    builder.value(&instance.unit.to_string());
+                    builder.end();
+                    builder.end();
+                }
+                builder.end();
+            }
             builder.end();
         }
         builder.end();
@@ -65,12 +65,31 @@ pub mod el {
     pub const POWER: &str = "power";
     pub const PROJECT: &str = "project";
     pub const PROPERTY: &str = "property";
+    pub const PROPERTY_DATASHEET: &str = "Datasheet";
+    pub const PROPERTY_DESCRIPTION: &str = "Description";
+    pub const PROPERTY_FOOTPRINT: &str = "Footprint";
+    pub const PROPERTY_FP_FILTERS: &str = "ki_fp_filters";
+    pub const PROPERTY_NETCLASS: &str = "Netclass";
     pub const PROPERTY_REFERENCE: &str = "Reference";
+    pub const PROPERTY_SHEET_FILE: &str = "Sheetfile";
+    pub const PROPERTY_SHEET_NAME: &str = "Sheetname";
+    pub const PROPERTY_SIM_DEVICE: &str = "Sim.Device";
+    pub const PROPERTY_SIM_ENABLE: &str = "Sim.Enable";
+    pub const PROPERTY_SIM_NAME: &str = "Sim.Name";
+    pub const PROPERTY_SIM_PARAMS: &str = "Sim.Params";
+    pub const PROPERTY_SIM_PINS: &str = "Sim.Pins";
+    pub const PROPERTY_SIM_TYPE: &str = "Sim.Type";
+    pub const PROPERTY_SPICE_MODEL: &str = "Spice_Model";
+    pub const PROPERTY_SPICE_NETLIST_ENABLED: &str = "Spice_Netlist_Enabled";
+    pub const PROPERTY_SPICE_NODE_SEQUENCE: &str = "Spice_Node_Sequence";
+    pub const PROPERTY_SPICE_PRIMITIVE: &str = "Spice_Primitive";
+    pub const PROPERTY_TOLERANCE: &str = "Tolerance";
     pub const PROPERTY_VALUE: &str = "Value";
     pub const PTS: &str = "pts";
     pub const RADIUS: &str = "radius";
     pub const RECTANGLE: &str = "rectangle";
     pub const REFERENCE: &str = "reference";
+    pub const RULE_AREA: &str = "rule_area";
     pub const SEGMENT: &str = "segment";
     pub const SEXP: &str = "sexp";
     pub const SHAPE: &str = "shape";
@@ -0,0 +1,308 @@
+//! Run the simulation of a [`Circuit`].
+//!
+//! The [`Simulator`] trait takes a circuit and returns the simulation results
+//! as a [`RawFile`]. Available simulators:
+//!
+//! - [`Ngspice`] - run ngspice in batch mode.
+//! - [`MockSimulator`] - return predefined results, used for testing.
+//!
+//! ```
+//! use std::path::Path;
+//! use recad_core::{
+//!     Circuit,
+//!     circuit::Analysis,
+//!     rawfile::RawFile,
+//!     simulator::{MockSimulator, Simulator},
+//! };
+//!
+//! let mut circuit = Circuit::new(String::from("rc"), vec![]);
+//! circuit.resistor(String::from("R1"), String::from("IN"), String::from("OUT"), String::from("1k"));
+//! circuit.analysis(Analysis::Op).unwrap();
+//!
+//! let simulator = MockSimulator::new(RawFile::load(Path::new("tests/spice/rc.raw")).unwrap());
+//! let raw = simulator.run(&circuit).unwrap();
+//! assert!(raw.plot("Transient Analysis").is_some());
+//! ```
+use std::{
+    cell::RefCell,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use crate::{rawfile::RawFile, Circuit, Error};
+
+/// Simulate a circuit.
+///
+/// A circuit with steps is simulated for every variant, see [`Circuit::variants`],
+/// the rawfile contains the plots of all variants in the order of the variants.
+pub trait Simulator {
+    fn run(&self, circuit: &Circuit) -> Result<RawFile, Error>;
+}
+
+/// Simulate the variants of the circuit and collect the plots.
+fn run_variants(
+    circuit: &Circuit,
+    mut run: impl FnMut(&Circuit) -> Result<RawFile, Error>,
+) -> Result<RawFile, Error> {
+    let mut result = RawFile::default();
+    for variant in circuit.variants() {
+        result.plots.append(&mut run(&variant)?.plots);
+    }
+    Ok(result)
+}
+
+/// Run ngspice as a subprocess in batch mode.
+///
+/// The netlist and the rawfile are written to a temporary directory, which
+/// is removed after the simulation. When the process does not finish
+/// within the timeout it is killed.
+#[derive(Debug, Clone)]
+pub struct Ngspice {
+    command: PathBuf,
+    timeout: Duration,
+}
+
+impl Default for Ngspice {
+    fn default() -> Self {
+        Self {
+            command: PathBuf::from("ngspice"),
+            timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl Ngspice {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the ngspice executable, the default is `ngspice` from the path.
+    pub fn command(mut self, command: &Path) -> Self {
+        self.command = command.to_path_buf();
+        self
+    }
+
+    /// Set the timeout for the simulation, the default is 60 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl Simulator for Ngspice {
+    fn run(&self, circuit: &Circuit) -> Result<RawFile, Error> {
+        let dir = std::env::temp_dir().join(format!("recad-{}", crate::uuid!()));
+        std::fs::create_dir_all(&dir)?;
+        let result = run_variants(circuit, |variant| self.simulate(variant, &dir));
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+}
+
+impl Ngspice {
+    fn simulate(&self, circuit: &Circuit, dir: &Path) -> Result<RawFile, Error> {
+        let netlist = dir.join("netlist.cir");
+        let rawfile = dir.join("netlist.raw");
+        std::fs::write(&netlist, circuit.to_str(true)?.join("\n") + "\n")?;
+
+        let mut child = match Command::new(&self.command)
+            .arg("-b")
+            .arg("-r")
+            .arg(&rawfile)
+            .arg(&netlist)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => {
+                return Err(Error(
+                    String::from("ngspice"),
+                    format!("can not start {}: {}", self.command.display(), err),
+                ))
+            }
+        };
+
+        //read stderr in a thread, the process blocks when the pipe is full.
+        let mut stderr = child.stderr.take().unwrap();
+        let reader = std::thread::spawn(move || {
+            let mut output = String::new();
+            let _ = stderr.read_to_string(&mut output);
+            output
+        });
+
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if start.elapsed() > self.timeout {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(Error(
+                    String::from("timeout"),
+                    format!("simulation did not finish within {:?}", self.timeout),
+                ));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        let stderr = reader.join().unwrap_or_default();
+
+        if !status.success() || !rawfile.exists() {
+            return Err(Error(String::from("ngspice"), errors(&stderr, status.code())));
+        }
+        RawFile::load(&rawfile)
+    }
+}
+
+/// Get the error lines from the ngspice output, or the complete output
+/// when there is no line starting with `Error`.
+fn errors(stderr: &str, code: Option<i32>) -> String {
+    let lines = stderr
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| l.to_lowercase().starts_with("error"))
+        .collect::<Vec<&str>>();
+    let message = if lines.is_empty() {
+        stderr.trim().to_string()
+    } else {
+        lines.join("\n")
+    };
+    match code {
+        Some(code) if message.is_empty() => format!("exit status {}", code),
+        Some(code) => format!("exit status {}: {}", code, message),
+        None if message.is_empty() => String::from("terminated by signal"),
+        None => message,
+    }
+}
+
+/// A simulator which returns predefined results.
+///
+/// The netlists of the simulated circuits are recorded
+/// and can be inspected by the tests.
+#[derive(Debug, Default)]
+pub struct MockSimulator {
+    result: RawFile,
+    netlists: RefCell<Vec<String>>,
+}
+
+impl MockSimulator {
+    pub fn new(result: RawFile) -> Self {
+        Self {
+            result,
+            netlists: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// The netlists of the simulated circuits.
+    pub fn netlists(&self) -> Vec<String> {
+        self.netlists.borrow().clone()
+    }
+}
+
+impl Simulator for MockSimulator {
+    fn run(&self, circuit: &Circuit) -> Result<RawFile, Error> {
+        run_variants(circuit, |variant| {
+            self.netlists
+                .borrow_mut()
+                .push(variant.to_str(true)?.join("\n"));
+            Ok(self.result.clone())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use super::{MockSimulator, Ngspice, Simulator};
+    use crate::{
+        circuit::{Analysis, Step},
+        rawfile::RawFile,
+        Circuit,
+    };
+
+    fn circuit(value: &str) -> Circuit {
+        let mut circuit = Circuit::new(String::from("rc"), vec![]);
+        circuit.resistor(String::from("R1"), String::from("IN"), String::from("OUT"), value.to_string());
+        circuit.capacitor(String::from("C1"), String::from("OUT"), String::from("GND"), String::from("1u"));
+        circuit.analysis(Analysis::Op).unwrap();
+        circuit
+    }
+
+    fn ngspice() -> Ngspice {
+        Ngspice::new().command(Path::new("tests/spice/ngspice.sh"))
+    }
+
+    #[test]
+    fn run_ngspice() {
+        let raw = ngspice().run(&circuit("1k")).unwrap();
+        assert_eq!(RawFile::load(Path::new("tests/spice/rc_binary.raw")).unwrap(), raw);
+    }
+
+    #[test]
+    fn ngspice_error() {
+        let err = ngspice().run(&circuit("error")).unwrap_err();
+        assert_eq!("ngspice", err.0);
+        assert_eq!(
+            "exit status 1: Error on line 2 : r1 in out error\nError: circuit not parsed.",
+            err.1
+        );
+    }
+
+    #[test]
+    fn ngspice_timeout() {
+        let err = ngspice()
+            .timeout(Duration::from_millis(200))
+            .run(&circuit("timeout"))
+            .unwrap_err();
+        assert_eq!("timeout", err.0);
+    }
+
+    #[test]
+    fn ngspice_step() {
+        let mut stepped = circuit("{r}");
+        stepped.param("r", "1k").unwrap();
+        stepped.step(Step::List { param: String::from("r"), values: vec![1e3, 2e3] }).unwrap();
+        let raw = ngspice().run(&stepped).unwrap();
+        let single = RawFile::load(Path::new("tests/spice/rc_binary.raw")).unwrap();
+        assert_eq!(2 * single.plots.len(), raw.plots.len());
+    }
+
+    #[test]
+    fn mock_step() {
+        let mut stepped = circuit("{r}");
+        stepped.param("r", "1k").unwrap();
+        stepped.step(Step::List { param: String::from("r"), values: vec![1e3, 2e3] }).unwrap();
+        let simulator = MockSimulator::new(RawFile::load(Path::new("tests/spice/rc.raw")).unwrap());
+        simulator.run(&stepped).unwrap();
+        let netlists = simulator.netlists();
+        assert_eq!(2, netlists.len());
+        assert!(netlists[0].contains(".param r=1000\n"));
+        assert!(netlists[1].contains(".param r=2000\n"));
+        assert!(!netlists[1].contains(".step"));
+    }
+
+    #[test]
+    fn ngspice_not_found() {
+        let err = Ngspice::new()
+            .command(Path::new("tests/spice/missing"))
+            .run(&circuit("1k"))
+            .unwrap_err();
+        assert_eq!("ngspice", err.0);
+    }
+
+    #[test]
+    fn mock() {
+        let raw = RawFile::load(Path::new("tests/spice/rc.raw")).unwrap();
+        let simulator = MockSimulator::new(raw.clone());
+        assert_eq!(raw, simulator.run(&circuit("1k")).unwrap());
+        let netlists = simulator.netlists();
+        assert_eq!(1, netlists.len());
+        assert!(netlists[0].contains("R1 IN OUT 1k"));
+        assert!(netlists[0].contains(".op"));
+    }
+}
@@ -3,7 +3,7 @@ use std::fmt::Display;
 use crate::{gr::{Effects, Pos, Property}, schema::Symbol, sexp::constants::el};
 
 ///The symbol token defines a symbol or sub-unit of a parent symbol
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct LibrarySymbol {
     ///Each symbol must have a unique "LIBRARY_ID" for each top level symbol in the library
     ///or a unique "UNIT_ID" for each unit embedded in a parent symbol. Library identifiers
@@ -63,8 +63,7 @@ impl LibrarySymbol {
     ///  represents. A `unit` value of zero (0) implies that the
     ///  symbol is universal across all units.
     pub fn unit(&self) -> u8 {
-        let splits = self.lib_id.split('_').collect::<Vec<&str>>();
-        splits.get(splits.len() - 2).unwrap().parse::<u8>().unwrap()
+        self.lib_id.rsplit('_').nth(1).unwrap().parse::<u8>().unwrap()
     }
 
     ///The `style` indicates which body style the unit represents.
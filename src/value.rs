@@ -0,0 +1,447 @@
+//! Component values with SI prefixes.
+//!
+//! The values of the schematic symbols are written in many notations: `4.7k`, `4k7`,
+//! `100nF`, `0.1u`, `1meg` or `10R`. A [`Value`] parses these notations and compares
+//! the numbers, `100nF` and `0.1u` are the same value. The unit is optional, values
+//! with different units are not equal.
+//!
+//! The KiCad notation is case sensitive, `M` is mega and `m` is milli. The spice notation
+//! is not case sensitive, `m` is milli and `meg` is mega, see [`Value::parse_spice`].
+//!
+//! ```
+//! use recad_core::value::{ESeries, Value};
+//!
+//! let value: Value = "4k7".parse().unwrap();
+//! assert_eq!(4700.0, value.value);
+//! assert_eq!("4.7k", value.to_string());
+//! assert_eq!("100n".parse::<Value>().unwrap(), "0.1uF".parse::<Value>().unwrap());
+//!
+//! let value: Value = "1M".parse().unwrap();
+//! assert_eq!("1Meg", value.spice());
+//! assert_eq!("4.7k", Value::new(4.9e3).snap(ESeries::E12).to_string());
+//! ```
+use std::{fmt, str::FromStr};
+
+use crate::Error;
+
+/// The SI prefixes with the KiCad and spice notation.
+const PREFIXES: [(f64, &str, &str); 10] = [
+    (1e12, "T", "T"),
+    (1e9, "G", "G"),
+    (1e6, "M", "Meg"),
+    (1e3, "k", "k"),
+    (1.0, "", ""),
+    (1e-3, "m", "m"),
+    (1e-6, "u", "u"),
+    (1e-9, "n", "n"),
+    (1e-12, "p", "p"),
+    (1e-15, "f", "f"),
+];
+
+/// The relative difference of two equal values.
+const EPSILON: f64 = 1e-9;
+
+/// The unit of a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Ohm,
+    Farad,
+    Henry,
+    Volt,
+    Ampere,
+    Hertz,
+    Watt,
+    Second,
+}
+
+impl Unit {
+    /// The unit symbol.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Unit::Ohm => "Ω",
+            Unit::Farad => "F",
+            Unit::Henry => "H",
+            Unit::Volt => "V",
+            Unit::Ampere => "A",
+            Unit::Hertz => "Hz",
+            Unit::Watt => "W",
+            Unit::Second => "s",
+        }
+    }
+
+    fn parse(unit: &str, spice: bool) -> Option<Self> {
+        if spice {
+            return match unit.to_lowercase().as_str() {
+                "ω" | "ohm" | "r" => Some(Unit::Ohm),
+                "f" => Some(Unit::Farad),
+                "h" => Some(Unit::Henry),
+                "v" => Some(Unit::Volt),
+                "a" => Some(Unit::Ampere),
+                "hz" => Some(Unit::Hertz),
+                "w" => Some(Unit::Watt),
+                "s" => Some(Unit::Second),
+                _ => None,
+            };
+        }
+        match unit {
+            "Ω" | "Ω" | "Ohm" | "ohm" | "R" | "r" => Some(Unit::Ohm),
+            "F" => Some(Unit::Farad),
+            "H" => Some(Unit::Henry),
+            "V" => Some(Unit::Volt),
+            "A" => Some(Unit::Ampere),
+            "Hz" => Some(Unit::Hertz),
+            "W" => Some(Unit::Watt),
+            "s" => Some(Unit::Second),
+            _ => None,
+        }
+    }
+}
+
+/// The E-series of preferred numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ESeries {
+    E6,
+    E12,
+    E24,
+    E96,
+}
+
+impl ESeries {
+    /// The values of the series in the decade from 1 to 10.
+    pub fn values(&self) -> &'static [f64] {
+        match self {
+            ESeries::E6 => &[1.0, 1.5, 2.2, 3.3, 4.7, 6.8],
+            ESeries::E12 => &[1.0, 1.2, 1.5, 1.8, 2.2, 2.7, 3.3, 3.9, 4.7, 5.6, 6.8, 8.2],
+            ESeries::E24 => &[
+                1.0, 1.1, 1.2, 1.3, 1.5, 1.6, 1.8, 2.0, 2.2, 2.4, 2.7, 3.0, 3.3, 3.6, 3.9, 4.3,
+                4.7, 5.1, 5.6, 6.2, 6.8, 7.5, 8.2, 9.1,
+            ],
+            ESeries::E96 => &[
+                1.00, 1.02, 1.05, 1.07, 1.10, 1.13, 1.15, 1.18, 1.21, 1.24, 1.27, 1.30, 1.33,
+                1.37, 1.40, 1.43, 1.47, 1.50, 1.54, 1.58, 1.62, 1.65, 1.69, 1.74, 1.78, 1.82,
+                1.87, 1.91, 1.96, 2.00, 2.05, 2.10, 2.15, 2.21, 2.26, 2.32, 2.37, 2.43, 2.49,
+                2.55, 2.61, 2.67, 2.74, 2.80, 2.87, 2.94, 3.01, 3.09, 3.16, 3.24, 3.32, 3.40,
+                3.48, 3.57, 3.65, 3.74, 3.83, 3.92, 4.02, 4.12, 4.22, 4.32, 4.42, 4.53, 4.64,
+                4.75, 4.87, 4.99, 5.11, 5.23, 5.36, 5.49, 5.62, 5.76, 5.90, 6.04, 6.19, 6.34,
+                6.49, 6.65, 6.81, 6.98, 7.15, 7.32, 7.50, 7.68, 7.87, 8.06, 8.25, 8.45, 8.66,
+                8.87, 9.09, 9.31, 9.53, 9.76,
+            ],
+        }
+    }
+}
+
+/// A component value with an optional unit.
+#[derive(Debug, Clone, Copy)]
+pub struct Value {
+    pub value: f64,
+    pub unit: Option<Unit>,
+}
+
+impl Value {
+    pub fn new(value: f64) -> Self {
+        Self { value, unit: None }
+    }
+
+    /// Set the unit of the value.
+    pub fn unit(mut self, unit: Unit) -> Self {
+        self.unit = Some(unit);
+        self
+    }
+
+    /// Parse a value in the spice notation, the prefixes are not case sensitive
+    /// and unknown units are ignored like spice does: `1M` is `1m` and `10Volt` is `10`.
+    pub fn parse_spice(value: &str) -> Result<Self, Error> {
+        parse(value, true)
+    }
+
+    /// Format the value for the spice netlist: `4.7k`, `1Meg`, `100n`.
+    pub fn spice(&self) -> String {
+        let (number, _, prefix) = prefixed(self.value);
+        format!("{}{}", number, prefix)
+    }
+
+    /// Format the value in the KiCad notation: `4.7k`, `1M`, `100nF`. The unit
+    /// of resistors is not written.
+    pub fn kicad(&self) -> String {
+        let (number, prefix, _) = prefixed(self.value);
+        let unit = match self.unit {
+            None | Some(Unit::Ohm) => "",
+            Some(unit) => unit.symbol(),
+        };
+        format!("{}{}{}", number, prefix, unit)
+    }
+
+    /// Get the nearest value of the E-series.
+    pub fn snap(&self, series: ESeries) -> Self {
+        if self.value == 0.0 || !self.value.is_finite() {
+            return *self;
+        }
+        let decade = 10f64.powf(self.value.abs().log10().floor());
+        let normalized = self.value.abs() / decade;
+        let distance = |v: &f64| (normalized / v).ln().abs();
+        let nearest = series
+            .values()
+            .iter()
+            .chain([10.0].iter())
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .unwrap();
+        Self {
+            value: (nearest * decade).copysign(self.value),
+            unit: self.unit,
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::new(value)
+    }
+}
+
+impl FromStr for Value {
+    type Err = Error;
+
+    /// Parse a value in the KiCad notation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s, false)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kicad())
+    }
+}
+
+/// The values are equal when the numbers are equal and the units are equal
+/// or one of the values has no unit.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        let unit = self.unit.is_none() || other.unit.is_none() || self.unit == other.unit;
+        unit && (self.value - other.value).abs()
+            <= EPSILON * self.value.abs().max(other.value.abs())
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        if self == other {
+            Some(std::cmp::Ordering::Equal)
+        } else {
+            self.value.partial_cmp(&other.value)
+        }
+    }
+}
+
+/// Split the value into the number and the KiCad and spice prefix.
+fn prefixed(value: f64) -> (String, &'static str, &'static str) {
+    if value == 0.0 || !value.is_finite() {
+        return (value.to_string(), "", "");
+    }
+    //remove the rounding errors
+    let value: f64 = format!("{:.11e}", value).parse().unwrap();
+    let (scale, kicad, spice) = PREFIXES
+        .iter()
+        .find(|(scale, _, _)| value.abs() >= *scale)
+        .unwrap_or(&PREFIXES[PREFIXES.len() - 1]);
+    let number: f64 = format!("{:.9}", value / scale).parse().unwrap();
+    (number.to_string(), kicad, spice)
+}
+
+/// The exponent of a prefix character.
+fn exponent(c: char, spice: bool) -> Option<i32> {
+    if spice {
+        return match c.to_ascii_lowercase() {
+            't' => Some(12),
+            'g' => Some(9),
+            'k' => Some(3),
+            'm' => Some(-3),
+            'u' | 'µ' | 'μ' => Some(-6),
+            'n' => Some(-9),
+            'p' => Some(-12),
+            'f' => Some(-15),
+            'a' => Some(-18),
+            _ => None,
+        };
+    }
+    match c {
+        'T' => Some(12),
+        'G' => Some(9),
+        'M' => Some(6),
+        'k' | 'K' => Some(3),
+        'm' => Some(-3),
+        'u' | 'µ' | 'μ' => Some(-6),
+        'n' => Some(-9),
+        'p' => Some(-12),
+        'f' => Some(-15),
+        _ => None,
+    }
+}
+
+fn parse(input: &str, spice: bool) -> Result<Value, Error> {
+    let error = || Error(String::from("value"), format!("invalid value '{}'", input));
+    let text = input.trim();
+
+    //the number, the exponent must be followed by a digit.
+    let end = text
+        .char_indices()
+        .find(|(i, c)| {
+            !(c.is_ascii_digit()
+                || *c == '.'
+                || ((*c == '-' || *c == '+') && (*i == 0 || text[..*i].ends_with(['e', 'E'])))
+                || ((*c == 'e' || *c == 'E')
+                    && text[i + 1..]
+                        .trim_start_matches(['-', '+'])
+                        .starts_with(|n: char| n.is_ascii_digit())))
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(text.len());
+    let (mut number, rest) = text.split_at(end);
+    //RKM notation without the integer part: `R47`
+    if number.is_empty() && rest.starts_with(['R', 'r']) {
+        number = "0";
+    }
+    let integer = !number.is_empty() && number.chars().all(|c| c.is_ascii_digit());
+    let mut value: f64 = number.parse().map_err(|_| error())?;
+    let rest = rest.trim_start();
+
+    //the prefix, the RKM notation uses the prefix as decimal mark: `4k7`, `2R2`.
+    let lower = rest.to_lowercase();
+    let (exponent, rest) = if lower.starts_with("meg") {
+        (6, &rest[3..])
+    } else if spice && lower.starts_with("mil") {
+        value *= 25.4;
+        (-6, &rest[3..])
+    } else {
+        let mut chars = rest.chars();
+        match chars.next() {
+            Some(c) => {
+                let after = chars.as_str();
+                let fraction = after
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(after.len());
+                let rkm = integer && fraction > 0;
+                let scale = if matches!(c, 'R' | 'r') && rkm {
+                    Some(0)
+                } else {
+                    exponent(c, spice)
+                };
+                match scale {
+                    Some(exponent) if rkm => {
+                        value = format!("{}.{}", number, &after[..fraction])
+                            .parse()
+                            .map_err(|_| error())?;
+                        (exponent, &after[fraction..])
+                    }
+                    Some(exponent) => (exponent, after),
+                    None => (0, rest),
+                }
+            }
+            None => (0, rest),
+        }
+    };
+
+    let rest = rest.trim();
+    let unit = if rest.is_empty() {
+        None
+    } else {
+        match Unit::parse(rest, spice) {
+            Some(unit) => Some(unit),
+            //spice ignores the letters after the number.
+            None if spice && rest.starts_with(|c: char| c.is_alphabetic()) => None,
+            None => return Err(error()),
+        }
+    };
+    //divide by the power of ten, 10 * 1e-6 is not 1e-5.
+    let value = if exponent < 0 {
+        value / 10f64.powi(-exponent)
+    } else {
+        value * 10f64.powi(exponent)
+    };
+    Ok(Value {
+        value,
+        unit,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ESeries, Unit, Value};
+
+    fn value(s: &str) -> Value {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(4700.0, value("4k7").value);
+        assert_eq!(4700.0, value("4.7k").value);
+        assert_eq!(2.2, value("2R2").value);
+        assert_eq!(0.47, value("R47").value);
+        assert_eq!(4.7e6, value("4M7").value);
+        assert_eq!(1e6, value("1meg").value);
+        assert_eq!(1e6, value("1M").value);
+        assert_eq!(1e-3, value("1m").value);
+        assert_eq!(1e-5, value("10 µF").value);
+        assert_eq!(1e-7, value("100n").value);
+        assert_eq!(1.5e-9, value("1.5e-9").value);
+        assert_eq!(-15.0, value("-15V").value);
+        assert_eq!(Some(Unit::Farad), value("2.2uF").unit);
+        assert_eq!(Some(Unit::Ohm), value("10R").unit);
+        assert_eq!(10.0, value("10R").value);
+        assert_eq!(Some(Unit::Ohm), value("1kOhm").unit);
+        assert_eq!(Some(Unit::Hertz), value("10kHz").unit);
+        assert!("TL072".parse::<Value>().is_err());
+        assert!("4.7k7".parse::<Value>().is_err());
+        assert!("10k 1%".parse::<Value>().is_err());
+        assert!("".parse::<Value>().is_err());
+    }
+
+    #[test]
+    fn parse_spice() {
+        assert_eq!(1e-3, Value::parse_spice("1M").unwrap().value);
+        assert_eq!(2.2e6, Value::parse_spice("2.2MEG").unwrap().value);
+        assert_eq!(1e-15, Value::parse_spice("1F").unwrap().value);
+        assert_eq!(10.0, Value::parse_spice("10Volt").unwrap().value);
+        assert_eq!(Some(Unit::Ohm), Value::parse_spice("10Ohm").unwrap().unit);
+        assert!(Value::parse_spice("VSIN").is_err());
+    }
+
+    #[test]
+    fn equal() {
+        assert_eq!(value("100nF"), value("0.1u"));
+        assert_eq!(value("4k7"), value("4.7k"));
+        assert_eq!(value("4k7"), Value::new(4.7e3));
+        assert_ne!(value("100nF"), value("100nH"));
+        assert_ne!(value("100n"), value("10n"));
+        assert!(value("1k") < value("1.1k"));
+    }
+
+    #[test]
+    fn format() {
+        assert_eq!("4.7k", value("4k7").to_string());
+        assert_eq!("100nF", value("0.1uF").kicad());
+        assert_eq!("100n", value("0.1uF").spice());
+        assert_eq!("1M", value("1meg").kicad());
+        assert_eq!("1Meg", value("1M").spice());
+        assert_eq!("10", value("10R").kicad());
+        assert_eq!("1m", value("0.001").spice());
+        assert_eq!("-15V", value("-15V").kicad());
+        assert_eq!("0", value("0").spice());
+        assert_eq!("999", Value::new(999.0).spice());
+        assert_eq!("1k", Value::new(999.999_999_999_9).spice());
+        assert_eq!("330p", Value::new(0.33e-9).spice());
+    }
+
+    #[test]
+    fn snap() {
+        assert_eq!(value("4.7k"), Value::new(4.9e3).snap(ESeries::E12));
+        assert_eq!(value("5.1k"), Value::new(4.9e3).snap(ESeries::E24));
+        assert_eq!(value("4.87k"), Value::new(4.9e3).snap(ESeries::E96));
+        assert_eq!(value("10k"), Value::new(9.0e3).snap(ESeries::E6));
+        assert_eq!(value("100n"), value("0.11u").snap(ESeries::E6));
+        assert_eq!(Some(Unit::Farad), value("110nF").snap(ESeries::E6).unit);
+        assert_eq!(96, ESeries::E96.values().len());
+        assert_eq!(24, ESeries::E24.values().len());
+    }
+}
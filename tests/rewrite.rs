@@ -1,7 +1,7 @@
 mod tests {
     mod rewrite {
         use colored::Colorize;
-        use std::path::Path;
+        use std::path::{Path, PathBuf};
 
         use similar::{ChangeTag, TextDiff};
         fn init() {
@@ -92,5 +92,38 @@ mod tests {
             }
             assert_eq!(diffs, 16);
         }
+
+        /// Collect all `tests/**/*.kicad_sch` files.
+        fn schemas(dir: &Path, files: &mut Vec<PathBuf>) {
+            for entry in std::fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    schemas(&path, files);
+                } else if path.extension().is_some_and(|ext| ext == "kicad_sch") {
+                    files.push(path);
+                }
+            }
+        }
+
+        #[test]
+        fn round_trip() {
+            init();
+
+            let mut fixtures = Vec::new();
+            schemas(Path::new("tests"), &mut fixtures);
+            fixtures.sort();
+            assert!(fixtures.len() > 10);
+
+            for left in &fixtures {
+                let name = left.to_string_lossy().replace('/', "_");
+                let right = Path::new("/tmp/recad").join(name);
+
+                let schema = recad_core::Schema::load(left).unwrap();
+                schema.save(&right).unwrap();
+                let reloaded = recad_core::Schema::load(&right).unwrap();
+
+                assert_eq!(schema, reloaded, "round trip failed for {}", left.display());
+            }
+        }
     }
 }
@@ -0,0 +1,15 @@
+#!/bin/sh
+# Stand-in for ngspice in the simulator tests: ngspice.sh -b -r <rawfile> <netlist>
+# The value of R1 selects the behaviour.
+case "$(grep "^R1 " "$4")" in
+    *timeout*)
+        sleep 5
+        ;;
+    *error*)
+        echo "Note: No compatibility mode selected!" >&2
+        echo "Error on line 2 : r1 in out error" >&2
+        echo "Error: circuit not parsed." >&2
+        exit 1
+        ;;
+esac
+cp "$(dirname "$0")/rc_binary.raw" "$3"